@@ -0,0 +1,203 @@
+use crate::clock::{division_to_samples, NoteEvent};
+use crate::rng::Rng;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ArpMode {
+    Up = 0,
+    Down,
+    UpDown,
+    Random,
+}
+
+// Steps through the held notes at a tempo-synced rate; the engine asks how long
+// until the next event, renders up to it, then fires it
+pub struct Arpeggiator {
+    enabled: bool,
+    mode: ArpMode,
+    division: u8,
+    held: Vec<(u8, f32)>, // (note, velocity), sorted by pitch
+    position: usize,
+    sounding: Option<u8>,
+    samples_to_step: f32,
+    samples_to_release: f32,
+    gate: f32, // Fraction of the step the note is held for
+    rng: Rng,
+    sample_rate: f32,
+}
+
+impl Arpeggiator {
+    pub fn new(sample_rate: f32) -> Self {
+        Arpeggiator {
+            enabled: false,
+            mode: ArpMode::Up,
+            division: 4,
+            held: Vec::new(),
+            position: 0,
+            sounding: None,
+            samples_to_step: 0.0,
+            samples_to_release: 0.0,
+            gate: 0.5,
+            rng: Rng::next_default(),
+            sample_rate,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled != enabled {
+            self.held.clear();
+            self.sounding = None;
+        }
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_mode(&mut self, mode: u8) {
+        self.mode = match mode {
+            1 => ArpMode::Down,
+            2 => ArpMode::UpDown,
+            3 => ArpMode::Random,
+            _ => ArpMode::Up,
+        };
+    }
+
+    pub fn set_division(&mut self, division: u8) {
+        self.division = division;
+    }
+
+    pub fn get_mode(&self) -> u8 {
+        self.mode as u8
+    }
+
+    pub fn get_division(&self) -> u8 {
+        self.division
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng.set_seed(seed);
+    }
+
+    pub fn note_on(&mut self, note: u8, velocity: f32) {
+        if self.held.is_empty() {
+            // First key starts the pattern straight away
+            self.position = 0;
+            self.samples_to_step = 0.0;
+        }
+        match self.held.binary_search_by_key(&note, |&(n, _)| n) {
+            Ok(idx) => self.held[idx].1 = velocity,
+            Err(idx) => self.held.insert(idx, (note, velocity)),
+        }
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        self.held.retain(|&(n, _)| n != note);
+    }
+
+    // Whole samples that can be rendered before the next event is due
+    pub fn samples_until_event(&self) -> usize {
+        let mut until = f32::MAX;
+        if self.sounding.is_some() {
+            until = if self.held.is_empty() { 0.0 } else { self.samples_to_release };
+        }
+        if !self.held.is_empty() {
+            until = until.min(self.samples_to_step);
+        }
+        if until == f32::MAX {
+            usize::MAX
+        } else {
+            until.max(0.0).ceil() as usize
+        }
+    }
+
+    pub fn advance(&mut self, samples: usize) {
+        self.samples_to_step -= samples as f32;
+        self.samples_to_release -= samples as f32;
+    }
+
+    // Emit whatever is due now
+    pub fn fire(&mut self, bpm: f32, events: &mut Vec<NoteEvent>) {
+        if let Some(note) = self.sounding {
+            if self.held.is_empty() || self.samples_to_release <= 0.0 || self.samples_to_step <= 0.0 {
+                events.push(NoteEvent::NoteOff(note));
+                self.sounding = None;
+            }
+        }
+
+        if !self.held.is_empty() && self.samples_to_step <= 0.0 {
+            let index = self.next_index();
+            let (note, velocity) = self.held[index];
+            events.push(NoteEvent::NoteOn(note, velocity));
+            self.sounding = Some(note);
+
+            let step = division_to_samples(self.division, bpm, self.sample_rate).max(1.0);
+            self.samples_to_step += step;
+            self.samples_to_release = self.samples_to_step - step * (1.0 - self.gate);
+        }
+    }
+
+    fn next_index(&mut self) -> usize {
+        let count = self.held.len();
+        let index = match self.mode {
+            ArpMode::Up => self.position % count,
+            ArpMode::Down => count - 1 - (self.position % count),
+            ArpMode::UpDown => {
+                // Bounce between the ends without repeating them
+                let cycle = (2 * count).saturating_sub(2).max(1);
+                let pos = self.position % cycle;
+                if pos < count { pos } else { cycle - pos }
+            }
+            ArpMode::Random => ((self.rng.random() * count as f32) as usize).min(count - 1),
+        };
+        self.position = self.position.wrapping_add(1);
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs the arpeggiator the way the engine does, returning the events in order
+    fn run(arp: &mut Arpeggiator, events: usize) -> Vec<NoteEvent> {
+        let mut fired = Vec::new();
+        while fired.len() < events {
+            arp.fire(120.0, &mut fired);
+            let until = arp.samples_until_event();
+            if until == usize::MAX {
+                break;
+            }
+            arp.advance(until);
+        }
+        fired
+    }
+
+    fn note_ons(events: &[NoteEvent]) -> Vec<u8> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                NoteEvent::NoteOn(note, _) => Some(*note),
+                NoteEvent::NoteOff(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn up_pattern_cycles_through_held_notes_in_ascending_order() {
+        let mut arp = Arpeggiator::new(48000.0);
+        arp.set_enabled(true);
+        for note in [67, 60, 64] {
+            arp.note_on(note, 0.8);
+        }
+        let events = run(&mut arp, 12);
+        assert_eq!(note_ons(&events), [60, 64, 67, 60, 64, 67]);
+        // Every note is released before the next one starts
+        assert!(events.chunks(2).all(|pair| matches!(pair, [NoteEvent::NoteOn(on, _), NoteEvent::NoteOff(off)] if on == off)));
+
+        for note in [60, 64, 67] {
+            arp.note_off(note);
+        }
+        assert!(note_ons(&run(&mut arp, 4)).is_empty());
+    }
+}
@@ -0,0 +1,34 @@
+// Tempo helpers shared by everything synced to the engine BPM
+
+pub const DEFAULT_TEMPO_BPM: f32 = 120.0;
+
+// Notes emitted by the clocked note sources (arpeggiator, sequencer)
+pub enum NoteEvent {
+    NoteOn(u8, f32),
+    NoteOff(u8),
+}
+
+// Note divisions in beats (quarter notes):
+// 0 = 1/1, 1 = 1/2, 2 = 1/4, 3 = 1/8, 4 = 1/16, 5 = 1/32,
+// 6 = 1/4 dotted, 7 = 1/8 dotted, 8 = 1/16 dotted,
+// 9 = 1/4 triplet, 10 = 1/8 triplet, 11 = 1/16 triplet
+pub fn division_to_beats(division: u8) -> f32 {
+    match division {
+        0 => 4.0,
+        1 => 2.0,
+        2 => 1.0,
+        3 => 0.5,
+        5 => 0.125,
+        6 => 1.5,
+        7 => 0.75,
+        8 => 0.375,
+        9 => 2.0 / 3.0,
+        10 => 1.0 / 3.0,
+        11 => 1.0 / 6.0,
+        _ => 0.25,
+    }
+}
+
+pub fn division_to_samples(division: u8, bpm: f32, sample_rate: f32) -> f32 {
+    division_to_beats(division) * 60.0 / bpm * sample_rate
+}
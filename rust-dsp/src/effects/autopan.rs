@@ -0,0 +1,75 @@
+use crate::lfo::Lfo;
+use std::f32::consts::{FRAC_PI_4, SQRT_2};
+
+// Sweeps the signal between the left and right channels with an equal-power
+// pan law, scaled so the centre position keeps the mono level on both sides
+pub struct AutoPan {
+    lfo: Lfo,
+    rate: f32,
+    depth: f32,
+}
+
+impl AutoPan {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut lfo = Lfo::new(sample_rate);
+        lfo.set_rate(0.5);
+        lfo.set_depth(1.0);
+        AutoPan {
+            lfo,
+            rate: 0.5,
+            depth: 1.0,
+        }
+    }
+
+    pub fn set_rate(&mut self, rate_hz: f32) {
+        self.rate = rate_hz;
+        self.lfo.set_rate(rate_hz);
+    }
+
+    // 0 = stays centred, 1 = swings hard left to hard right
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    pub fn get_rate(&self) -> f32 {
+        self.rate
+    }
+
+    pub fn get_depth(&self) -> f32 {
+        self.depth
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.lfo.set_seed(seed);
+    }
+
+    // Works as a balance control on a stereo pair; a mono pair pans normally
+    pub fn process(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let position = self.lfo.process() * self.depth;
+        let angle = (position + 1.0) * FRAC_PI_4;
+        (left * angle.cos() * SQRT_2, right * angle.sin() * SQRT_2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_energies_cross_over_within_a_cycle() {
+        let mut autopan = AutoPan::new(48000.0);
+        autopan.set_rate(10.0);
+        let output: Vec<(f32, f32)> = (0..4800).map(|_| autopan.process(1.0, 1.0)).collect();
+
+        // Energy per half of the cycle: one side leads in each, and they swap
+        let energies: Vec<(f32, f32)> = output
+            .chunks(2400)
+            .map(|half| half.iter().fold((0.0, 0.0), |(l, r), &(left, right)| (l + left * left, r + right * right)))
+            .collect();
+        let lead = |(l, r): (f32, f32)| l - r;
+        assert!(lead(energies[0]) * lead(energies[1]) < 0.0);
+        assert!(lead(energies[0]).abs() > 1000.0 && lead(energies[1]).abs() > 1000.0);
+        // Equal power: the pair keeps the level of both channels at the centre
+        assert!(output.iter().all(|&(l, r)| (l * l + r * r - 2.0).abs() < 1e-4));
+    }
+}
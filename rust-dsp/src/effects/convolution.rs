@@ -0,0 +1,202 @@
+use crate::spectrum::fft;
+
+const BLOCK: usize = 128;
+const FFT_SIZE: usize = BLOCK * 2;
+const BINS: usize = BLOCK + 1; // Up to Nyquist; the rest mirror them for real signals
+const MAX_IR_SECONDS: f32 = 3.0;
+
+// Convolution with a loaded impulse response, without added latency: the first
+// BLOCK taps run as a direct FIR, and every later block of the IR is an
+// overlap-save partition whose output is ready before its first sample is due.
+// Cost is an FFT pair per BLOCK samples plus one complex multiply-add per bin
+// and partition, so a 2 s IR at 48 kHz is ~750 partitions.
+pub struct Convolution {
+    head: Vec<f32>,          // IR taps 0..BLOCK, run directly
+    history: Vec<f32>,       // Last BLOCK inputs for the head, as a ring
+    history_pos: usize,
+    partitions: Vec<Vec<(f32, f32)>>, // Spectra of IR blocks 1.. (bins 0..=BLOCK)
+    spectra: Vec<Vec<(f32, f32)>>,    // Spectra of past input block pairs, as a ring
+    newest: usize,
+    previous_block: Vec<f32>,
+    current_block: Vec<f32>,
+    tail: Vec<f32>, // Output of the partitions for the block being played
+    block_pos: usize,
+    re: Vec<f32>,
+    im: Vec<f32>,
+    mix: f32,
+    sample_rate: f32,
+}
+
+impl Convolution {
+    pub fn new(sample_rate: f32) -> Self {
+        Convolution {
+            head: vec![0.0; BLOCK],
+            history: vec![0.0; BLOCK],
+            history_pos: 0,
+            partitions: Vec::new(),
+            spectra: Vec::new(),
+            newest: 0,
+            previous_block: vec![0.0; BLOCK],
+            current_block: vec![0.0; BLOCK],
+            tail: vec![0.0; BLOCK],
+            block_pos: 0,
+            re: vec![0.0; FFT_SIZE],
+            im: vec![0.0; FFT_SIZE],
+            mix: 0.3,
+            sample_rate,
+        }
+    }
+
+    // The IR is taken at the engine sample rate and cut off after 3 s.
+    // Loading clears the running tail
+    pub fn load_ir(&mut self, samples: &[f32]) {
+        let max_len = (MAX_IR_SECONDS * self.sample_rate) as usize;
+        let ir = &samples[..samples.len().min(max_len)];
+
+        self.head.fill(0.0);
+        let head_len = ir.len().min(BLOCK);
+        self.head[..head_len].copy_from_slice(&ir[..head_len]);
+
+        self.partitions.clear();
+        for block in ir.chunks(BLOCK).skip(1) {
+            self.re.fill(0.0);
+            self.im.fill(0.0);
+            self.re[..block.len()].copy_from_slice(block);
+            fft(&mut self.re, &mut self.im);
+            self.partitions.push((0..BINS).map(|k| (self.re[k], self.im[k])).collect());
+        }
+        self.spectra = vec![vec![(0.0, 0.0); BINS]; self.partitions.len()];
+        self.reset();
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+
+    pub fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.history_pos = 0;
+        for spectrum in &mut self.spectra {
+            spectrum.fill((0.0, 0.0));
+        }
+        self.newest = 0;
+        self.previous_block.fill(0.0);
+        self.current_block.fill(0.0);
+        self.tail.fill(0.0);
+        self.block_pos = 0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let wet = self.process_wet(input);
+        input * (1.0 - self.mix) + wet * self.mix
+    }
+
+    // Mono IR: the mid signal is convolved and the result goes to both sides
+    pub fn process_stereo(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let wet = self.process_wet((left + right) * 0.5) * self.mix;
+        (left * (1.0 - self.mix) + wet, right * (1.0 - self.mix) + wet)
+    }
+
+    fn process_wet(&mut self, input: f32) -> f32 {
+        self.history[self.history_pos] = input;
+        let mut output = self.tail[self.block_pos];
+        for (tap, coefficient) in self.head.iter().enumerate() {
+            output += coefficient * self.history[(self.history_pos + BLOCK - tap) % BLOCK];
+        }
+        self.history_pos = (self.history_pos + 1) % BLOCK;
+
+        self.current_block[self.block_pos] = input;
+        self.block_pos += 1;
+        if self.block_pos == BLOCK {
+            self.block_pos = 0;
+            self.next_block();
+        }
+        output
+    }
+
+    // Called once a full input block is in: transforms it (with the block before,
+    // for overlap-save) and sums every partition against the matching past block
+    fn next_block(&mut self) {
+        self.re[..BLOCK].copy_from_slice(&self.previous_block);
+        self.re[BLOCK..].copy_from_slice(&self.current_block);
+        std::mem::swap(&mut self.previous_block, &mut self.current_block);
+        if self.partitions.is_empty() {
+            return;
+        }
+
+        self.im.fill(0.0);
+        fft(&mut self.re, &mut self.im);
+
+        let count = self.spectra.len();
+        self.newest = (self.newest + 1) % count;
+        for (k, bin) in self.spectra[self.newest].iter_mut().enumerate() {
+            *bin = (self.re[k], self.im[k]);
+        }
+
+        // Partition p (IR block p + 1) meets the input block p blocks back
+        let mut sum = [(0.0f32, 0.0f32); BINS];
+        for (p, partition) in self.partitions.iter().enumerate() {
+            let spectrum = &self.spectra[(self.newest + count - p) % count];
+            for ((acc, h), x) in sum.iter_mut().zip(partition).zip(spectrum) {
+                acc.0 += h.0 * x.0 - h.1 * x.1;
+                acc.1 += h.0 * x.1 + h.1 * x.0;
+            }
+        }
+
+        // Inverse transform of a real signal: mirror the bins, then run the
+        // forward FFT with real and imaginary parts swapped
+        for (k, &(re, im)) in sum.iter().enumerate() {
+            self.re[k] = re;
+            self.im[k] = im;
+            if k > 0 && k < BLOCK {
+                self.re[FFT_SIZE - k] = re;
+                self.im[FFT_SIZE - k] = -im;
+            }
+        }
+        fft(&mut self.im, &mut self.re);
+
+        // The second half is the linear part of the circular convolution
+        let scale = 1.0 / FFT_SIZE as f32;
+        for (out, value) in self.tail.iter_mut().zip(&self.re[BLOCK..]) {
+            *out = value * scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn noise(seed: u64, len: usize) -> Vec<f32> {
+        let mut rng = Rng::new(seed);
+        (0..len).map(|_| rng.random() * 2.0 - 1.0).collect()
+    }
+
+    #[test]
+    fn unit_impulse_ir_is_the_identity() {
+        let mut convolution = Convolution::new(48000.0);
+        convolution.load_ir(&[1.0]);
+        convolution.set_mix(1.0);
+        let input = noise(1, 1000);
+        assert!(input.iter().all(|&x| (convolution.process(x) - x).abs() < 1e-6));
+    }
+
+    #[test]
+    fn partitions_match_direct_convolution() {
+        let ir = noise(2, 1000);
+        let input = noise(3, 2000);
+        let mut convolution = Convolution::new(48000.0);
+        convolution.load_ir(&ir);
+        convolution.set_mix(1.0);
+        for (n, &x) in input.iter().enumerate() {
+            let expected: f32 = (0..=n.min(ir.len() - 1)).map(|k| ir[k] * input[n - k]).sum();
+            let output = convolution.process(x);
+            assert!((output - expected).abs() < 1e-3, "sample {n}: {output} vs {expected}");
+        }
+    }
+}
@@ -1,55 +1,954 @@
+use crate::clock::{division_to_beats, DEFAULT_TEMPO_BPM};
+use crate::follower::EnvelopeFollower;
+use crate::lfo::{Lfo, LfoWaveform};
+use crate::rng::stream_seed;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, SQRT_2};
+
+const WOW_RATE_HZ: f32 = 0.7;
+const MAX_WOW_MS: f32 = 3.0; // Read position swing at full wow
+const FLUTTER_RATE_HZ: f32 = 9.0;
+const FLUTTER_SHARE: f32 = 0.1; // Flutter swing as a fraction of the wow depth
+const MAX_SATURATION_DRIVE: f32 = 4.0;
+const MAX_TAPS: usize = 4;
+const REVERSE_FADE_MS: f32 = 10.0;
+const FREEZE_FADE_MS: f32 = 20.0;
+const MAX_DELAY_LIMIT_MS: f32 = 10_000.0;
+const DUCK_ATTACK_MS: f32 = 5.0;
+const DUCK_FULL_LEVEL: f32 = 0.5; // Dry peak level that ducks by the full amount
+const TONE_BYPASS_HZ: f32 = 20000.0;
+const TIME_CHANGE_MS: f32 = 50.0;
+
+// How the read position follows a new delay time
+#[derive(Clone, Copy, PartialEq)]
+pub enum TimeChange {
+    Crossfade = 0, // Fade from the old position to the new one, pitch untouched
+    Slew = 1,      // Glide the position over, bending the repeats like tape
+}
+
+impl TimeChange {
+    pub fn from_u8(mode: u8) -> Self {
+        match mode {
+            1 => TimeChange::Slew,
+            _ => TimeChange::Crossfade,
+        }
+    }
+}
+
+// Where one channel reads: the delay it is heading to and, during a crossfade,
+// the one it is leaving
+struct ReadHead {
+    delay: f32,
+    fade_from: f32,
+    fade: f32, // Weight of `delay`; 1 once no crossfade is running
+}
+
+impl ReadHead {
+    fn new(delay: f32) -> Self {
+        ReadHead {
+            delay,
+            fade_from: delay,
+            fade: 1.0,
+        }
+    }
+
+    // Returns (old delay, new delay, weight of the new one). A change arriving
+    // mid-crossfade waits for it to finish, so neither side ever cuts out
+    fn next(&mut self, target: f32, mode: TimeChange, slew_coeff: f32, fade_step: f32) -> (f32, f32, f32) {
+        if self.fade < 1.0 {
+            self.fade = (self.fade + fade_step).min(1.0);
+        } else if self.delay != target {
+            match mode {
+                TimeChange::Crossfade => {
+                    self.fade_from = self.delay;
+                    self.delay = target;
+                    self.fade = fade_step;
+                }
+                TimeChange::Slew => self.delay += (target - self.delay) * slew_coeff,
+            }
+        }
+        (self.fade_from, self.delay, self.fade)
+    }
+}
+
+// Reverse playback in grains of the delay time: each grain starts at the newest
+// sample and reads backwards at twice the write speed relative to the write
+// position, so it plays the last grain's worth of input backwards. The next grain
+// fades in over the end of the current one
+struct ReverseHead {
+    pos: f32,   // Samples into the current grain
+    grain: f32, // Length, latched when the grain starts so time changes land on a boundary
+}
+
+impl ReverseHead {
+    fn new(grain: f32) -> Self {
+        ReverseHead { pos: 0.0, grain }
+    }
+
+    // Returns (current read delay, next grain's read delay, weight of the next grain)
+    fn next(&mut self, target: f32, fade_samples: f32) -> (f32, f32, f32) {
+        let fade = fade_samples.min(self.grain * 0.25).max(1.0);
+        let overlap = self.pos - (self.grain - fade);
+        let current = 1.0 + 2.0 * self.pos;
+        let (next, weight) = if overlap >= 0.0 { (1.0 + 2.0 * overlap, overlap / fade) } else { (current, 0.0) };
+        self.pos += 1.0;
+        if self.pos >= self.grain {
+            self.pos = overlap.max(0.0) + 1.0;
+            self.grain = target;
+        }
+        (current, next, weight)
+    }
+}
+
+// One read of a multi-tap pattern, all on the same line
+struct Tap {
+    time_ms: f32,
+    delay: f32,
+    head: ReadHead,
+    level: f32,
+    pan: f32,
+    gains: (f32, f32), // Equal-power pan as in AutoPan, unity on both sides at centre
+}
+
+impl Tap {
+    fn set_pan(&mut self, pan: f32) {
+        self.pan = pan.clamp(-1.0, 1.0);
+        let angle = (self.pan + 1.0) * FRAC_PI_4;
+        self.gains = (angle.cos() * SQRT_2, angle.sin() * SQRT_2);
+    }
+}
+
 pub struct Delay {
     buffer: Vec<f32>,
+    buffer_right: Vec<f32>, // Second line for the stereo path, with its own time
     write_pos: usize,
-    delay_samples: usize,
+    delay_samples: f32, // Fractional, read with interpolation
+    delay_samples_right: f32,
+    head: ReadHead,
+    head_right: ReadHead,
+    time_change: TimeChange,
+    slew_coeff: f32,
+    fade_step: f32,
+    delay_time_ms: f32,
+    delay_time_right_ms: f32,
+    sync: Option<u8>, // Note division both sides follow instead of the ms times
+    bpm: f32,
     feedback: f32,
     mix: f32,
+    kill_dry: bool,
+    dry_gain: f32,
+    wet_gain: f32,
+    ping_pong: bool,
+    taps: Vec<Tap>, // Empty = the single echo at delay_samples
+    // Ducking: a peak follower on the dry input turns the echoes down while playing
+    duck_amount: f32,
+    duck_follower: EnvelopeFollower,
+    reverse: bool,
+    frozen: bool,
+    freeze: f32, // 0 = normal writes, 1 = the lines loop what they hold; ramps between
+    freeze_step: f32,
+    reverse_head: ReverseHead,
+    reverse_head_right: ReverseHead,
+    feedback_tap: u8, // Past the last tap = the last tap
+    // Tape character: a lowpass inside the feedback loop darkens every repeat,
+    // a slow LFO plus faster random flutter on the read position adds wow,
+    // and soft clipping in the loop rounds off repeats that build up
+    tone_cutoff: f32,
+    tone_coeff: f32,
+    tone_state: f32,
+    tone_state_right: f32,
+    wow_depth_ms: f32,
+    wow_lfo: Lfo,
+    flutter_lfo: Lfo,
+    saturation: f32,
+    max_delay_ms: f32,
     sample_rate: f32,
 }
 
 impl Delay {
     pub fn new(sample_rate: f32, max_delay_ms: f32) -> Self {
         let max_samples = (max_delay_ms * sample_rate / 1000.0) as usize;
+        let delay_samples = sample_rate * 0.5 / 1000.0; // 0.5ms default, 24 samples at 48 kHz
+        let mut wow_lfo = Lfo::new(sample_rate);
+        wow_lfo.set_rate(WOW_RATE_HZ);
+        wow_lfo.set_depth(1.0);
+        let mut flutter_lfo = Lfo::new(sample_rate);
+        flutter_lfo.set_waveform(LfoWaveform::SmoothRandom as u8);
+        flutter_lfo.set_rate(FLUTTER_RATE_HZ);
+        flutter_lfo.set_depth(1.0);
         Delay {
             buffer: vec![0.0; max_samples],
+            buffer_right: vec![0.0; max_samples],
             write_pos: 0,
-            delay_samples: (sample_rate * 0.5 / 1000.0) as usize, // 0.5ms default
+            delay_samples,
+            delay_samples_right: delay_samples,
+            head: ReadHead::new(delay_samples),
+            head_right: ReadHead::new(delay_samples),
+            time_change: TimeChange::Crossfade,
+            // Same one-pole curve as the parameter smoothers
+            slew_coeff: 1.0 - (-1000.0 / (TIME_CHANGE_MS * sample_rate)).exp(),
+            fade_step: 1000.0 / (TIME_CHANGE_MS * sample_rate),
+            delay_time_ms: 0.5,
+            delay_time_right_ms: 0.5,
+            sync: None,
+            bpm: DEFAULT_TEMPO_BPM,
             feedback: 0.3,
             mix: 0.3,
+            kill_dry: false,
+            dry_gain: (0.3 * FRAC_PI_2).cos(),
+            wet_gain: (0.3 * FRAC_PI_2).sin(),
+            ping_pong: false,
+            taps: Vec::new(),
+            duck_amount: 0.0,
+            duck_follower: EnvelopeFollower::new(sample_rate, DUCK_ATTACK_MS, 300.0),
+            reverse: false,
+            frozen: false,
+            freeze: 0.0,
+            freeze_step: 1000.0 / (FREEZE_FADE_MS * sample_rate),
+            reverse_head: ReverseHead::new(delay_samples),
+            reverse_head_right: ReverseHead::new(delay_samples),
+            feedback_tap: u8::MAX,
+            tone_cutoff: TONE_BYPASS_HZ,
+            tone_coeff: 1.0,
+            tone_state: 0.0,
+            tone_state_right: 0.0,
+            wow_depth_ms: 0.0,
+            wow_lfo,
+            flutter_lfo,
+            saturation: 0.0,
+            max_delay_ms,
             sample_rate,
         }
     }
 
+    // Sets both channels; the mono path only uses this one
     pub fn set_delay_time(&mut self, time_ms: f32) {
-        self.delay_samples = ((time_ms * self.sample_rate) / 1000.0) as usize;
-        self.delay_samples = self.delay_samples.min(self.buffer.len());
+        self.set_delay_time_lr(time_ms, time_ms);
+    }
+
+    // While synced the times are only stored, and come back when sync is turned off
+    pub fn set_delay_time_lr(&mut self, left_ms: f32, right_ms: f32) {
+        self.delay_time_ms = left_ms;
+        self.delay_time_right_ms = right_ms;
+        self.update_times();
+    }
+
+    // Division as in clock::division_to_beats (dotted and triplet included), None = ms times.
+    // Tempo changes then move the echoes through the same crossfade or slew as any time change
+    pub fn set_sync(&mut self, division: Option<u8>) {
+        self.sync = division;
+        self.update_times();
+    }
+
+    pub fn get_sync(&self) -> Option<u8> {
+        self.sync
+    }
+
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.bpm = bpm;
+        self.update_times();
+    }
+
+    // Resizes both lines (100 ms to 10 s), keeping the most recent audio so the
+    // running echoes carry on. Allocates, so call it from a setter, never per sample.
+    // Times past the new length are clamped to it
+    pub fn set_max_delay(&mut self, ms: f32) {
+        let ms = ms.clamp(100.0, MAX_DELAY_LIMIT_MS);
+        let len = (ms * self.sample_rate / 1000.0) as usize;
+        if len == self.buffer.len() {
+            return;
+        }
+        self.max_delay_ms = ms;
+        let kept = self.buffer.len().min(len);
+        self.buffer = Self::resized(&self.buffer, self.write_pos, len);
+        self.buffer_right = Self::resized(&self.buffer_right, self.write_pos, len);
+        self.write_pos = kept % len;
+        self.update_times();
+        for tap in &mut self.taps {
+            tap.delay = (tap.time_ms * self.sample_rate / 1000.0).clamp(1.0, (len - 1) as f32);
+        }
+        let grain = self.reverse_grain(self.delay_samples);
+        self.reverse_head.grain = self.reverse_head.grain.min(grain);
+        let grain = self.reverse_grain(self.delay_samples_right);
+        self.reverse_head_right.grain = self.reverse_head_right.grain.min(grain);
+    }
+
+    pub fn get_max_delay(&self) -> f32 {
+        self.max_delay_ms
+    }
+
+    // The newest `len` samples (or all of them) in order, oldest first, so writing
+    // resumes right after them
+    fn resized(buffer: &[f32], write_pos: usize, len: usize) -> Vec<f32> {
+        let keep = buffer.len().min(len);
+        let mut resized = vec![0.0; len];
+        for (i, sample) in resized[..keep].iter_mut().enumerate() {
+            *sample = buffer[(write_pos + buffer.len() - keep + i) % buffer.len()];
+        }
+        resized
+    }
+
+    fn update_times(&mut self) {
+        let (left_ms, right_ms) = match self.sync {
+            Some(division) => {
+                let ms = division_to_beats(division) * 60_000.0 / self.bpm;
+                (ms, ms)
+            }
+            None => (self.delay_time_ms, self.delay_time_right_ms),
+        };
+        self.delay_samples = self.time_to_samples(left_ms);
+        self.delay_samples_right = self.time_to_samples(right_ms);
+    }
+
+    // Up to 4 taps reading the one delay line, each with its own time and level
+    // (missing levels are 1). Only the feedback tap goes back into the line, so the
+    // whole pattern repeats at its time. Empty times go back to the single echo.
+    // Taps ignore sync, ping-pong and reverse
+    pub fn set_taps(&mut self, times_ms: &[f32], levels: &[f32]) {
+        let count = times_ms.len().min(MAX_TAPS);
+        self.taps.truncate(count);
+        for (i, &time_ms) in times_ms[..count].iter().enumerate() {
+            let delay = self.time_to_samples(time_ms);
+            let level = levels.get(i).copied().unwrap_or(1.0).clamp(0.0, 1.0);
+            match self.taps.get_mut(i) {
+                Some(tap) => {
+                    tap.time_ms = time_ms;
+                    tap.delay = delay;
+                    tap.level = level;
+                }
+                None => {
+                    let mut tap = Tap {
+                        time_ms,
+                        delay,
+                        head: ReadHead::new(delay),
+                        level,
+                        pan: 0.0,
+                        gains: (1.0, 1.0),
+                    };
+                    tap.set_pan(0.0);
+                    self.taps.push(tap);
+                }
+            }
+        }
+    }
+
+    // -1 = left, 1 = right, per tap in order; only heard through process_stereo
+    pub fn set_tap_pans(&mut self, pans: &[f32]) {
+        for (tap, &pan) in self.taps.iter_mut().zip(pans) {
+            tap.set_pan(pan);
+        }
+    }
+
+    pub fn set_feedback_tap(&mut self, index: u8) {
+        self.feedback_tap = index;
+    }
+
+    // (time ms, level, pan) per tap
+    pub fn get_taps(&self) -> Vec<(f32, f32, f32)> {
+        self.taps.iter().map(|tap| (tap.time_ms, tap.level, tap.pan)).collect()
+    }
+
+    pub fn get_feedback_tap(&self) -> u8 {
+        self.feedback_tap
+    }
+
+    // Reads happen before the write, so a delay of 0 would land on the oldest slot
+    // (a full buffer of delay): the shortest is one sample. The longest is kept one
+    // short of the buffer so the interpolation always has a sample past it
+    fn time_to_samples(&self, time_ms: f32) -> f32 {
+        (time_ms * self.sample_rate / 1000.0).clamp(1.0, (self.buffer.len() - 1) as f32)
+    }
+
+    // 0 = crossfade to the new time, 1 = slew to it (pitch bend), both over ~50 ms
+    pub fn set_time_change_mode(&mut self, mode: u8) {
+        self.time_change = TimeChange::from_u8(mode);
+    }
+
+    pub fn get_time_change_mode(&self) -> u8 {
+        self.time_change as u8
     }
 
     pub fn set_feedback(&mut self, feedback: f32) {
         self.feedback = feedback.clamp(0.0, 0.95);
     }
 
+    // Equal-power dry/wet: 0 = dry only, 0.5 = both at -3 dB, 1 = echoes only
     pub fn set_mix(&mut self, mix: f32) {
         self.mix = mix.clamp(0.0, 1.0);
+        self.update_mix_gains();
     }
 
-    pub fn process(&mut self, input: f32) -> f32 {
-        let read_pos = if self.write_pos >= self.delay_samples {
-            self.write_pos - self.delay_samples
+    // Drops the dry signal whatever the mix, for using the delay as a send
+    pub fn set_kill_dry(&mut self, enabled: bool) {
+        self.kill_dry = enabled;
+        self.update_mix_gains();
+    }
+
+    pub fn get_kill_dry(&self) -> bool {
+        self.kill_dry
+    }
+
+    fn update_mix_gains(&mut self) {
+        let (wet, dry) = (self.mix * FRAC_PI_2).sin_cos();
+        // cos(pi / 2) is only nearly 0 in f32
+        self.dry_gain = if self.kill_dry || self.mix >= 1.0 { 0.0 } else { dry };
+        self.wet_gain = wet;
+    }
+
+    // Stereo path only: the input enters the left line and each repeat crosses
+    // to the other side, so echoes alternate. A round trip passes the feedback
+    // twice, so the same clamp keeps it stable
+    pub fn set_ping_pong(&mut self, enabled: bool) {
+        self.ping_pong = enabled;
+    }
+
+    pub fn get_ping_pong(&self) -> bool {
+        self.ping_pong
+    }
+
+    // Plays each grain of the delay time backwards (up to half the maximum delay
+    // time), so repeats swell in. Switching either way clears the lines
+    pub fn set_reverse(&mut self, enabled: bool) {
+        if enabled == self.reverse {
+            return;
+        }
+        self.reverse = enabled;
+        self.buffer.fill(0.0);
+        self.buffer_right.fill(0.0);
+        self.reset_tone();
+        self.reverse_head = ReverseHead::new(self.reverse_grain(self.delay_samples));
+        self.reverse_head_right = ReverseHead::new(self.reverse_grain(self.delay_samples_right));
+    }
+
+    pub fn get_reverse(&self) -> bool {
+        self.reverse
+    }
+
+    // Amount 0-1 the echoes drop under a loud dry signal; they come back over the
+    // release time (10-2000 ms) once it stops. Only the wet output is ducked, so the
+    // repeats keep building underneath. Amount 0 turns the follower off
+    pub fn set_ducking(&mut self, amount: f32, release_ms: f32) {
+        self.duck_amount = amount.clamp(0.0, 1.0);
+        self.duck_follower.set_release(release_ms.clamp(10.0, 2000.0));
+        if self.duck_amount == 0.0 {
+            self.duck_follower.reset();
+        }
+    }
+
+    pub fn get_ducking(&self) -> (f32, f32) {
+        (self.duck_amount, self.duck_follower.get_release())
+    }
+
+    // (dry, wet) gains for this sample, the wet one after ducking
+    fn next_mix_gains(&mut self, input: f32) -> (f32, f32) {
+        if self.duck_amount == 0.0 {
+            return (self.dry_gain, self.wet_gain);
+        }
+        let level = self.duck_follower.process(input);
+        let duck = 1.0 - self.duck_amount * (level / DUCK_FULL_LEVEL).min(1.0);
+        (self.dry_gain, self.wet_gain * duck)
+    }
+
+    // Holds what the lines contain: each one re-writes its own echo at unity, so
+    // the loop is the delay time long. Input still passes dry but is not recorded,
+    // and feedback, tone and saturation are bypassed. Both ways fade over ~20 ms
+    pub fn set_freeze(&mut self, enabled: bool) {
+        self.frozen = enabled;
+    }
+
+    fn next_freeze(&mut self) -> f32 {
+        let target = if self.frozen { 1.0 } else { 0.0 };
+        if self.freeze < target {
+            self.freeze = (self.freeze + self.freeze_step).min(1.0);
+        } else if self.freeze > target {
+            self.freeze = (self.freeze - self.freeze_step).max(0.0);
+        }
+        self.freeze
+    }
+
+    // What goes into a line: the normal input-plus-feedback, the held echo, or a blend
+    fn hold(freeze: f32, normal: f32, held: f32) -> f32 {
+        if freeze == 0.0 {
+            return normal;
+        }
+        normal + (held - normal) * freeze
+    }
+
+    // A grain reads back up to twice its length
+    fn reverse_grain(&self, delay: f32) -> f32 {
+        delay.min((self.buffer.len() - 2) as f32 * 0.5)
+    }
+
+    // Lowpass cutoff of the feedback path; 20 kHz leaves the repeats untouched
+    pub fn set_feedback_tone(&mut self, cutoff_hz: f32) {
+        self.tone_cutoff = cutoff_hz.clamp(200.0, TONE_BYPASS_HZ);
+        self.tone_coeff = if self.tone_cutoff >= TONE_BYPASS_HZ {
+            1.0
         } else {
-            self.buffer.len() - (self.delay_samples - self.write_pos)
+            1.0 - (-2.0 * std::f32::consts::PI * self.tone_cutoff / self.sample_rate).exp()
         };
+    }
+
+    // 0 = steady, 1 = up to 3 ms of slow pitch drift on the repeats, at the current wow rate
+    pub fn set_wow(&mut self, amount: f32) {
+        self.wow_depth_ms = amount.clamp(0.0, 1.0) * MAX_WOW_MS;
+    }
+
+    // Read position swing (0-3 ms) and rate of the wow LFO (0.05-10 Hz). The random
+    // flutter on top swings a tenth as far at ~9 Hz. Depth 0 leaves the reads untouched
+    pub fn set_wow_flutter(&mut self, depth_ms: f32, rate_hz: f32) {
+        self.wow_depth_ms = depth_ms.clamp(0.0, MAX_WOW_MS);
+        self.set_wow_rate(rate_hz);
+    }
+
+    pub fn set_wow_rate(&mut self, rate_hz: f32) {
+        self.wow_lfo.set_rate(rate_hz.clamp(0.05, 10.0));
+    }
 
-        let delayed = self.buffer[read_pos];
-        let output = input + delayed * self.mix;
-        self.buffer[self.write_pos] = input + delayed * self.feedback;
+    // Soft clipping of the repeats inside the feedback loop, 0 = clean
+    pub fn set_saturation(&mut self, amount: f32) {
+        self.saturation = amount.clamp(0.0, 1.0);
+    }
+
+    // Clears the feedback lowpass, so a delay switched back on doesn't start from a stale level
+    pub fn reset_tone(&mut self) {
+        self.tone_state = 0.0;
+        self.tone_state_right = 0.0;
+    }
+
+    pub fn get_feedback_tone(&self) -> f32 {
+        self.tone_cutoff
+    }
+
+    pub fn get_wow(&self) -> f32 {
+        self.wow_depth_ms / MAX_WOW_MS
+    }
+
+    pub fn get_wow_rate(&self) -> f32 {
+        self.wow_lfo.get_rate()
+    }
+
+    pub fn get_saturation(&self) -> f32 {
+        self.saturation
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.wow_lfo.set_seed(stream_seed(seed, 0));
+        self.flutter_lfo.set_seed(stream_seed(seed, 1));
+    }
+
+    pub fn get_delay_time(&self) -> f32 {
+        self.delay_time_ms
+    }
+
+    pub fn get_delay_time_right(&self) -> f32 {
+        self.delay_time_right_ms
+    }
+
+    pub fn get_feedback(&self) -> f32 {
+        self.feedback
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
 
+    fn advance(&mut self) {
         self.write_pos += 1;
         if self.write_pos >= self.buffer.len() {
             self.write_pos = 0;
         }
+    }
+
+    // Linear interpolation between the two samples around a fractional delay
+    fn read_fractional(&self, buffer: &[f32], delay: f32) -> f32 {
+        let len = buffer.len();
+        let whole = delay.floor() as usize;
+        let frac = delay - whole as f32;
+        let newer = buffer[(self.write_pos + len - whole.min(len - 1)) % len];
+        let older = buffer[(self.write_pos + len - (whole + 1).min(len - 1)) % len];
+        newer + (older - newer) * frac
+    }
 
+    // Offset of the read position from wow and flutter this sample, in samples.
+    // Both only push the read back, so it never passes the write position
+    fn next_wow_offset(&mut self) -> f32 {
+        if self.wow_depth_ms == 0.0 {
+            return 0.0;
+        }
+        let swing = self.wow_depth_ms * self.sample_rate / 1000.0;
+        let wow = (self.wow_lfo.process() + 1.0) * 0.5;
+        let flutter = (self.flutter_lfo.process() + 1.0) * 0.5 * FLUTTER_SHARE;
+        (wow + flutter) * swing
+    }
+
+    fn read_head(&self, buffer: &[f32], (from, to, fade): (f32, f32, f32), wow_offset: f32) -> f32 {
+        let new = self.read_fractional(buffer, to + wow_offset);
+        if fade >= 1.0 {
+            return new;
+        }
+        let old = self.read_fractional(buffer, from + wow_offset);
+        old + (new - old) * fade
+    }
+
+    // Reads the reversed grain, crossfading equal-power into the next one since
+    // the two hold unrelated audio
+    fn read_reverse(&self, buffer: &[f32], (current, next, weight): (f32, f32, f32), wow_offset: f32) -> f32 {
+        let output = self.read_fractional(buffer, current + wow_offset);
+        if weight <= 0.0 {
+            return output;
+        }
+        let (fade_in, fade_out) = (weight * std::f32::consts::FRAC_PI_2).sin_cos();
+        output * fade_out + self.read_fractional(buffer, next + wow_offset) * fade_in
+    }
+
+    // The echo of one line, from its read head or its reversed grains
+    fn read_line(&mut self, right: bool, wow_offset: f32) -> f32 {
+        if self.reverse {
+            let fade = REVERSE_FADE_MS * self.sample_rate / 1000.0;
+            return if right {
+                let grain = self.reverse_head_right.next(self.reverse_grain(self.delay_samples_right), fade);
+                self.read_reverse(&self.buffer_right, grain, wow_offset)
+            } else {
+                let grain = self.reverse_head.next(self.reverse_grain(self.delay_samples), fade);
+                self.read_reverse(&self.buffer, grain, wow_offset)
+            };
+        }
+        if right {
+            let head =
+                self.head_right.next(self.delay_samples_right, self.time_change, self.slew_coeff, self.fade_step);
+            self.read_head(&self.buffer_right, head, wow_offset)
+        } else {
+            let head = self.head.next(self.delay_samples, self.time_change, self.slew_coeff, self.fade_step);
+            self.read_head(&self.buffer, head, wow_offset)
+        }
+    }
+
+    fn tone(coeff: f32, state: &mut f32, input: f32) -> f32 {
+        if coeff >= 1.0 {
+            return input;
+        }
+        *state += coeff * (input - *state);
+        *state
+    }
+
+    // Returns the tap sum (mono, left, right) and the plain read of the feedback tap
+    fn read_taps(&mut self, wow_offset: f32) -> (f32, f32, f32, f32) {
+        let mut taps = std::mem::take(&mut self.taps);
+        let feedback_tap = (self.feedback_tap as usize).min(taps.len() - 1);
+        let (mut mono, mut left, mut right, mut feedback) = (0.0, 0.0, 0.0, 0.0);
+        for (i, tap) in taps.iter_mut().enumerate() {
+            let head = tap.head.next(tap.delay, self.time_change, self.slew_coeff, self.fade_step);
+            let read = self.read_head(&self.buffer, head, wow_offset);
+            mono += read * tap.level;
+            left += read * tap.level * tap.gains.0;
+            right += read * tap.level * tap.gains.1;
+            if i == feedback_tap {
+                feedback = read;
+            }
+        }
+        self.taps = taps;
+        (mono, left, right, feedback)
+    }
+
+    // tanh with the drive divided back out, so quiet repeats pass at unity gain
+    fn saturate(&self, input: f32) -> f32 {
+        if self.saturation == 0.0 {
+            return input;
+        }
+        let drive = 1.0 + self.saturation * MAX_SATURATION_DRIVE;
+        (input * drive).tanh() / drive
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let wow_offset = self.next_wow_offset();
+        let freeze = self.next_freeze();
+        let (dry, wet) = self.next_mix_gains(input);
+        if !self.taps.is_empty() {
+            let (delayed, _, _, feedback) = self.read_taps(wow_offset);
+            let repeat = Self::tone(self.tone_coeff, &mut self.tone_state, feedback);
+            let normal = input + self.saturate(repeat) * self.feedback;
+            self.buffer[self.write_pos] = Self::hold(freeze, normal, feedback);
+            self.advance();
+            return input * dry + delayed * wet;
+        }
+        let delayed = self.read_line(false, wow_offset);
+        let output = input * dry + delayed * wet;
+        let repeat = Self::tone(self.tone_coeff, &mut self.tone_state, delayed);
+        let repeat = self.saturate(repeat);
+        self.buffer[self.write_pos] = Self::hold(freeze, input + repeat * self.feedback, delayed);
+
+        self.advance();
         output
     }
+
+    // Mono in, two independently timed echo lines out (or one line bouncing between
+    // sides, or the panned taps of the left line)
+    pub fn process_stereo(&mut self, input: f32) -> (f32, f32) {
+        let wow_offset = self.next_wow_offset();
+        let freeze = self.next_freeze();
+        let (dry, wet) = self.next_mix_gains(input);
+        if !self.taps.is_empty() {
+            let (_, delayed_left, delayed_right, feedback) = self.read_taps(wow_offset);
+            let repeat = Self::tone(self.tone_coeff, &mut self.tone_state, feedback);
+            let normal = input + self.saturate(repeat) * self.feedback;
+            self.buffer[self.write_pos] = Self::hold(freeze, normal, feedback);
+            // Kept current so the two-line echo picks up cleanly when the taps are cleared
+            self.buffer_right[self.write_pos] = input;
+            self.advance();
+            return (input * dry + delayed_left * wet, input * dry + delayed_right * wet);
+        }
+        let delayed_left = self.read_line(false, wow_offset);
+        let delayed_right = self.read_line(true, wow_offset);
+        let repeat_left = Self::tone(self.tone_coeff, &mut self.tone_state, delayed_left);
+        let repeat_right = Self::tone(self.tone_coeff, &mut self.tone_state_right, delayed_right);
+        let (repeat_left, repeat_right) = (self.saturate(repeat_left), self.saturate(repeat_right));
+        let (normal_left, normal_right) = if self.ping_pong {
+            (input + repeat_right * self.feedback, repeat_left * self.feedback)
+        } else {
+            (input + repeat_left * self.feedback, input + repeat_right * self.feedback)
+        };
+        self.buffer[self.write_pos] = Self::hold(freeze, normal_left, delayed_left);
+        self.buffer_right[self.write_pos] = Self::hold(freeze, normal_right, delayed_right);
+
+        self.advance();
+        (input * dry + delayed_left * wet, input * dry + delayed_right * wet)
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Echoes only, with time changes settled before the impulse goes in
+    fn echo_delay(sample_rate: f32) -> Delay {
+        let mut delay = Delay::new(sample_rate, 2000.0);
+        delay.set_mix(1.0);
+        delay.set_feedback(0.0);
+        delay
+    }
+
+    fn settle(delay: &mut Delay) {
+        for _ in 0..4800 {
+            delay.process_stereo(0.0);
+        }
+    }
+
+    fn stereo_impulse_response(delay: &mut Delay, len: usize) -> Vec<(f32, f32)> {
+        settle(delay);
+        (0..len).map(|n| delay.process_stereo(if n == 0 { 1.0 } else { 0.0 })).collect()
+    }
+
+    fn peak(channel: impl Iterator<Item = f32>) -> usize {
+        channel.enumerate().fold((0, 0.0), |best, (n, x)| if x.abs() > best.1 { (n, x.abs()) } else { best }).0
+    }
+
+    #[test]
+    fn left_and_right_times_echo_at_their_own_offsets() {
+        let mut delay = echo_delay(48000.0);
+        delay.set_delay_time_lr(10.0, 15.0);
+        let response = stereo_impulse_response(&mut delay, 1200);
+        assert_eq!(peak(response.iter().map(|&(l, _)| l)), 480);
+        assert_eq!(peak(response.iter().map(|&(_, r)| r)), 720);
+        assert!((response[480].0 - 1.0).abs() < 1e-6 && (response[720].1 - 1.0).abs() < 1e-6);
+    }
+
+    // Share of an echo's energy in its sample-to-sample differences, a rough brightness
+    fn brightness(echo: &[f32]) -> f32 {
+        let energy: f32 = echo.iter().map(|x| x * x).sum();
+        let edges: f32 = echo.windows(2).map(|w| (w[1] - w[0]).powi(2)).sum();
+        edges / energy
+    }
+
+    #[test]
+    fn feedback_tone_darkens_each_repeat() {
+        let mut delay = echo_delay(48000.0);
+        delay.set_delay_time(10.0);
+        delay.set_feedback(0.8);
+        delay.set_feedback_tone(2000.0);
+        let response: Vec<f32> = stereo_impulse_response(&mut delay, 2640).into_iter().map(|(l, _)| l).collect();
+        let echo = |k: usize| &response[k * 480 - 240..k * 480 + 240];
+
+        let brightness: Vec<f32> = (1..=5).map(|k| brightness(echo(k))).collect();
+        assert!(brightness.windows(2).all(|pair| pair[1] < pair[0]), "{brightness:?}");
+        assert!(brightness[4] < 0.5 * brightness[0], "{brightness:?}");
+    }
+
+    #[test]
+    fn fractional_delay_splits_the_impulse() {
+        // 1.25 ms at 44.1 kHz is 55.125 samples
+        let mut delay = echo_delay(44100.0);
+        delay.set_delay_time(1.25);
+        let response = stereo_impulse_response(&mut delay, 100);
+        assert!((response[55].0 - 0.875).abs() < 1e-6 && (response[56].0 - 0.125).abs() < 1e-6);
+        let centroid: f32 = response.iter().enumerate().map(|(n, &(l, _))| n as f32 * l).sum();
+        assert!((centroid - 55.125).abs() < 1e-4, "{centroid}");
+    }
+
+    // Largest sample-to-sample step of the echoes of a sine while the time moves 100 -> 400 ms
+    fn largest_jump_across_time_change(mode: u8) -> f32 {
+        let mut delay = echo_delay(48000.0);
+        delay.set_time_change_mode(mode);
+        delay.set_delay_time(100.0);
+        let sine = |n: usize| (2.0 * std::f32::consts::PI * 220.0 * n as f32 / 48000.0).sin();
+        let mut output = Vec::new();
+        for n in 0..48000 {
+            if n == 24000 {
+                delay.set_delay_time(400.0);
+            }
+            output.push(delay.process(sine(n)));
+        }
+        output[6000..].windows(2).map(|w| (w[1] - w[0]).abs()).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn delay_time_changes_without_jumps() {
+        // The sine alone steps by up to 0.029; a jumped read head would step by up to 2
+        let crossfade = largest_jump_across_time_change(0);
+        assert!(crossfade < 0.035, "{crossfade}");
+        // Slewing bends the pitch up while the head moves, which steepens the wave
+        let slew = largest_jump_across_time_change(1);
+        assert!(slew < 0.25, "{slew}");
+    }
+
+    #[test]
+    fn quarter_notes_at_120_bpm_echo_after_500_ms() {
+        let mut delay = echo_delay(48000.0);
+        delay.set_tempo(120.0);
+        delay.set_sync(Some(2));
+        assert_eq!(delay.delay_samples, 24000.0);
+        let response = stereo_impulse_response(&mut delay, 30000);
+        assert_eq!(peak(response.iter().map(|&(l, _)| l)), 24000);
+
+        // Dotted eighths follow a tempo change
+        delay.set_sync(Some(7));
+        delay.set_tempo(90.0);
+        assert_eq!(delay.delay_samples, 0.75 * 60.0 / 90.0 * 48000.0);
+    }
+
+    #[test]
+    fn longest_and_shortest_delays_are_exact() {
+        // 100 ms is a 4800-sample line, so the longest delay is 4799 samples
+        let mut delay = Delay::new(48000.0, 100.0);
+        delay.set_mix(1.0);
+        delay.set_feedback(0.0);
+        delay.set_delay_time(1000.0);
+        let response = stereo_impulse_response(&mut delay, 4800);
+        assert_eq!(peak(response.iter().map(|&(l, _)| l)), 4799);
+        assert_eq!(response[4799].0, 1.0);
+        assert!(response.iter().enumerate().all(|(n, &(l, _))| n == 4799 || l == 0.0));
+
+        // 0 ms is one sample: the read never lands on the sample being written
+        delay.set_delay_time(0.0);
+        let response = stereo_impulse_response(&mut delay, 8);
+        assert_eq!(response.iter().map(|&(l, _)| l).collect::<Vec<_>>(), [0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+    }
+
+    // A second of noise through a 40% feedback delay with the given tape character
+    fn tape_echo(depth_ms: f32, seed: u64) -> Vec<(f32, f32)> {
+        let mut delay = Delay::new(48000.0, 2000.0);
+        delay.set_delay_time(120.0);
+        delay.set_feedback(0.4);
+        delay.set_mix(0.5);
+        delay.set_seed(seed);
+        delay.set_wow_flutter(depth_ms, 2.0);
+        let mut noise = crate::rng::Rng::new(3);
+        (0..48000).map(|_| delay.process_stereo(noise.random() - 0.5)).collect()
+    }
+
+    #[test]
+    fn zero_wow_depth_is_bit_identical_to_the_clean_delay() {
+        let bits = |out: Vec<(f32, f32)>| out.iter().map(|(l, r)| (l.to_bits(), r.to_bits())).collect::<Vec<_>>();
+        let mut clean = Delay::new(48000.0, 2000.0);
+        clean.set_delay_time(120.0);
+        clean.set_feedback(0.4);
+        clean.set_mix(0.5);
+        let mut noise = crate::rng::Rng::new(3);
+        let clean: Vec<_> = (0..48000).map(|_| clean.process_stereo(noise.random() - 0.5)).collect();
+        assert_eq!(bits(tape_echo(0.0, 1)), bits(clean));
+        assert_eq!(bits(tape_echo(2.0, 1)), bits(tape_echo(2.0, 1)));
+        assert_ne!(bits(tape_echo(2.0, 1)), bits(tape_echo(2.0, 2)));
+    }
+
+    #[test]
+    fn reverse_plays_an_ascending_ramp_back_descending() {
+        let mut delay = echo_delay(48000.0);
+        delay.set_delay_time(200.0);
+        delay.set_reverse(true);
+        let wet: Vec<f32> = (0..48000).map(|n| delay.process(n as f32 / 48000.0)).collect();
+        // Past the first grain every sample steps down but the 10 ms crossfades into the next
+        let falling = wet[9600..].windows(2).filter(|w| w[1] < w[0]).count();
+        assert!(falling as f32 > 0.9 * (wet.len() - 9601) as f32, "{falling}");
+    }
+
+    // Wet output of a 100 ms, 80% feedback delay fed 300 ms of a loud sine, then silence
+    fn ducked_wet(amount: f32) -> Vec<f32> {
+        let mut delay = Delay::new(48000.0, 2000.0);
+        delay.set_delay_time(100.0);
+        delay.set_feedback(0.8);
+        delay.set_kill_dry(true);
+        delay.set_ducking(amount, 100.0);
+        let input = |n: usize| if n < 14400 { 0.8 * (n as f32 * 0.05).sin() } else { 0.0 };
+        (0..48000).map(|n| delay.process(input(n))).collect()
+    }
+
+    fn rms(block: &[f32]) -> f32 {
+        (block.iter().map(|x| x * x).sum::<f32>() / block.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn ducking_holds_repeats_down_while_playing_and_lets_them_back() {
+        let (plain, ducked) = (ducked_wet(0.0), ducked_wet(1.0));
+        // Echoes under the playing are pushed well down
+        assert!(rms(&ducked[9600..14400]) < 0.1 * rms(&plain[9600..14400]));
+        // Half a second after the last note the repeats are back to full level
+        let gap = 38400..43200;
+        assert!((rms(&ducked[gap.clone()]) / rms(&plain[gap]) - 1.0).abs() < 0.05);
+    }
+
+    fn mono_impulse_response(delay: &mut Delay, len: usize) -> Vec<f32> {
+        (0..len).map(|n| delay.process(if n == 0 { 1.0 } else { 0.0 })).collect()
+    }
+
+    #[test]
+    fn full_mix_is_wet_only_and_zero_mix_exactly_dry() {
+        let mut delay = echo_delay(48000.0);
+        delay.set_delay_time(10.0);
+        settle(&mut delay);
+        let wet = mono_impulse_response(&mut delay, 960);
+        assert_eq!(wet[0], 0.0);
+        assert!((wet[480] - 1.0).abs() < 1e-6);
+
+        delay.set_mix(0.0);
+        let dry = mono_impulse_response(&mut delay, 960);
+        assert_eq!(dry[0], 1.0);
+        assert!(dry[1..].iter().all(|&x| x == 0.0));
+
+        // Equal power at the centre, and kill dry drops the impulse there too
+        delay.set_mix(0.5);
+        let centre = mono_impulse_response(&mut delay, 1);
+        assert!((centre[0] - FRAC_PI_4.cos()).abs() < 1e-6);
+        delay.set_kill_dry(true);
+        assert_eq!(mono_impulse_response(&mut delay, 1)[0], 0.0);
+    }
+
+    // Largest sample in the delay line after a minute of constant input at 95% feedback
+    fn buffer_peak_under_constant_input(saturation: f32) -> f32 {
+        let mut delay = Delay::new(48000.0, 2000.0);
+        delay.set_delay_time(50.0);
+        delay.set_feedback(0.95);
+        delay.set_saturation(saturation);
+        for _ in 0..48000 * 60 {
+            delay.process(0.5);
+        }
+        delay.buffer.iter().fold(0.0f32, |peak, x| peak.max(x.abs()))
+    }
+
+    #[test]
+    fn saturated_feedback_stays_bounded() {
+        // Linear repeats pile up towards 0.5 / (1 - 0.95) = 10
+        assert!(buffer_peak_under_constant_input(0.0) > 9.0);
+        // Each saturated repeat is at most 1 / drive, so the line can't pass input + 0.95 / 5
+        let peak = buffer_peak_under_constant_input(1.0);
+        assert!(peak.is_finite() && peak <= 0.5 + 0.95 / 5.0 + 1e-4, "{peak}");
+    }
+}
@@ -0,0 +1,98 @@
+const ATTACK_MS: f32 = 1.0; // Short dip instead of a hard step so the duck doesn't click
+
+// Sidechain-style pumping: every trigger dips the gain by `amount`,
+// which then climbs back to unity over the release time
+pub struct Duck {
+    amount: f32,
+    release_ms: f32,
+    gain: f32,
+    attacking: bool,
+    attack_step: f32,
+    release_step: f32,
+    sample_rate: f32,
+}
+
+impl Duck {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut duck = Duck {
+            amount: 0.5,
+            release_ms: 200.0,
+            gain: 1.0,
+            attacking: false,
+            attack_step: 1.0,
+            release_step: 1.0,
+            sample_rate,
+        };
+        duck.update_steps();
+        duck
+    }
+
+    pub fn set_amount(&mut self, amount: f32) {
+        self.amount = amount.clamp(0.0, 1.0);
+        self.update_steps();
+    }
+
+    pub fn set_release(&mut self, release_ms: f32) {
+        self.release_ms = release_ms.clamp(10.0, 2000.0);
+        self.update_steps();
+    }
+
+    pub fn get_amount(&self) -> f32 {
+        self.amount
+    }
+
+    pub fn get_release(&self) -> f32 {
+        self.release_ms
+    }
+
+    // Linear ramps, so a full dip always takes exactly attack + release
+    fn update_steps(&mut self) {
+        self.attack_step = self.amount.max(1e-6) * 1000.0 / (ATTACK_MS * self.sample_rate);
+        self.release_step = self.amount.max(1e-6) * 1000.0 / (self.release_ms * self.sample_rate);
+    }
+
+    pub fn trigger(&mut self) {
+        self.attacking = true;
+    }
+
+    pub fn reset(&mut self) {
+        self.gain = 1.0;
+        self.attacking = false;
+    }
+
+    pub fn next(&mut self) -> f32 {
+        let floor = 1.0 - self.amount;
+        if self.attacking {
+            self.gain -= self.attack_step;
+            if self.gain <= floor {
+                self.gain = floor;
+                self.attacking = false;
+            }
+        } else {
+            self.gain = (self.gain + self.release_step).min(1.0);
+        }
+        self.gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_dips_then_recovers_over_the_release() {
+        let mut duck = Duck::new(48000.0);
+        duck.set_amount(0.6);
+        duck.set_release(100.0);
+        duck.trigger();
+        // Down to the floor within the 1 ms attack
+        let attack: Vec<f32> = (0..48).map(|_| duck.next()).collect();
+        assert!(attack[0] < 1.0 && (attack[47] - 0.4).abs() < 1e-4);
+
+        let release: Vec<f32> = (0..4900).map(|_| duck.next()).collect();
+        assert!((release[2399] - 0.7).abs() < 0.01, "{}", release[2399]);
+        assert!(release.windows(2).all(|w| w[1] >= w[0]));
+        let recovered = release.iter().position(|&g| g == 1.0).unwrap();
+        assert!((4790..4810).contains(&recovered), "{recovered}");
+    }
+}
@@ -0,0 +1,122 @@
+use std::f32::consts::TAU;
+
+// Three slow LFOs at unrelated rates, so the sweeps never line up into an obvious wobble
+const LFO_RATES_HZ: [f32; 3] = [0.5, 0.9, 1.3];
+const CENTER_DELAY_MS: f32 = 10.0;
+const MAX_SWEEP_MS: f32 = 3.0; // Each line swings this far either side of the center at full depth
+
+// String-machine ensemble: three modulated delay lines, each read twice with opposite
+// sweeps for the left and right outputs, so the sides decorrelate
+pub struct Ensemble {
+    buffer: Vec<f32>,
+    write_pos: usize,
+    phases: [f32; 3],
+    phase_increments: [f32; 3],
+    depth: f32,
+    intensity: f32,
+    sample_rate: f32,
+}
+
+impl Ensemble {
+    pub fn new(sample_rate: f32) -> Self {
+        let max_samples = ((CENTER_DELAY_MS + MAX_SWEEP_MS) * sample_rate / 1000.0) as usize + 2;
+        Ensemble {
+            buffer: vec![0.0; max_samples],
+            write_pos: 0,
+            // Staggered by a third of a cycle, like the three-phase BBD clocks
+            phases: [0.0, 1.0 / 3.0, 2.0 / 3.0],
+            phase_increments: LFO_RATES_HZ.map(|rate| rate / sample_rate),
+            depth: 0.5,
+            intensity: 0.5,
+            sample_rate,
+        }
+    }
+
+    // Sweep width, 0 = static doubling to 1 = ±3 ms
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth.clamp(0.0, 1.0);
+    }
+
+    // 0 = dry to 1 = even dry/wet blend
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity.clamp(0.0, 1.0);
+    }
+
+    pub fn get_depth(&self) -> f32 {
+        self.depth
+    }
+
+    pub fn get_intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    fn read(&self, delay_ms: f32) -> f32 {
+        let delay = delay_ms * self.sample_rate / 1000.0;
+        let len = self.buffer.len();
+        let pos = self.write_pos as f32 + len as f32 - delay;
+        let index = pos as usize;
+        let frac = pos - index as f32;
+        let a = self.buffer[index % len];
+        let b = self.buffer[(index + 1) % len];
+        a + (b - a) * frac
+    }
+
+    // Mono sum of the inputs in, decorrelated pair out over the dry sides
+    pub fn process_stereo(&mut self, left: f32, right: f32) -> (f32, f32) {
+        self.buffer[self.write_pos] = 0.5 * (left + right);
+        let sweep = self.depth * MAX_SWEEP_MS;
+        let offsets = self.phases.map(|phase| (phase * TAU).sin() * sweep);
+        for (phase, increment) in self.phases.iter_mut().zip(self.phase_increments) {
+            *phase = (*phase + increment).fract();
+        }
+        let (mut wet_left, mut wet_right) = (0.0, 0.0);
+        for offset in offsets {
+            wet_left += self.read(CENTER_DELAY_MS + offset);
+            wet_right += self.read(CENTER_DELAY_MS - offset);
+        }
+
+        self.write_pos += 1;
+        if self.write_pos >= self.buffer.len() {
+            self.write_pos = 0;
+        }
+
+        let dry = 1.0 - 0.5 * self.intensity;
+        let wet = 0.5 * self.intensity / LFO_RATES_HZ.len() as f32;
+        (left * dry + wet_left * wet, right * dry + wet_right * wet)
+    }
+
+    // Mono output is the mid of the stereo pair
+    pub fn process(&mut self, input: f32) -> f32 {
+        let (left, right) = self.process_stereo(input, input);
+        0.5 * (left + right)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    // Normalized correlation of the two sides for the same noise fed to both
+    fn side_correlation(intensity: f32) -> f32 {
+        let mut ensemble = Ensemble::new(48000.0);
+        ensemble.set_depth(1.0);
+        ensemble.set_intensity(intensity);
+        let mut noise = Rng::new(5);
+        let (mut lr, mut ll, mut rr) = (0.0, 0.0, 0.0);
+        for _ in 0..96000 {
+            let x = noise.random() - 0.5;
+            let (l, r) = ensemble.process_stereo(x, x);
+            (lr, ll, rr) = (lr + l * r, ll + l * l, rr + r * r);
+        }
+        lr / (ll * rr).sqrt()
+    }
+
+    #[test]
+    fn sides_decorrelate_when_enabled() {
+        assert!((side_correlation(0.0) - 1.0).abs() < 1e-6);
+        let correlation = side_correlation(1.0);
+        // Half of each side is the shared dry signal, so the wet lines have to differ a lot to get here
+        assert!(correlation < 0.9, "{correlation}");
+    }
+}
@@ -42,6 +42,26 @@ impl Flanger {
         self.lfo.set_rate(rate);
     }
 
+    pub fn get_lfo_rate(&self) -> f32 {
+        self.lfo.get_rate()
+    }
+
+    pub fn get_delay_range(&self) -> f32 {
+        self.delay_range
+    }
+
+    pub fn get_feedback(&self) -> f32 {
+        self.feedback
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.lfo.set_seed(seed);
+    }
+
     pub fn process(&mut self, input: f32) -> f32 {
         let lfo_value = self.lfo.process();
         let delay_ms = 0.5 + (self.delay_range - 0.5) * (lfo_value * 0.5 + 0.5);
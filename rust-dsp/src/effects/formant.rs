@@ -0,0 +1,138 @@
+use std::f32::consts::PI;
+
+use crate::filter::{blend_vowels, VOWELS};
+
+const BANDWIDTHS_HZ: [f32; 3] = [80.0, 90.0, 120.0]; // Of the first, second and third formant
+const MAKEUP: f32 = 4.0; // The narrow resonators pass little of a full-range signal
+
+// Constant-peak bandpass biquad (RBJ), unity gain at the center
+struct Resonator {
+    b0: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Resonator {
+    fn new() -> Self {
+        Resonator {
+            b0: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    fn tune(&mut self, center: f32, bandwidth: f32, sample_rate: f32) {
+        let center = center.clamp(20.0, sample_rate * 0.45);
+        let w0 = 2.0 * PI * center / sample_rate;
+        let alpha = w0.sin() * bandwidth / (2.0 * center);
+        let a0 = 1.0 + alpha;
+        self.b0 = alpha / a0;
+        self.a1 = -2.0 * w0.cos() / a0;
+        self.a2 = (1.0 - alpha) / a0;
+    }
+
+    // Transposed direct form II; b1 is 0 and b2 is -b0
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.b0 * input + self.z1;
+        self.z1 = -self.a1 * output + self.z2;
+        self.z2 = -self.b0 * input - self.a2 * output;
+        output
+    }
+}
+
+// Vowel resonances: three parallel bandpass biquads on the formants of a
+// vowel, morphing toward the next vowel (A -> E -> I -> O -> U -> A)
+pub struct Formant {
+    resonators: [Resonator; 3],
+    gains: [f32; 3],
+    vowel: u8,
+    morph: f32,
+    sample_rate: f32,
+}
+
+impl Formant {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut formant = Formant {
+            resonators: [Resonator::new(), Resonator::new(), Resonator::new()],
+            gains: [0.0; 3],
+            vowel: 0,
+            morph: 0.0,
+            sample_rate,
+        };
+        formant.update();
+        formant
+    }
+
+    // 0 = A, 1 = E, 2 = I, 3 = O, 4 = U
+    pub fn set_vowel(&mut self, vowel: u8) {
+        self.vowel = vowel.min(VOWELS.len() as u8 - 1);
+        self.update();
+    }
+
+    // 0 = the vowel as set, 1 = the next one
+    pub fn set_morph(&mut self, t: f32) {
+        self.morph = t.clamp(0.0, 1.0);
+        self.update();
+    }
+
+    pub fn get_vowel(&self) -> u8 {
+        self.vowel
+    }
+
+    pub fn get_morph(&self) -> f32 {
+        self.morph
+    }
+
+    fn update(&mut self) {
+        let vowel = self.vowel as usize;
+        let formants = blend_vowels(vowel, (vowel + 1) % VOWELS.len(), self.morph);
+        for (i, (center, gain)) in formants.into_iter().enumerate() {
+            self.resonators[i].tune(center, BANDWIDTHS_HZ[i], self.sample_rate);
+            self.gains[i] = gain * MAKEUP;
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.resonators
+            .iter_mut()
+            .zip(&self.gains)
+            .map(|(resonator, gain)| resonator.process(input) * gain)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Magnitude of the impulse response every 10 Hz up to 4 kHz
+    fn response_every_10_hz(formant: &mut Formant) -> Vec<f32> {
+        let response: Vec<f32> = (0..8192).map(|n| formant.process(if n == 0 { 1.0 } else { 0.0 })).collect();
+        (0..400)
+            .map(|k| {
+                let w = 2.0 * PI * (10 * k) as f32 / 48000.0;
+                let (re, im) = response.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, &x)| {
+                    (re + x * (w * n as f32).cos(), im - x * (w * n as f32).sin())
+                });
+                re.hypot(im)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn vowel_a_peaks_at_its_formants() {
+        let response = response_every_10_hz(&mut Formant::new(48000.0));
+        let peaks: Vec<f32> = (1..response.len() - 1)
+            .filter(|&k| response[k] > response[k - 1] && response[k] > response[k + 1])
+            .map(|k| (10 * k) as f32)
+            .collect();
+        assert_eq!(peaks.len(), 3, "{peaks:?}");
+        for (peak, (center, _)) in peaks.iter().zip(VOWELS[0]) {
+            assert!((peak - center).abs() < 0.03 * center, "{peak} Hz vs {center} Hz");
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::f32::consts::PI;
+
+// Olli Niemitalo's Hilbert pair: two chains of second-order allpasses whose
+// outputs stay 90 degrees apart (within ~0.7 degrees) from about 20 Hz to 20 kHz
+const REAL_COEFFS: [f32; 4] = [0.692_387_8, 0.936_065_43, 0.988_229_5, 0.998_748_85];
+const IMAG_COEFFS: [f32; 4] = [0.402_192_12, 0.856_171_1, 0.972_290_95, 0.995_288_5];
+
+// y[n] = a^2 (x[n] + y[n-2]) - x[n-2]
+#[derive(Clone, Copy)]
+struct AllpassStage {
+    coeff: f32,
+    inputs: [f32; 2],
+    outputs: [f32; 2],
+}
+
+impl AllpassStage {
+    fn new(a: f32) -> Self {
+        AllpassStage {
+            coeff: a * a,
+            inputs: [0.0; 2],
+            outputs: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let output = self.coeff * (input + self.outputs[1]) - self.inputs[1];
+        self.inputs = [input, self.inputs[0]];
+        self.outputs = [output, self.outputs[0]];
+        output
+    }
+}
+
+// Single-sideband shifter: every partial moves by the same number of Hz, so
+// harmonic sounds turn inharmonic (unlike the pitch shifter, which scales them)
+pub struct FreqShifter {
+    real: [AllpassStage; 4],
+    imag: [AllpassStage; 4],
+    real_delayed: f32, // The real chain runs one sample behind the imaginary one
+    phase: f32,
+    shift_hz: f32,
+    mix: f32,
+    sample_rate: f32,
+}
+
+impl FreqShifter {
+    pub fn new(sample_rate: f32) -> Self {
+        FreqShifter {
+            real: REAL_COEFFS.map(AllpassStage::new),
+            imag: IMAG_COEFFS.map(AllpassStage::new),
+            real_delayed: 0.0,
+            phase: 0.0,
+            shift_hz: 0.0,
+            mix: 0.5,
+            sample_rate,
+        }
+    }
+
+    // Negative shifts move everything down; partials pushed below 0 Hz fold back up
+    pub fn set_shift_hz(&mut self, hz: f32) {
+        let limit = self.sample_rate * 0.25;
+        self.shift_hz = hz.clamp(-limit, limit);
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    pub fn get_shift_hz(&self) -> f32 {
+        self.shift_hz
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let real = self.real_delayed;
+        self.real_delayed = self.real.iter_mut().fold(input, |x, stage| stage.process(x));
+        let imag = self.imag.iter_mut().fold(input, |x, stage| stage.process(x));
+
+        let (sin, cos) = (self.phase * 2.0 * PI).sin_cos();
+        self.phase = (self.phase + self.shift_hz / self.sample_rate).rem_euclid(1.0);
+        let shifted = real * cos + imag * sin;
+
+        input * (1.0 - self.mix) + shifted * self.mix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::Spectrum;
+
+    // Strongest frequency of the shifted sine, and the level of its mirror image below
+    fn shifted_peak(frequency: f32) -> (f32, f32) {
+        let mut shifter = FreqShifter::new(48000.0);
+        shifter.set_shift_hz(100.0);
+        shifter.set_mix(1.0);
+        let output: Vec<f32> = (0..16384).map(|n| shifter.process((2.0 * PI * frequency * n as f32 / 48000.0).sin())).collect();
+
+        let mut bins = [0.0; 4096];
+        Spectrum::new().analyze(&output[8192..], &mut bins);
+        let peak = (0..bins.len()).max_by(|&a, &b| bins[a].total_cmp(&bins[b])).unwrap();
+        let mirror = ((frequency - 100.0) * 8192.0 / 48000.0).round() as usize;
+        (peak as f32 * 48000.0 / 8192.0, bins[peak] - bins[mirror])
+    }
+
+    #[test]
+    fn shift_adds_hz_rather_than_scaling() {
+        // Scaling 400 Hz up to 500 Hz would take 800 Hz to 1000 Hz rather than 900
+        for frequency in [400.0, 800.0] {
+            let (peak, rejection) = shifted_peak(frequency);
+            assert!((peak - (frequency + 100.0)).abs() < 6.0, "{frequency} Hz -> {peak} Hz");
+            assert!(rejection > 30.0, "{frequency} Hz: mirror {rejection} dB down");
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::clock::{division_to_samples, DEFAULT_TEMPO_BPM};
+
+pub const MAX_GATE_STEPS: usize = 16;
+const FADE_MS: f32 = 2.0; // Ramp on each step edge so the gate doesn't click
+
+// Tempo-synced trance gate: steps through an on/off pattern, muting the off steps
+pub struct Gate {
+    pattern: Vec<bool>,
+    division: u8,
+    bpm: f32,
+    step: usize,
+    samples_into_step: f32,
+    gain: f32,
+    fade_increment: f32,
+    sample_rate: f32,
+}
+
+impl Gate {
+    pub fn new(sample_rate: f32) -> Self {
+        Gate {
+            pattern: vec![true; MAX_GATE_STEPS],
+            division: 4,
+            bpm: DEFAULT_TEMPO_BPM,
+            step: 0,
+            samples_into_step: 0.0,
+            gain: 1.0,
+            fade_increment: 1000.0 / (FADE_MS * sample_rate),
+            sample_rate,
+        }
+    }
+
+    // Up to 16 steps; an empty pattern lets everything through
+    pub fn set_pattern(&mut self, steps: &[bool]) {
+        self.pattern = steps.iter().copied().take(MAX_GATE_STEPS).collect();
+        if self.pattern.is_empty() {
+            self.pattern.push(true);
+        }
+        self.step %= self.pattern.len();
+    }
+
+    pub fn get_pattern(&self) -> &[bool] {
+        &self.pattern
+    }
+
+    pub fn set_division(&mut self, division: u8) {
+        self.division = division;
+    }
+
+    pub fn get_division(&self) -> u8 {
+        self.division
+    }
+
+    pub fn set_tempo(&mut self, bpm: f32) {
+        self.bpm = bpm;
+    }
+
+    // Back to the first step, e.g. when the gate is switched on
+    pub fn restart(&mut self) {
+        self.step = 0;
+        self.samples_into_step = 0.0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let step_samples = division_to_samples(self.division, self.bpm, self.sample_rate).max(1.0);
+        if self.samples_into_step >= step_samples {
+            self.samples_into_step -= step_samples;
+            self.step = (self.step + 1) % self.pattern.len();
+        }
+        self.samples_into_step += 1.0;
+
+        let target = if self.pattern[self.step] { 1.0 } else { 0.0 };
+        if self.gain < target {
+            self.gain = (self.gain + self.fade_increment).min(target);
+        } else if self.gain > target {
+            self.gain = (self.gain - self.fade_increment).max(target);
+        }
+
+        input * self.gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternating_pattern_passes_and_mutes() {
+        let mut gate = Gate::new(48000.0);
+        gate.set_pattern(&[true, false]);
+        gate.restart();
+        let step = division_to_samples(4, DEFAULT_TEMPO_BPM, 48000.0) as usize;
+        let fade = (FADE_MS * 48.0) as usize + 1;
+
+        let output: Vec<f32> = (0..6 * step).map(|_| gate.process(1.0)).collect();
+        for (i, block) in output.chunks(step).enumerate() {
+            let expected = if i % 2 == 0 { 1.0 } else { 0.0 };
+            assert!(block[fade..].iter().all(|&x| x == expected), "step {i}");
+        }
+        // The edges ramp rather than jump
+        let largest_jump = output.windows(2).map(|w| (w[1] - w[0]).abs()).fold(0.0, f32::max);
+        assert!(largest_jump < 0.02, "{largest_jump}");
+    }
+}
@@ -1,3 +1,20 @@
+// Always glides from the voice's previous pitch; Legato (fingered portamento) only glides
+// while another note is held, from that note's pitch, and starts lone notes at pitch
+#[derive(Clone, Copy, PartialEq)]
+pub enum GlideMode {
+    Always = 0,
+    Legato = 1,
+}
+
+impl GlideMode {
+    pub fn from_u8(mode: u8) -> Self {
+        match mode {
+            1 => GlideMode::Legato,
+            _ => GlideMode::Always,
+        }
+    }
+}
+
 pub struct Glide {
     current_freq: f32,
     target_freq: f32,
@@ -21,10 +38,19 @@ impl Glide {
         self.glide_time = time_ms;
     }
 
+    pub fn get_glide_time(&self) -> f32 {
+        self.glide_time
+    }
+
+    // Frequency the last process call glided to
+    pub fn get_current(&self) -> f32 {
+        self.current_freq
+    }
+
     pub fn set_target(&mut self, target_freq: f32) {
         self.target_freq = target_freq;
         if self.glide_time > 0.0 {
-            let samples = (self.glide_time * self.sample_rate / 1000.0) as f32;
+            let samples = self.glide_time * self.sample_rate / 1000.0;
             self.increment = (target_freq - self.current_freq) / samples;
         } else {
             self.current_freq = target_freq;
@@ -32,6 +58,13 @@ impl Glide {
         }
     }
 
+    // Moves straight to a pitch, dropping any glide in progress
+    pub fn jump_to(&mut self, freq: f32) {
+        self.current_freq = freq;
+        self.target_freq = freq;
+        self.increment = 0.0;
+    }
+
     pub fn process(&mut self) -> f32 {
         if (self.current_freq - self.target_freq).abs() < 0.1 {
             self.current_freq = self.target_freq;
@@ -41,9 +74,5 @@ impl Glide {
         }
         self.current_freq
     }
-
-    pub fn get_frequency(&self) -> f32 {
-        self.current_freq
-    }
 }
 
@@ -3,6 +3,17 @@ pub mod reverb;
 pub mod flanger;
 pub mod tremolo;
 pub mod glide;
+pub mod ringmod;
+pub mod gate;
+pub mod autopan;
+pub mod duck;
+pub mod formant;
+pub mod pitchshift;
+pub mod convolution;
+pub mod freqshift;
+pub mod widener;
+pub mod noisegate;
+pub mod ensemble;
 
 // Effects will be integrated into the audio engine in future updates
 // pub use delay::Delay;
@@ -0,0 +1,127 @@
+use crate::follower::EnvelopeFollower;
+
+// Detector times: fast enough to catch transients, slow enough not to follow the waveform
+const DETECT_ATTACK_MS: f32 = 0.1;
+const DETECT_RELEASE_MS: f32 = 10.0;
+
+// Mutes the signal while its level stays under the threshold. The gate opens as soon as
+// the level crosses it, stays open for the hold time after it drops back, then closes;
+// the gain moves at the attack/release rates so neither edge clicks
+pub struct NoiseGate {
+    detector: EnvelopeFollower,
+    gain: EnvelopeFollower, // Follows 1 (open) or 0 (closed)
+    threshold_db: f32,
+    threshold: f32,
+    hold_ms: f32,
+    hold_samples: u32,
+    hold_counter: u32,
+    sample_rate: f32,
+}
+
+impl NoiseGate {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut gate = NoiseGate {
+            detector: EnvelopeFollower::new(sample_rate, DETECT_ATTACK_MS, DETECT_RELEASE_MS),
+            gain: EnvelopeFollower::new(sample_rate, 1.0, 100.0),
+            threshold_db: 0.0,
+            threshold: 1.0,
+            hold_ms: 0.0,
+            hold_samples: 0,
+            hold_counter: 0,
+            sample_rate,
+        };
+        gate.set_threshold_db(-50.0);
+        gate.set_hold_ms(50.0);
+        gate
+    }
+
+    pub fn set_threshold_db(&mut self, db: f32) {
+        self.threshold_db = db.clamp(-96.0, 0.0);
+        self.threshold = 10f32.powf(self.threshold_db / 20.0);
+    }
+
+    pub fn set_attack_ms(&mut self, ms: f32) {
+        self.gain.set_attack(ms.clamp(0.1, 100.0));
+    }
+
+    pub fn set_release_ms(&mut self, ms: f32) {
+        self.gain.set_release(ms.clamp(5.0, 2000.0));
+    }
+
+    pub fn set_hold_ms(&mut self, ms: f32) {
+        self.hold_ms = ms.clamp(0.0, 1000.0);
+        self.hold_samples = (self.hold_ms * 0.001 * self.sample_rate) as u32;
+    }
+
+    pub fn get_threshold_db(&self) -> f32 {
+        self.threshold_db
+    }
+
+    pub fn get_attack_ms(&self) -> f32 {
+        self.gain.get_attack()
+    }
+
+    pub fn get_release_ms(&self) -> f32 {
+        self.gain.get_release()
+    }
+
+    pub fn get_hold_ms(&self) -> f32 {
+        self.hold_ms
+    }
+
+    // Closed, with an empty detector; the next loud sample opens it at the attack rate
+    pub fn reset(&mut self) {
+        self.detector.reset();
+        self.gain.reset();
+        self.hold_counter = 0;
+    }
+
+    fn next_gain(&mut self, input: f32) -> f32 {
+        let level = self.detector.process(input);
+        let open = if level >= self.threshold {
+            self.hold_counter = self.hold_samples;
+            true
+        } else if self.hold_counter > 0 {
+            self.hold_counter -= 1;
+            true
+        } else {
+            false
+        };
+        self.gain.process(if open { 1.0 } else { 0.0 })
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        input * self.next_gain(input)
+    }
+
+    // Linked stereo: one gain from the louder side, so the image doesn't shift
+    pub fn process_stereo(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let gain = self.next_gain(left.abs().max(right.abs()));
+        (left * gain, right * gain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32, n: usize) -> f32 {
+        amplitude * (n as f32 * std::f32::consts::TAU / 48.0).sin()
+    }
+
+    #[test]
+    fn quiet_tail_is_muted_after_the_hold_and_loud_signal_passes() {
+        let mut gate = NoiseGate::new(48000.0);
+        gate.set_threshold_db(-40.0);
+        gate.set_hold_ms(50.0);
+        gate.set_release_ms(20.0);
+        // Loud: through at unity once the 1 ms attack has opened the gate
+        let loud: Vec<f32> = (0..4800).map(|n| gate.process(sine(0.5, n)) - sine(0.5, n)).collect();
+        assert!(loud[480..].iter().all(|d| d.abs() < 1e-3));
+        // -60 dB: still open through the 50 ms hold, then down by 40 dB 150 ms later
+        let quiet: Vec<f32> = (0..9600).map(|n| gate.process(sine(0.001, n))).collect();
+        let peak = |block: &[f32]| block.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        assert!(peak(&quiet[1440..2160]) > 0.0009);
+        assert!(peak(&quiet[9120..]) < 1e-5);
+    }
+}
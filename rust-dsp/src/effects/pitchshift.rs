@@ -0,0 +1,112 @@
+use std::f32::consts::PI;
+
+const WINDOW_MS: f32 = 50.0;
+
+// Delay-line pitch shifter: two read heads sweep through a short window at the
+// shifted speed, half a window apart, and each fades out with a raised cosine
+// just before it jumps back, so the splices don't click
+pub struct PitchShifter {
+    buffer: Vec<f32>,
+    write_pos: usize,
+    window: f32, // Samples
+    phase: f32,  // Position of the first head in the window, 0-1
+    ratio: f32,
+    semitones: f32,
+    mix: f32,
+}
+
+impl PitchShifter {
+    pub fn new(sample_rate: f32) -> Self {
+        let window = (WINDOW_MS * sample_rate / 1000.0).max(16.0);
+        PitchShifter {
+            buffer: vec![0.0; window as usize + 2],
+            write_pos: 0,
+            window,
+            phase: 0.0,
+            ratio: 1.0,
+            semitones: 0.0,
+            mix: 0.5,
+        }
+    }
+
+    // Output frequency over input frequency, 2.0 = an octave up
+    pub fn set_ratio(&mut self, ratio: f32) {
+        self.ratio = ratio.clamp(0.25, 4.0);
+        self.semitones = 12.0 * self.ratio.log2();
+    }
+
+    // -24 to +24, so two octaves either way
+    pub fn set_semitones(&mut self, semitones: f32) {
+        self.semitones = semitones.clamp(-24.0, 24.0);
+        self.ratio = (self.semitones / 12.0).exp2();
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    pub fn get_semitones(&self) -> f32 {
+        self.semitones
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.phase = 0.0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        input * (1.0 - self.mix) + self.process_wet(input) * self.mix
+    }
+
+    // Shifted signal only
+    pub fn process_wet(&mut self, input: f32) -> f32 {
+        self.buffer[self.write_pos] = input;
+
+        // Reading faster than writing shortens the delay by (ratio - 1) samples per sample
+        self.phase = (self.phase - (self.ratio - 1.0) / self.window).rem_euclid(1.0);
+        let second = (self.phase + 0.5).fract();
+        let fade = (self.phase * PI).sin();
+        let output = self.read(self.phase * self.window) * fade * fade
+            + self.read(second * self.window) * (1.0 - fade * fade);
+
+        self.write_pos += 1;
+        if self.write_pos >= self.buffer.len() {
+            self.write_pos = 0;
+        }
+        output
+    }
+
+    fn read(&self, delay: f32) -> f32 {
+        let len = self.buffer.len();
+        let whole = delay as usize;
+        let fraction = delay - whole as f32;
+        let a = self.buffer[(self.write_pos + len - whole) % len];
+        let b = self.buffer[(self.write_pos + len - whole - 1) % len];
+        a + (b - a) * fraction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::Spectrum;
+
+    #[test]
+    fn octave_up_doubles_the_frequency() {
+        let mut shifter = PitchShifter::new(48000.0);
+        shifter.set_semitones(12.0);
+        shifter.set_mix(1.0);
+        let output: Vec<f32> = (0..16384).map(|n| shifter.process((2.0 * PI * 375.0 * n as f32 / 48000.0).sin())).collect();
+
+        let mut bins = [0.0; 4096];
+        Spectrum::new().analyze(&output[8192..], &mut bins);
+        let peak = (0..bins.len()).max_by(|&a, &b| bins[a].total_cmp(&bins[b])).unwrap() as f32 * 48000.0 / 8192.0;
+        // The splices modulate it, so the energy spreads a little either side
+        assert!((peak - 750.0).abs() < 25.0, "{peak} Hz");
+        assert!(bins[128] > bins[64] + 20.0, "{} vs {} dB", bins[128], bins[64]);
+    }
+}
@@ -1,15 +1,167 @@
+use std::f32::consts::FRAC_PI_2;
+
+use super::pitchshift::PitchShifter;
+use crate::smoother::Smoother;
+
+// Freeverb's comb feedback range: room size 0-1 maps to 0.7-0.98
+const ROOM_FEEDBACK_MIN: f32 = 0.7;
+const ROOM_FEEDBACK_RANGE: f32 = 0.28;
+const ROOM_SMOOTHING_MS: f32 = 50.0; // Feedback slews instead of stepping, so size changes don't click
+// Freeverb's damping scale: at 1.0 the one-pole still moves instead of freezing the tail
+const DAMPING_SCALE: f32 = 0.4;
+// The comb sum is scaled by sqrt(1 - g^2), which holds a noise-fed tail at one level
+// whatever the feedback g; this is the feedback that scale is unity at
+const REFERENCE_FEEDBACK: f32 = 0.425;
+const ALLPASS_FEEDBACK: f32 = 0.5; // Freeverb's
+// Output scale of the tank; sets the wet level now that the loops themselves aren't turned down
+const TANK_GAIN: f32 = 0.2;
+// Early reflections at room size 1: (ms, gain), thinning out over the first 80 ms
+const EARLY_TAPS: [(f32, f32); 8] = [
+    (7.0, 0.84),
+    (13.0, 0.72),
+    (19.0, 0.63),
+    (27.0, 0.53),
+    (37.0, 0.44),
+    (48.0, 0.36),
+    (61.0, 0.29),
+    (79.0, 0.22),
+];
+const EARLY_GAIN: f32 = 0.25; // Brings the reflections to about the level of the tail
+const EARLY_MIN_SCALE: f32 = 0.4; // At room size 0 the pattern shrinks to 40% of its length
+const EARLY_PAN: f32 = 0.5; // Taps alternate this far left and right on the stereo output
+// The wet output passes untouched up to here and rounds off toward 1 above it
+const LIMIT_KNEE: f32 = 0.5;
+
+// Freeverb's stereo spread: the right tank's delays are this much longer (at 44.1 kHz)
+const STEREO_SPREAD: usize = 23;
+
+const FREEZE_RAMP_MS: f32 = 100.0; // Both ways, so neither the lock nor the release thumps
+
+const MAX_PREDELAY_MS: f32 = 200.0;
+const PREDELAY_FADE_MS: f32 = 30.0; // Crossfade to a new pre-delay instead of jumping the read
+
+// Wet at about the old fixed 6%, with the dry just under unity
+const DEFAULT_MIX: f32 = 0.04;
+
+// Shimmer return at full amount; the octave-up tail builds slowly without running away
+const SHIMMER_FEEDBACK: f32 = 0.9;
+// The shifter passes the lows through unshifted, so they are cut from the return
+// instead of looping through the tank
+const SHIMMER_HIGHPASS_HZ: f32 = 300.0;
+
 pub struct Reverb {
-    comb_filters: Vec<CombFilter>,
-    allpass_filters: Vec<AllpassFilter>,
+    tank: Tank,
+    tank_right: Tank, // Only run by process_stereo
+    width: f32,
+    early: EarlyReflections,
+    early_late: f32,
+    early_gain: f32,
+    late_gain: f32,
+    feedback: Smoother,
     room_size: f32,
     damping: f32,
+    saturation: bool,
+    shimmer: f32,
+    shifter: PitchShifter,
+    shimmer_return: f32, // Pitched-up wet output, fed back into the combs on the next sample
+    shimmer_lows: f32,
+    shimmer_highpass: f32,
+    mix: f32,
+    dry_gain: f32,
+    wet_gain: f32,
+    predelay_buffer: Vec<f32>,
+    predelay_pos: usize,
+    predelay_ms: f32,
+    predelay: usize,      // Samples
+    predelay_from: usize, // Previous pre-delay, while the crossfade runs
+    predelay_fade: f32,   // Weight of `predelay`; 1 once no crossfade is running
+    predelay_fade_step: f32,
+    frozen: bool,
+    freeze: f32, // Ramps 0-1; at 1 the combs loop losslessly and take no input
+    freeze_step: f32,
+    sample_rate: f32,
+}
+
+fn room_feedback(room_size: f32) -> f32 {
+    ROOM_FEEDBACK_MIN + room_size * ROOM_FEEDBACK_RANGE
+}
+
+// Unity below the knee, then a tanh curve with matching slope that never passes 1
+fn soft_limit(x: f32) -> f32 {
+    let magnitude = x.abs();
+    if magnitude <= LIMIT_KNEE {
+        return x;
+    }
+    let headroom = 1.0 - LIMIT_KNEE;
+    (LIMIT_KNEE + headroom * ((magnitude - LIMIT_KNEE) / headroom).tanh()).copysign(x)
+}
+
+// Per-sample state of the comb loops, shared by both tanks
+#[derive(Clone, Copy)]
+struct LoopSettings {
+    feedback: f32,
+    saturation: bool,
+    freeze: f32,
+}
+
+// Discrete taps off a plain delay line, ahead of the tail building up
+struct EarlyReflections {
+    buffer: Vec<f32>,
+    write_pos: usize, // Slot of the newest sample
+    sample_rate: f32,
 }
 
+impl EarlyReflections {
+    fn new(sample_rate: f32) -> Self {
+        let longest = EARLY_TAPS[EARLY_TAPS.len() - 1].0;
+        EarlyReflections {
+            buffer: vec![0.0; (longest * sample_rate / 1000.0) as usize + 2],
+            write_pos: 0,
+            sample_rate,
+        }
+    }
+
+    fn write(&mut self, input: f32) {
+        self.write_pos += 1;
+        if self.write_pos >= self.buffer.len() {
+            self.write_pos = 0;
+        }
+        self.buffer[self.write_pos] = input;
+    }
+
+    // (left, right), with the tap times stretched by `scale`
+    fn read(&self, scale: f32) -> (f32, f32) {
+        let len = self.buffer.len();
+        let (mut left, mut right) = (0.0, 0.0);
+        for (i, &(time_ms, gain)) in EARLY_TAPS.iter().enumerate() {
+            let pos = self.write_pos as f32 + len as f32 - time_ms * scale * self.sample_rate / 1000.0;
+            let index = pos as usize;
+            let frac = pos - index as f32;
+            let a = self.buffer[index % len];
+            let b = self.buffer[(index + 1) % len];
+            let tap = (a + (b - a) * frac) * gain * EARLY_GAIN;
+            let pan = if i % 2 == 0 { -EARLY_PAN } else { EARLY_PAN };
+            left += tap * (1.0 - pan);
+            right += tap * (1.0 + pan);
+        }
+        (left, right)
+    }
+}
+
+// 8 parallel combs into 4 series allpasses
+struct Tank {
+    comb_filters: Vec<CombFilter>,
+    allpass_filters: Vec<AllpassFilter>,
+}
+
+// Freeverb's lowpass-feedback comb: the damping one-pole sits in the loop only,
+// the output is the plain delayed sample
 struct CombFilter {
     buffer: Vec<f32>,
     write_pos: usize,
-    feedback: f32,
-    filter_state: f32,
+    filter_store: f32,
+    damp1: f32,
+    damp2: f32,
 }
 
 struct AllpassFilter {
@@ -19,65 +171,294 @@ struct AllpassFilter {
 
 impl Reverb {
     pub fn new(sample_rate: f32) -> Self {
-        // Freeverb-style reverb with 8 comb and 4 allpass filters
-        // Scale delays based on sample rate (base is 44.1kHz)
-        let scale = sample_rate / 44100.0;
-        let comb_delays = vec![1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
-        let allpass_delays = vec![556, 441, 341, 225];
+        let mut shifter = PitchShifter::new(sample_rate);
+        shifter.set_ratio(2.0);
 
-        let mut comb_filters = Vec::new();
-        for delay in comb_delays {
-            let scaled_delay = (delay as f32 * scale) as usize;
-            comb_filters.push(CombFilter::new(scaled_delay));
-        }
-
-        let mut allpass_filters = Vec::new();
-        for delay in allpass_delays {
-            let scaled_delay = (delay as f32 * scale) as usize;
-            allpass_filters.push(AllpassFilter::new(scaled_delay));
-        }
-
-        Reverb {
-            comb_filters,
-            allpass_filters,
+        let mut reverb = Reverb {
+            tank: Tank::new(sample_rate, 0),
+            tank_right: Tank::new(sample_rate, STEREO_SPREAD),
+            width: 1.0,
+            early: EarlyReflections::new(sample_rate),
+            early_late: 1.0,
+            early_gain: 0.0,
+            late_gain: 1.0,
+            feedback: Smoother::new(sample_rate, room_feedback(0.5), ROOM_SMOOTHING_MS),
             room_size: 0.5,
             damping: 0.5,
-        }
+            saturation: false,
+            shimmer: 0.0,
+            shifter,
+            shimmer_return: 0.0,
+            shimmer_lows: 0.0,
+            shimmer_highpass: 1.0 - (-2.0 * std::f32::consts::PI * SHIMMER_HIGHPASS_HZ / sample_rate).exp(),
+            mix: 0.0,
+            dry_gain: 1.0,
+            wet_gain: 0.0,
+            predelay_buffer: vec![0.0; (MAX_PREDELAY_MS * sample_rate / 1000.0) as usize + 1],
+            predelay_pos: 0,
+            predelay_ms: 0.0,
+            predelay: 0,
+            predelay_from: 0,
+            predelay_fade: 1.0,
+            predelay_fade_step: 1000.0 / (PREDELAY_FADE_MS * sample_rate),
+            frozen: false,
+            freeze: 0.0,
+            freeze_step: 1000.0 / (FREEZE_RAMP_MS * sample_rate),
+            sample_rate,
+        };
+        reverb.set_mix(DEFAULT_MIX);
+        reverb.set_damping(reverb.damping);
+        reverb
     }
 
     pub fn set_room_size(&mut self, size: f32) {
         self.room_size = size.clamp(0.0, 1.0);
-        // Further reduced feedback to prevent distortion (0.35 to 0.5 range)
-        let feedback = self.room_size * 0.15 + 0.35;
-        for comb in &mut self.comb_filters {
-            comb.set_feedback(feedback);
-        }
+        self.feedback.set_target(room_feedback(self.room_size));
     }
 
     pub fn set_damping(&mut self, damping: f32) {
         self.damping = damping.clamp(0.0, 1.0);
+        let damp1 = self.damping * DAMPING_SCALE;
+        for comb in self.tank.comb_filters.iter_mut().chain(&mut self.tank_right.comb_filters) {
+            comb.set_damping(damp1);
+        }
+    }
+
+    // Soft-clips the comb sum so loud input rounds off instead of spiking
+    pub fn set_saturation(&mut self, enabled: bool) {
+        self.saturation = enabled;
+    }
+
+    pub fn get_saturation(&self) -> bool {
+        self.saturation
+    }
+
+    // Feeds the tail back through an octave-up shifter, so it keeps rising as it rings
+    pub fn set_shimmer(&mut self, amount: f32) {
+        self.shimmer = amount.clamp(0.0, 1.0);
+        if self.shimmer == 0.0 {
+            self.shifter.reset();
+            self.shimmer_return = 0.0;
+            self.shimmer_lows = 0.0;
+        }
+    }
+
+    pub fn get_shimmer(&self) -> f32 {
+        self.shimmer
+    }
+
+    // Equal-power dry/wet: 0 = dry only, 1 = tail only. The tank runs either way
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+        let (wet, dry) = (self.mix * FRAC_PI_2).sin_cos();
+        // cos(pi / 2) is only nearly 0 in f32
+        self.dry_gain = if self.mix >= 1.0 { 0.0 } else { dry };
+        self.wet_gain = wet;
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+
+    // Gap between the dry attack and the reverb onset, 0-200 ms. 0 adds no latency
+    pub fn set_predelay(&mut self, ms: f32) {
+        self.predelay_ms = ms.clamp(0.0, MAX_PREDELAY_MS);
+    }
+
+    pub fn get_predelay(&self) -> f32 {
+        self.predelay_ms
+    }
+
+    // Stereo output only: 0 = the same mono tail on both sides, 1 = each side its own tank
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 1.0);
+    }
+
+    pub fn get_width(&self) -> f32 {
+        self.width
+    }
+
+    // Holds the current tail indefinitely: the combs stop taking input and loop at unity
+    // without damping, while the dry signal keeps passing
+    pub fn set_freeze(&mut self, enabled: bool) {
+        self.frozen = enabled;
+    }
+
+    fn next_freeze(&mut self) -> f32 {
+        let target = if self.frozen { 1.0 } else { 0.0 };
+        if self.freeze < target {
+            self.freeze = (self.freeze + self.freeze_step).min(1.0);
+        } else if self.freeze > target {
+            self.freeze = (self.freeze - self.freeze_step).max(0.0);
+        }
+        self.freeze
+    }
+
+    // Equal-power balance of the early reflections against the diffuse tail: 0 = early
+    // only, 1 = tail only (the default), which skips the reflection taps altogether
+    pub fn set_early_late_balance(&mut self, balance: f32) {
+        self.early_late = balance.clamp(0.0, 1.0);
+        let (early, late) = ((1.0 - self.early_late) * FRAC_PI_2).sin_cos();
+        self.early_gain = early;
+        self.late_gain = late;
+    }
+
+    pub fn get_early_late_balance(&self) -> f32 {
+        self.early_late
+    }
+
+    pub fn get_room_size(&self) -> f32 {
+        self.room_size
+    }
+
+    pub fn get_damping(&self) -> f32 {
+        self.damping
     }
 
     pub fn process(&mut self, input: f32) -> f32 {
+        let wet = self.process_wet(input);
+        // The tank keeps running at mix 0, but adding its silence would turn -0.0 into 0.0
+        if self.wet_gain == 0.0 {
+            return input;
+        }
+        input * self.dry_gain + wet * self.wet_gain
+    }
+
+    // Both tanks are fed the mid signal; their slightly different delays decorrelate
+    // the sides, and the width crossfeeds them back toward mono
+    pub fn process_stereo(&mut self, left: f32, right: f32) -> (f32, f32) {
+        let input = self.predelayed((left + right) * 0.5);
+        let loop_settings = self.next_loop_settings();
+        let late_left = self.tank.process(input + self.shimmer_return, loop_settings);
+        let late_right = self.tank_right.process(input + self.shimmer_return, loop_settings);
+        let (wet_left, wet_right) = match self.next_early(input, loop_settings) {
+            Some((early_left, early_right)) => (
+                soft_limit(late_left * self.late_gain + early_left * self.early_gain),
+                soft_limit(late_right * self.late_gain + early_right * self.early_gain),
+            ),
+            None => (soft_limit(late_left), soft_limit(late_right)),
+        };
+        self.update_shimmer((wet_left + wet_right) * 0.5);
+        if self.wet_gain == 0.0 {
+            return (left, right);
+        }
+
+        let direct = (1.0 + self.width) * 0.5 * self.wet_gain;
+        let cross = (1.0 - self.width) * 0.5 * self.wet_gain;
+        (
+            left * self.dry_gain + wet_left * direct + wet_right * cross,
+            right * self.dry_gain + wet_right * direct + wet_left * cross,
+        )
+    }
+
+    // Written before it is read, so a pre-delay of 0 hands back the input itself
+    fn predelayed(&mut self, input: f32) -> f32 {
+        let len = self.predelay_buffer.len();
+        self.predelay_buffer[self.predelay_pos] = input;
+        let target = ((self.predelay_ms * self.sample_rate / 1000.0) as usize).min(len - 1);
+        if self.predelay_fade < 1.0 {
+            self.predelay_fade = (self.predelay_fade + self.predelay_fade_step).min(1.0);
+        } else if self.predelay != target {
+            self.predelay_from = self.predelay;
+            self.predelay = target;
+            self.predelay_fade = self.predelay_fade_step;
+        }
+        let read = |delay: usize| self.predelay_buffer[(self.predelay_pos + len - delay) % len];
+        let output = if self.predelay_fade < 1.0 {
+            read(self.predelay_from) * (1.0 - self.predelay_fade) + read(self.predelay) * self.predelay_fade
+        } else {
+            read(self.predelay)
+        };
+
+        self.predelay_pos += 1;
+        if self.predelay_pos >= len {
+            self.predelay_pos = 0;
+        }
+        output
+    }
+
+    fn process_wet(&mut self, input: f32) -> f32 {
+        let input = self.predelayed(input);
+        let loop_settings = self.next_loop_settings();
+        let late = self.tank.process(input + self.shimmer_return, loop_settings);
+        let output = match self.next_early(input, loop_settings) {
+            // The mono sum of the reflections is the mid of the panned pair
+            Some((early_left, early_right)) => {
+                soft_limit(late * self.late_gain + (early_left + early_right) * 0.5 * self.early_gain)
+            }
+            None => soft_limit(late),
+        };
+        self.update_shimmer(output);
+        output
+    }
+
+    // The line is always fed, so the reflections are ready when the balance moves off
+    // fully late; the taps are only read while they are heard. Tap spacing follows the
+    // smoothed feedback, so room size changes bend the reflections instead of jumping them
+    fn next_early(&mut self, input: f32, settings: LoopSettings) -> Option<(f32, f32)> {
+        self.early.write(input * (1.0 - settings.freeze));
+        if self.early_late >= 1.0 {
+            return None;
+        }
+        let room_size = (settings.feedback - ROOM_FEEDBACK_MIN) / ROOM_FEEDBACK_RANGE;
+        Some(self.early.read(EARLY_MIN_SCALE + (1.0 - EARLY_MIN_SCALE) * room_size))
+    }
+
+    fn next_loop_settings(&mut self) -> LoopSettings {
+        LoopSettings {
+            feedback: self.feedback.next(),
+            saturation: self.saturation,
+            freeze: self.next_freeze(),
+        }
+    }
+
+    fn update_shimmer(&mut self, output: f32) {
+        if self.shimmer > 0.0 {
+            let shifted = self.shifter.process_wet(output);
+            self.shimmer_lows += (shifted - self.shimmer_lows) * self.shimmer_highpass;
+            self.shimmer_return = (shifted - self.shimmer_lows) * self.shimmer * SHIMMER_FEEDBACK;
+        }
+    }
+}
+
+impl Tank {
+    // Freeverb's delays, given at 44.1 kHz and scaled to the sample rate; `spread`
+    // lengthens every one of them for the second tank of a stereo pair
+    fn new(sample_rate: f32, spread: usize) -> Self {
+        let scale = sample_rate / 44100.0;
+        let comb_delays = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+        let allpass_delays = [556, 441, 341, 225];
+        let scaled = |delay: usize| ((delay + spread) as f32 * scale) as usize;
+
+        Tank {
+            comb_filters: comb_delays.iter().map(|&delay| CombFilter::new(scaled(delay))).collect(),
+            allpass_filters: allpass_delays.iter().map(|&delay| AllpassFilter::new(scaled(delay))).collect(),
+        }
+    }
+
+    fn process(&mut self, input: f32, settings: LoopSettings) -> f32 {
         let mut output = 0.0;
+        let LoopSettings { feedback, saturation, freeze } = settings;
+        let input = input * (1.0 - freeze);
+        // Freeze pulls the feedback up to unity; the level scale stays with the room size
+        let loop_feedback = feedback + (1.0 - feedback) * freeze;
 
         // Process through comb filters and AVERAGE instead of sum
         for comb in &mut self.comb_filters {
-            output += comb.process(input, self.damping);
+            output += comb.process(input, loop_feedback, freeze);
         }
         output /= self.comb_filters.len() as f32; // Average the comb outputs
-        output *= 0.4; // Additional gain reduction to prevent distortion
+        output *= ((1.0 - feedback * feedback) / (1.0 - REFERENCE_FEEDBACK * REFERENCE_FEEDBACK)).sqrt();
+        if saturation {
+            output = output.tanh();
+        }
+        output *= TANK_GAIN;
 
         // Process through allpass filters
         for allpass in &mut self.allpass_filters {
             output = allpass.process(output);
         }
-
-        // Wet/dry mix: 6% wet, 94% dry - very conservative to prevent volume spikes
-        let wet = output * 0.06; // Much more reduced wet signal
-        let dry = input * 0.94;  // More preserved dry signal
-        
-        wet + dry
+        output
     }
 }
 
@@ -86,33 +467,36 @@ impl CombFilter {
         CombFilter {
             buffer: vec![0.0; delay_samples],
             write_pos: 0,
-            feedback: 0.0,
-            filter_state: 0.0,
+            filter_store: 0.0,
+            damp1: 0.0,
+            damp2: 1.0,
         }
     }
 
-    fn set_feedback(&mut self, feedback: f32) {
-        self.feedback = feedback;
+    fn set_damping(&mut self, damp1: f32) {
+        self.damp1 = damp1;
+        self.damp2 = 1.0 - damp1;
     }
 
-    fn process(&mut self, input: f32, damping: f32) -> f32 {
-        let read_pos = if self.write_pos == 0 {
-            self.buffer.len() - 1
+    // Freeze fades the damping out along with raising the feedback
+    fn process(&mut self, input: f32, feedback: f32, freeze: f32) -> f32 {
+        let (damp1, damp2) = if freeze > 0.0 {
+            let damp1 = self.damp1 * (1.0 - freeze);
+            (damp1, 1.0 - damp1)
         } else {
-            self.write_pos - 1
+            (self.damp1, self.damp2)
         };
-
-        let delayed = self.buffer[read_pos];
-        self.filter_state = delayed * (1.0 - damping) + self.filter_state * damping;
-        let output = input + self.filter_state * self.feedback;
-        self.buffer[self.write_pos] = output;
+        // The slot about to be overwritten holds the oldest sample, so the delay is the full buffer
+        let output = self.buffer[self.write_pos];
+        self.filter_store = output * damp2 + self.filter_store * damp1;
+        self.buffer[self.write_pos] = input + self.filter_store * feedback;
 
         self.write_pos += 1;
         if self.write_pos >= self.buffer.len() {
             self.write_pos = 0;
         }
 
-        delayed
+        output
     }
 }
 
@@ -124,17 +508,11 @@ impl AllpassFilter {
         }
     }
 
+    // Freeverb's allpass, reading the oldest sample like the combs
     fn process(&mut self, input: f32) -> f32 {
-        let read_pos = if self.write_pos == 0 {
-            self.buffer.len() - 1
-        } else {
-            self.write_pos - 1
-        };
-
-        let delayed = self.buffer[read_pos];
-        // Further reduced allpass feedback to 0.15 for cleaner sound
-        let output = delayed + input * 0.15;
-        self.buffer[self.write_pos] = input + delayed * 0.15;
+        let delayed = self.buffer[self.write_pos];
+        let output = delayed - input;
+        self.buffer[self.write_pos] = input + delayed * ALLPASS_FEEDBACK;
 
         self.write_pos += 1;
         if self.write_pos >= self.buffer.len() {
@@ -145,3 +523,215 @@ impl AllpassFilter {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::Spectrum;
+
+    // Largest output of the tank under a loud impulse train at the biggest room
+    fn loudest_tank_output(saturation: bool) -> f32 {
+        let mut tank = Tank::new(48000.0, 0);
+        let settings = LoopSettings { feedback: room_feedback(1.0), saturation, freeze: 0.0 };
+        (0..3 * 48000)
+            .map(|n| tank.process(if n % 100 == 0 { 20.0 } else { 0.0 }, settings).abs())
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn saturation_bounds_a_loud_impulse_train() {
+        let (saturated, clean) = (loudest_tank_output(true), loudest_tank_output(false));
+        // The comb sum is held under 1, which the gain and allpasses only stretch a little
+        assert!(saturated.is_finite() && saturated < 1.5, "{saturated}");
+        assert!(clean > 2.0 * saturated, "{clean} vs {saturated}");
+
+        let mut reverb = Reverb::new(48000.0);
+        reverb.set_room_size(1.0);
+        reverb.set_mix(1.0);
+        reverb.set_saturation(true);
+        assert!((0..3 * 48000).all(|n| reverb.process(if n % 100 == 0 { 20.0 } else { 0.0 }).abs() <= 1.0));
+    }
+
+    // Share of the energy in sample-to-sample differences of the tail from 0.5 s to 1 s
+    fn tail_brightness(damping: f32) -> f32 {
+        let mut reverb = Reverb::new(48000.0);
+        reverb.set_room_size(0.8);
+        reverb.set_damping(damping);
+        reverb.set_mix(1.0);
+        let response: Vec<f32> = (0..48000).map(|n| reverb.process(if n == 0 { 1.0 } else { 0.0 })).collect();
+        let tail = &response[24000..];
+        let energy: f32 = tail.iter().map(|x| x * x).sum();
+        tail.windows(2).map(|w| (w[1] - w[0]).powi(2)).sum::<f32>() / energy
+    }
+
+    #[test]
+    fn damping_darkens_the_tail() {
+        let (bright, dark) = (tail_brightness(0.0), tail_brightness(1.0));
+        assert!(dark < 0.5 * bright, "{dark} vs {bright}");
+    }
+
+    // dB of the octave above a 500 Hz burst relative to the burst itself, in the
+    // tail at 0.3 s and at 1.5 s
+    fn octave_up_over_time(shimmer: f32) -> (f32, f32) {
+        let mut reverb = Reverb::new(48000.0);
+        reverb.set_room_size(0.9);
+        reverb.set_damping(0.2);
+        reverb.set_mix(1.0);
+        reverb.set_shimmer(shimmer);
+        let response: Vec<f32> = (0..96000)
+            .map(|n| {
+                let burst = if n < 9600 { (2.0 * std::f32::consts::PI * 500.0 * n as f32 / 48000.0).sin() } else { 0.0 };
+                reverb.process(burst)
+            })
+            .collect();
+        let mut spectrum = Spectrum::new();
+        let mut octave_over_fundamental = |start: usize| {
+            let mut bins = [0.0; 4096];
+            spectrum.analyze(&response[start..start + 8192], &mut bins);
+            let around = |hz: f32| {
+                let bin = (hz * 8192.0 / 48000.0) as usize;
+                bins[bin - 3..=bin + 3].iter().copied().fold(f32::MIN, f32::max)
+            };
+            around(1000.0) - around(500.0)
+        };
+        (octave_over_fundamental(14400), octave_over_fundamental(72000))
+    }
+
+    #[test]
+    fn shimmer_grows_the_octave_above() {
+        let (early, late) = octave_up_over_time(1.0);
+        let (_, plain) = octave_up_over_time(0.0);
+        assert!(late > early + 6.0, "{early} -> {late} dB");
+        assert!(late > plain + 10.0, "{late} vs {plain} dB");
+    }
+
+    #[test]
+    fn zero_mix_is_bit_identical_to_the_input() {
+        let mut reverb = Reverb::new(48000.0);
+        reverb.set_room_size(0.9);
+        reverb.set_mix(0.0);
+        let input = |n: usize| (n as f32 * 0.01).sin() * 0.7;
+        for n in 0..48000 {
+            assert_eq!(reverb.process(input(n)).to_bits(), input(n).to_bits());
+            let (l, r) = reverb.process_stereo(input(n), -input(n));
+            assert_eq!((l.to_bits(), r.to_bits()), (input(n).to_bits(), (-input(n)).to_bits()));
+        }
+    }
+
+    // Seconds for the wet tail of an impulse to fall 60 dB, extrapolated from the -5 to
+    // -35 dB stretch of its backward-integrated energy (Schroeder's method)
+    fn rt60(room_size: f32) -> f32 {
+        let mut reverb = Reverb::new(48000.0);
+        reverb.set_mix(1.0);
+        reverb.set_room_size(room_size);
+        for _ in 0..4800 {
+            reverb.process(0.0);
+        }
+        let tail: Vec<f32> = (0..8 * 48000).map(|n| reverb.process(if n == 0 { 0.5 } else { 0.0 })).collect();
+        let mut remaining = tail.iter().map(|x| x * x).sum::<f32>();
+        let mut decay_db = Vec::with_capacity(tail.len());
+        let total = remaining;
+        for x in &tail {
+            decay_db.push(10.0 * (remaining / total).log10());
+            remaining -= x * x;
+        }
+        let time_to = |db: f32| decay_db.iter().position(|&level| level < db).unwrap() as f32 / 48000.0;
+        2.0 * (time_to(-35.0) - time_to(-5.0))
+    }
+
+    #[test]
+    fn room_size_spreads_the_decay_time() {
+        let (small, medium, large) = (rt60(0.2), rt60(0.5), rt60(0.9));
+        // About 0.65 s, 1 s and 3.4 s at the default damping
+        assert!(small < medium && medium < large, "{small} {medium} {large}");
+        assert!(small < 1.0 && large > 2.5 && large > 4.0 * small, "{small} {medium} {large}");
+    }
+
+    // Jezar's public-domain Freeverb tank at 44.1 kHz, written out from the original:
+    // eight lowpass-feedback combs in parallel into four series allpasses
+    struct ReferenceFreeverb {
+        combs: Vec<(Vec<f32>, usize, f32)>, // (buffer, index, filterstore)
+        allpasses: Vec<(Vec<f32>, usize)>,
+        feedback: f32,
+        damp1: f32,
+        damp2: f32,
+    }
+
+    impl ReferenceFreeverb {
+        fn new(room_size: f32, damp: f32, stereo_spread: usize) -> Self {
+            let combs = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+            let allpasses = [556, 441, 341, 225];
+            ReferenceFreeverb {
+                combs: combs.iter().map(|&n| (vec![0.0; n + stereo_spread], 0, 0.0)).collect(),
+                allpasses: allpasses.iter().map(|&n| (vec![0.0; n + stereo_spread], 0)).collect(),
+                feedback: room_size * 0.28 + 0.7,
+                damp1: damp * 0.4,
+                damp2: 1.0 - damp * 0.4,
+            }
+        }
+
+        fn process(&mut self, input: f32) -> f32 {
+            let mut out = 0.0;
+            for (buffer, index, filterstore) in &mut self.combs {
+                let output = buffer[*index];
+                *filterstore = output * self.damp2 + *filterstore * self.damp1;
+                buffer[*index] = input + *filterstore * self.feedback;
+                *index = (*index + 1) % buffer.len();
+                out += output;
+            }
+            for (buffer, index) in &mut self.allpasses {
+                let bufout = buffer[*index];
+                let output = -out + bufout;
+                buffer[*index] = out + bufout * 0.5;
+                *index = (*index + 1) % buffer.len();
+                out = output;
+            }
+            out
+        }
+    }
+
+    #[test]
+    fn tank_matches_reference_freeverb() {
+        for (room_size, damping, spread) in [(0.2, 0.0, 0), (0.5, 0.5, 0), (0.9, 1.0, STEREO_SPREAD)] {
+            let mut tank = Tank::new(44100.0, spread);
+            for comb in &mut tank.comb_filters {
+                comb.set_damping(damping * DAMPING_SCALE);
+            }
+            let feedback = room_feedback(room_size);
+            let settings = LoopSettings { feedback, saturation: false, freeze: 0.0 };
+            let mut reference = ReferenceFreeverb::new(room_size, damping, spread);
+            // The tank averages the combs and scales the sum to hold the level across room sizes
+            let level = TANK_GAIN / 8.0
+                * ((1.0 - feedback * feedback) / (1.0 - REFERENCE_FEEDBACK * REFERENCE_FEEDBACK)).sqrt();
+            for n in 0..2 * 44100 {
+                let input = if n == 0 { 1.0 } else { 0.0 };
+                let (ours, theirs) = (tank.process(input, settings), reference.process(input) * level);
+                assert!((ours - theirs).abs() < 1e-6, "size {room_size}, sample {n}: {ours} vs {theirs}");
+            }
+        }
+    }
+
+    #[test]
+    fn mono_input_gives_different_wet_sides() {
+        let impulse = |n: usize| if n == 0 { 0.5 } else { 0.0 };
+        let mut reverb = Reverb::new(48000.0);
+        reverb.set_mix(1.0);
+        let wet: Vec<(f32, f32)> = (0..48000).map(|n| reverb.process_stereo(impulse(n), impulse(n))).collect();
+        let (mut lr, mut ll, mut rr) = (0.0, 0.0, 0.0);
+        for &(l, r) in &wet {
+            (lr, ll, rr) = (lr + l * r, ll + l * l, rr + r * r);
+        }
+        assert!(ll > 0.0 && rr > 0.0);
+        // The right tank's longer delays leave the two tails largely unrelated
+        let correlation = lr / (ll * rr).sqrt();
+        assert!(correlation.abs() < 0.5, "{correlation}");
+
+        // At width 0 both sides carry the same blend
+        let mut reverb = Reverb::new(48000.0);
+        reverb.set_mix(1.0);
+        reverb.set_width(0.0);
+        for n in 0..48000 {
+            let (l, r) = reverb.process_stereo(impulse(n), impulse(n));
+            assert_eq!(l, r);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::f32::consts::PI;
+
+// Multiplies the input by a sine carrier, leaving only the sum and difference
+// frequencies (unlike tremolo, whose unipolar LFO keeps the original pitch)
+pub struct RingMod {
+    phase: f32,
+    frequency: f32,
+    mix: f32,
+    sample_rate: f32,
+}
+
+impl RingMod {
+    pub fn new(sample_rate: f32) -> Self {
+        RingMod {
+            phase: 0.0,
+            frequency: 440.0,
+            mix: 0.5,
+            sample_rate,
+        }
+    }
+
+    pub fn set_frequency(&mut self, frequency_hz: f32) {
+        self.frequency = frequency_hz.clamp(0.1, self.sample_rate * 0.45);
+    }
+
+    // For running oversampled
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    pub fn set_mix(&mut self, mix: f32) {
+        self.mix = mix.clamp(0.0, 1.0);
+    }
+
+    pub fn get_frequency(&self) -> f32 {
+        self.frequency
+    }
+
+    pub fn get_mix(&self) -> f32 {
+        self.mix
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let carrier = (self.phase * 2.0 * PI).sin();
+        self.phase += self.frequency / self.sample_rate;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+        }
+
+        input * (1.0 - self.mix) + input * carrier * self.mix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::Spectrum;
+
+    #[test]
+    fn output_has_sum_and_difference_but_not_the_input() {
+        // 750 Hz through a 187.5 Hz carrier; both land on bins of a 4096-point FFT
+        let mut ring = RingMod::new(48000.0);
+        ring.set_frequency(187.5);
+        ring.set_mix(1.0);
+        let output: Vec<f32> = (0..4096).map(|i| ring.process((2.0 * PI * 750.0 * i as f32 / 48000.0).sin())).collect();
+        let mut bins = [0.0; 2048];
+        Spectrum::new().analyze(&output, &mut bins);
+
+        let db_at = |hz: f32| bins[(hz * 4096.0 / 48000.0) as usize];
+        assert!(db_at(937.5) > -7.0 && db_at(562.5) > -7.0);
+        assert!(db_at(750.0) < -60.0, "{} dB", db_at(750.0));
+    }
+}
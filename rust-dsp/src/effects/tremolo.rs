@@ -4,6 +4,7 @@ pub struct Tremolo {
     lfo: Lfo,
     depth: f32,
     rate: f32,
+    depth_scale: f32,
 }
 
 impl Tremolo {
@@ -14,6 +15,7 @@ impl Tremolo {
             lfo,
             depth: 0.5,
             rate: 5.0,
+            depth_scale: 1.0,
         }
     }
 
@@ -27,9 +29,26 @@ impl Tremolo {
         self.lfo.set_depth(depth);
     }
 
+    // Real-time scaling of the depth from a performance controller
+    pub fn set_depth_scale(&mut self, scale: f32) {
+        self.depth_scale = scale.clamp(0.0, 1.0);
+    }
+
+    pub fn get_rate(&self) -> f32 {
+        self.rate
+    }
+
+    pub fn get_depth(&self) -> f32 {
+        self.depth
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.lfo.set_seed(seed);
+    }
+
     pub fn process(&mut self, input: f32) -> f32 {
         let lfo_value = self.lfo.process();
-        let modulation = 1.0 - (lfo_value * 0.5 + 0.5) * self.depth;
+        let modulation = 1.0 - (lfo_value * 0.5 + 0.5) * self.depth * self.depth_scale;
         input * modulation
     }
 }
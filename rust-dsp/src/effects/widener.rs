@@ -0,0 +1,59 @@
+// Mid/side width: the side (L - R) part is scaled while the mid is left alone,
+// so 0 folds to mono, 1 is untouched and 2 doubles the stereo difference
+pub struct Widener {
+    width: f32,
+}
+
+impl Widener {
+    pub fn new() -> Self {
+        Widener { width: 1.0 }
+    }
+
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width.clamp(0.0, 2.0);
+    }
+
+    pub fn get_width(&self) -> f32 {
+        self.width
+    }
+
+    pub fn process(&self, left: f32, right: f32) -> (f32, f32) {
+        if self.width == 1.0 {
+            return (left, right);
+        }
+        let mid = (left + right) * 0.5;
+        let side = (left - right) * 0.5 * self.width;
+        (mid + side, mid - side)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // (mid, side) energy of a pair of unrelated sines through the widener
+    fn mid_side_energy(width: f32) -> (f32, f32) {
+        let mut widener = Widener::new();
+        widener.set_width(width);
+        (0..4800).fold((0.0, 0.0), |(mid, side), n| {
+            let t = n as f32 / 48000.0;
+            let left = (std::f32::consts::TAU * 300.0 * t).sin();
+            let right = (std::f32::consts::TAU * 500.0 * t).sin() * 0.5;
+            let (l, r) = widener.process(left, right);
+            (mid + (l + r).powi(2), side + (l - r).powi(2))
+        })
+    }
+
+    #[test]
+    fn width_folds_to_mono_and_widens() {
+        let mut widener = Widener::new();
+        widener.set_width(0.0);
+        let (l, r) = widener.process(0.8, -0.3);
+        assert_eq!(l, r);
+        assert_eq!(mid_side_energy(0.0).1, 0.0);
+
+        let (normal, wide) = (mid_side_energy(1.0), mid_side_energy(2.0));
+        assert!((wide.1 / normal.1 - 4.0).abs() < 1e-3);
+        assert!((wide.0 - normal.0).abs() < 1e-3 * normal.0);
+    }
+}
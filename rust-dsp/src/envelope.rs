@@ -1,14 +1,32 @@
 #[derive(Clone, Copy, PartialEq)]
 pub enum EnvelopeStage {
     Idle,
+    Delay,
     Attack,
+    Hold,
     Decay,
     Sustain,
     Release,
 }
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum EnvelopeMode {
+    Adsr = 0,
+    OneShot = 1, // Attack, then decay to 0 and finish; note off is ignored
+}
+
+impl EnvelopeMode {
+    pub fn from_u8(mode: u8) -> Self {
+        match mode {
+            1 => EnvelopeMode::OneShot,
+            _ => EnvelopeMode::Adsr,
+        }
+    }
+}
+
 pub struct Envelope {
     stage: EnvelopeStage,
+    mode: EnvelopeMode,
     value: f32,
     attack_time: f32,
     decay_time: f32,
@@ -19,12 +37,19 @@ pub struct Envelope {
     decay_increment: f32,
     release_increment: f32,
     release_start_value: f32, // Store value when release starts
+    delay_ms: f32,
+    hold_ms: f32,
+    delay_samples: f32,
+    hold_samples: f32,
+    stage_pos: f32, // Samples spent in the delay or hold stage
+    attack_scale: f32, // Per-note factor on the attack time (velocity to attack)
 }
 
 impl Envelope {
     pub fn new(sample_rate: f32) -> Self {
-        Envelope {
+        let mut envelope = Envelope {
             stage: EnvelopeStage::Idle,
+            mode: EnvelopeMode::Adsr,
             value: 0.0,
             attack_time: 0.01,
             decay_time: 0.3,
@@ -35,7 +60,15 @@ impl Envelope {
             decay_increment: 0.0,
             release_increment: 0.0,
             release_start_value: 0.0,
-        }
+            delay_ms: 0.0,
+            hold_ms: 0.0,
+            delay_samples: 0.0,
+            hold_samples: 0.0,
+            stage_pos: 0.0,
+            attack_scale: 1.0,
+        };
+        envelope.set_adsr(0.01, 0.3, 0.7, 0.5);
+        envelope
     }
 
     pub fn set_adsr(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
@@ -44,20 +77,60 @@ impl Envelope {
         self.sustain_level = sustain.clamp(0.0, 1.0);
         self.release_time = release.max(0.001);
 
-        let attack_samples = (self.attack_time * self.sample_rate).max(1.0);
+        let attack_samples = (self.attack_time * self.attack_scale * self.sample_rate).max(1.0);
         let decay_samples = (self.decay_time * self.sample_rate).max(1.0);
 
         self.attack_increment = 1.0 / attack_samples;
-        self.decay_increment = (1.0 - self.sustain_level) / decay_samples;
+        // One-shot decays all the way down in the same time
+        let decay_floor = if self.mode == EnvelopeMode::OneShot { 0.0 } else { self.sustain_level };
+        self.decay_increment = (1.0 - decay_floor) / decay_samples;
         // Release increment calculated dynamically in gate_off
     }
 
+    // Wait before the attack starts (a retriggered note holds its level meanwhile)
+    pub fn set_delay(&mut self, ms: f32) {
+        self.delay_ms = ms.clamp(0.0, 10_000.0);
+        self.delay_samples = (self.delay_ms * self.sample_rate / 1000.0).round();
+    }
+
+    // Time at full level between the attack and the decay
+    pub fn set_hold(&mut self, ms: f32) {
+        self.hold_ms = ms.clamp(0.0, 10_000.0);
+        self.hold_samples = (self.hold_ms * self.sample_rate / 1000.0).round();
+    }
+
+    // (delay ms, hold ms)
+    pub fn get_delay_hold(&self) -> (f32, f32) {
+        (self.delay_ms, self.hold_ms)
+    }
+
+    pub fn set_mode(&mut self, mode: u8) {
+        self.mode = EnvelopeMode::from_u8(mode);
+        self.set_adsr(self.attack_time, self.decay_time, self.sustain_level, self.release_time);
+    }
+
+    pub fn get_mode(&self) -> u8 {
+        self.mode as u8
+    }
+
+    // Shortens (or keeps) the attack for the next notes; get_adsr still reports the set time
+    pub fn set_attack_scale(&mut self, scale: f32) {
+        self.attack_scale = scale.clamp(0.0, 1.0);
+        let attack_samples = (self.attack_time * self.attack_scale * self.sample_rate).max(1.0);
+        self.attack_increment = 1.0 / attack_samples;
+    }
+
+    pub fn get_adsr(&self) -> (f32, f32, f32, f32) {
+        (self.attack_time, self.decay_time, self.sustain_level, self.release_time)
+    }
+
     pub fn gate_on(&mut self) {
-        self.stage = EnvelopeStage::Attack;
+        self.stage_pos = 0.0;
+        self.stage = if self.delay_samples > 0.0 { EnvelopeStage::Delay } else { EnvelopeStage::Attack };
     }
 
     pub fn gate_off(&mut self) {
-        if self.stage != EnvelopeStage::Idle {
+        if self.stage != EnvelopeStage::Idle && self.mode == EnvelopeMode::Adsr {
             // Store current value when starting release
             self.release_start_value = self.value;
             
@@ -74,16 +147,34 @@ impl Envelope {
             EnvelopeStage::Idle => {
                 self.value = 0.0;
             }
+            EnvelopeStage::Delay => {
+                self.stage_pos += 1.0;
+                if self.stage_pos >= self.delay_samples {
+                    self.stage = EnvelopeStage::Attack;
+                }
+            }
             EnvelopeStage::Attack => {
                 self.value += self.attack_increment;
                 if self.value >= 1.0 {
                     self.value = 1.0;
+                    self.stage_pos = 0.0;
+                    self.stage = if self.hold_samples > 0.0 { EnvelopeStage::Hold } else { EnvelopeStage::Decay };
+                }
+            }
+            EnvelopeStage::Hold => {
+                self.stage_pos += 1.0;
+                if self.stage_pos >= self.hold_samples {
                     self.stage = EnvelopeStage::Decay;
                 }
             }
             EnvelopeStage::Decay => {
                 self.value -= self.decay_increment;
-                if self.value <= self.sustain_level {
+                if self.mode == EnvelopeMode::OneShot {
+                    if self.value <= 0.0 {
+                        self.value = 0.0;
+                        self.stage = EnvelopeStage::Idle;
+                    }
+                } else if self.value <= self.sustain_level {
                     self.value = self.sustain_level;
                     self.stage = EnvelopeStage::Sustain;
                 }
@@ -105,5 +196,35 @@ impl Envelope {
     pub fn is_active(&self) -> bool {
         self.stage != EnvelopeStage::Idle
     }
+
+    // Level output by the last process call
+    pub fn get_value(&self) -> f32 {
+        self.value
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_stage_holds_silence_before_the_attack() {
+        let mut envelope = Envelope::new(48000.0);
+        envelope.set_adsr(0.01, 0.1, 0.7, 0.2);
+        envelope.set_delay(100.0);
+        envelope.gate_on();
+        assert!((0..4800).all(|_| envelope.process() == 0.0));
+        assert!(envelope.process() > 0.0);
+    }
+
+    #[test]
+    fn hold_stage_stays_at_the_peak() {
+        let mut envelope = Envelope::new(48000.0);
+        envelope.set_adsr(0.01, 0.1, 0.7, 0.2);
+        envelope.set_hold(50.0);
+        envelope.gate_on();
+        let levels: Vec<f32> = (0..3360).map(|_| envelope.process()).collect();
+        assert!(levels[480..2880].iter().all(|&v| v == 1.0));
+        assert!(levels[3359] < 1.0);
+    }
+}
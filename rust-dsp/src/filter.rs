@@ -1,6 +1,56 @@
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::smoother::Smoother;
+use crate::DEFAULT_SMOOTHING_MS;
+
+pub const MAX_RESONANCE: f32 = 1.2;
+pub const MIN_Q: f32 = 0.5;
+pub const MAX_Q: f32 = 25.0;
+const MAX_COEFF: f32 = 0.5; // Keeps the SVF recursion stable at every damping
+const STATE_LIMIT: f32 = 100.0;
+const DENORMAL_LIMIT: f32 = 1e-20;
+const SELF_OSC_SEED: f32 = 1e-5;
+const MIN_DRAWN_DAMPING: f32 = 0.01; // Self-oscillating settings draw as a very sharp peak
+
+// Samples (f32) or, for the drawn response, transfer function values (Complex)
+pub trait Signal: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self> {}
+
+impl<T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>> Signal for T {}
+
+// Every response the SVF produces in one tick
+#[derive(Clone, Copy)]
+pub struct SvfOutputs<T = f32> {
+    pub lp: T,
+    pub hp: T,
+    pub bp: T,
+    pub notch: T,
+}
+
+impl<T: Signal> SvfOutputs<T> {
+    // 0 = lowpass, 0.5 = bandpass, 1 = highpass, linear crossfade in between
+    pub fn morph(&self, position: f32) -> T {
+        let position = position.clamp(0.0, 1.0) * 2.0;
+        if position <= 1.0 {
+            self.lp + (self.bp - self.lp) * position
+        } else {
+            self.bp + (self.hp - self.bp) * (position - 1.0)
+        }
+    }
+}
+
 pub struct StateVariableFilter {
     sample_rate: f32,
     cutoff: f32,
+    coeff: f32,
+    resonance: f32,
+    q_setting: Option<f32>, // Set when the damping came from set_q rather than set_resonance
+    damping: f32, // 1 / Q; negative past resonance 1.1, where the filter self-oscillates
+    damping_smoother: Smoother, // Per-sample damping, so resonance sweeps don't zipper
+    peak_gain: f32, // Linear gain of the bell at the cutoff
+    shelf_gain: f32, // Linear gain of the shelf modes past the cutoff
+    drive: f32,
+    drive_gain: f32,
+    drive_makeup: f32,
     low: f32,
     band: f32,
     high: f32,
@@ -12,6 +62,16 @@ impl StateVariableFilter {
         StateVariableFilter {
             sample_rate,
             cutoff: 20000.0,
+            coeff: MAX_COEFF,
+            resonance: 0.0,
+            q_setting: None,
+            damping: damping_for(0.0),
+            damping_smoother: Smoother::new(sample_rate, damping_for(0.0), DEFAULT_SMOOTHING_MS),
+            peak_gain: 1.0,
+            shelf_gain: 1.0,
+            drive: 0.0,
+            drive_gain: 1.0,
+            drive_makeup: 1.0,
             low: 0.0,
             band: 0.0,
             high: 0.0,
@@ -20,46 +80,1263 @@ impl StateVariableFilter {
     }
 
     pub fn set_cutoff(&mut self, cutoff: f32) {
-        self.cutoff = cutoff.clamp(20.0, 20000.0);
+        self.cutoff = clamp_svf_cutoff(cutoff, self.sample_rate);
+        self.coeff = svf_coefficient(self.cutoff, self.sample_rate);
     }
 
-    pub fn process(&mut self, input: f32) -> f32 {
-        let f = 2.0 * (self.cutoff / self.sample_rate);
-        let f = f.clamp(0.0, 0.5);
-        let q = 0.707; // Fixed Q for stable, musical filter response
+    // For running oversampled: cutoff and smoothing time keep their meaning
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.damping_smoother.set_sample_rate(sample_rate);
+        self.set_cutoff(self.cutoff);
+    }
+
+    // Small-signal response at a cutoff, using the resonance target rather than the smoothed value
+    fn response(&self, cutoff: f32, z1: Complex) -> SvfOutputs<Complex> {
+        let coeff = svf_coefficient(clamp_svf_cutoff(cutoff, self.sample_rate), self.sample_rate);
+        let mut outputs = svf_response(coeff, self.damping.max(MIN_DRAWN_DAMPING), z1);
+        outputs.bp = outputs.bp * band_gain(self.damping);
+        outputs
+    }
+
+    // Slope of the drive saturator around zero
+    fn small_signal_gain(&self) -> f32 {
+        if self.drive == 0.0 { 1.0 } else { self.drive_gain * self.drive_makeup }
+    }
+
+    pub fn reset(&mut self) {
+        self.low = 0.0;
+        self.band = 0.0;
+        self.high = 0.0;
+        self.notch = 0.0;
+    }
+
+    // Run once per block: clears the state if it went NaN/Inf (returns true),
+    // otherwise bounds it and flushes denormals
+    pub fn sanitize(&mut self) -> bool {
+        if !(self.low.is_finite() && self.band.is_finite() && self.high.is_finite()) {
+            self.reset();
+            return true;
+        }
+        for state in [&mut self.low, &mut self.band, &mut self.high, &mut self.notch] {
+            *state = if state.abs() < DENORMAL_LIMIT { 0.0 } else { state.clamp(-STATE_LIMIT, STATE_LIMIT) };
+        }
+        false
+    }
+
+    // 0.0 keeps the original fixed damping, 1.0 is close to self-oscillation,
+    // and 1.1 up to MAX_RESONANCE makes the filter sing on its own
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.resonance = resonance.clamp(0.0, MAX_RESONANCE);
+        self.q_setting = None;
+        self.set_damping(damping_for(self.resonance));
+    }
+
+    // Q from MIN_Q to MAX_Q; the damping is 1 / Q
+    pub fn set_q(&mut self, q: f32) {
+        let q = q.clamp(MIN_Q, MAX_Q);
+        self.q_setting = Some(q);
+        self.set_damping(1.0 / q);
+        self.resonance = resonance_for(self.damping).clamp(0.0, MAX_RESONANCE);
+    }
+
+    fn set_damping(&mut self, damping: f32) {
+        self.damping = damping;
+        self.damping_smoother.set_target(damping);
+    }
+
+    // Cutoff is not smoothed here: the engine smooths the knob before adding
+    // per-sample modulation, which has to reach the filter unfiltered
+    pub fn set_smoothing_time(&mut self, ms: f32) {
+        self.damping_smoother.set_time(ms);
+    }
+
+    pub fn get_resonance(&self) -> f32 {
+        self.resonance
+    }
+
+    // Infinite once the filter self-oscillates
+    pub fn get_q(&self) -> f32 {
+        if self.damping > 0.0 { 1.0 / self.damping } else { f32::INFINITY }
+    }
+
+    pub fn get_q_setting(&self) -> Option<f32> {
+        self.q_setting
+    }
+
+    // Boost/cut of the peak mode in dB, 0 dB is transparent
+    pub fn set_peak_gain_db(&mut self, db: f32) {
+        self.peak_gain = 10.0_f32.powf(db.clamp(-18.0, 18.0) / 20.0);
+    }
+
+    pub fn get_peak_gain_db(&self) -> f32 {
+        20.0 * self.peak_gain.log10()
+    }
+
+    // Boost/cut of the shelf modes in dB, 0 dB is transparent
+    pub fn set_shelf_gain_db(&mut self, db: f32) {
+        self.shelf_gain = 10.0_f32.powf(db.clamp(-18.0, 18.0) / 20.0);
+    }
+
+    pub fn get_shelf_gain_db(&self) -> f32 {
+        20.0 * self.shelf_gain.log10()
+    }
+
+    // Input saturation, 0 = clean. Up to +19 dB into a tanh, with the output
+    // pulled back by the square root of that so turning it up adds grit more than level
+    pub fn set_drive(&mut self, amount: f32) {
+        self.drive = amount.clamp(0.0, 1.0);
+        self.drive_gain = 1.0 + 8.0 * self.drive;
+        self.drive_makeup = 1.0 / self.drive_gain.sqrt();
+    }
+
+    pub fn get_drive(&self) -> f32 {
+        self.drive
+    }
+
+    fn saturate(&self, input: f32) -> f32 {
+        if self.drive == 0.0 {
+            return input;
+        }
+        (input * self.drive_gain).tanh() * self.drive_makeup
+    }
+
+    // One step of the recursion shared by every mode; returns the damping used.
+    // Past resonance 1.0 the damping goes negative and the filter rings on its
+    // own, held to a steady level by a tanh on the band integrator.
+    fn tick(&mut self, input: f32) -> f32 {
+        let mut input = self.saturate(input);
+        let f = self.coeff;
+        let q = self.damping_smoother.next();
+        let self_oscillating = q < damping_for(1.0);
+        if self_oscillating {
+            // Stands in for analog noise so it starts singing from silence
+            input += SELF_OSC_SEED;
+        }
 
-        // State variable filter algorithm
         self.low += f * self.band;
         self.high = input - self.low - q * self.band;
         self.band += f * self.high;
+        if self_oscillating {
+            self.band = self.band.tanh();
+        }
         self.notch = self.high + self.low;
+        q
+    }
 
-        // Return lowpass output
-        self.low
+    pub fn process(&mut self, input: f32) -> SvfOutputs {
+        let q = self.tick(input);
+        SvfOutputs {
+            lp: self.low,
+            hp: self.high,
+            bp: self.band * band_gain(q),
+            notch: self.notch,
+        }
     }
 
-    pub fn process_highpass(&mut self, input: f32) -> f32 {
-        let f = 2.0 * (self.cutoff / self.sample_rate);
-        let f = f.clamp(0.0, 0.5);
-        let q = 0.707; // Fixed Q for stable, musical filter response
+    pub fn process_peak(&mut self, input: f32) -> f32 {
+        let q = self.tick(input).max(0.0);
+        // band * q has unity gain at the cutoff, so this adds (gain - 1) there
+        self.saturate(input) + (self.peak_gain - 1.0) * q * self.band
+    }
 
-        self.low += f * self.band;
-        self.high = input - self.low - q * self.band;
-        self.band += f * self.high;
+    // The shelves run the same recursion as every other mode, so switching
+    // modes mid-stream just picks a different mix of the shared state
+    pub fn process_low_shelf(&mut self, input: f32) -> f32 {
+        self.tick(input);
+        // The lowpass tracks the input below the cutoff, so this scales that region
+        self.saturate(input) + (self.shelf_gain - 1.0) * self.low
+    }
+
+    pub fn process_high_shelf(&mut self, input: f32) -> f32 {
+        self.tick(input);
+        self.saturate(input) + (self.shelf_gain - 1.0) * self.high
+    }
+}
+
+// Single-response shorthands for callers that only need one output
+#[allow(dead_code)]
+impl StateVariableFilter {
+    pub fn process_lowpass(&mut self, input: f32) -> f32 {
+        self.process(input).lp
+    }
 
-        self.high
+    pub fn process_highpass(&mut self, input: f32) -> f32 {
+        self.process(input).hp
     }
 
     pub fn process_bandpass(&mut self, input: f32) -> f32 {
-        let f = 2.0 * (self.cutoff / self.sample_rate);
-        let f = f.clamp(0.0, 0.5);
-        let q = 0.707; // Fixed Q for stable, musical filter response
+        self.process(input).bp
+    }
 
-        self.low += f * self.band;
-        self.high = input - self.low - q * self.band;
-        self.band += f * self.high;
+    // Width of the notch follows the damping, like the resonance of the other modes
+    pub fn process_notch(&mut self, input: f32) -> f32 {
+        self.process(input).notch
+    }
+}
+
+fn clamp_svf_cutoff(cutoff: f32, sample_rate: f32) -> f32 {
+    // NaN from a broken modulation source lands on the lowest cutoff instead of the state
+    let cutoff = if cutoff.is_nan() { 20.0 } else { cutoff };
+    cutoff.clamp(20.0, 20000.0_f32.min(sample_rate * 0.45))
+}
+
+fn svf_coefficient(cutoff: f32, sample_rate: f32) -> f32 {
+    let f = 2.0 * (std::f32::consts::PI * cutoff / sample_rate).sin();
+    f.clamp(0.0, MAX_COEFF)
+}
+
+// Transfer functions of the recursion in tick(), at z^-1 = z1:
+// bp = f(1 - z1) / D, lp = f^2 z1 / D, hp = (1 - z1)^2 / D,
+// with D = (1 - z1)^2 + f^2 z1 + q f z1 (1 - z1)
+fn svf_response(coeff: f32, damping: f32, z1: Complex) -> SvfOutputs<Complex> {
+    let diff = Complex::real(1.0) - z1;
+    let denominator = diff * diff + z1 * (coeff * coeff) + z1 * diff * (damping * coeff);
+    let lp = z1 * (coeff * coeff) / denominator;
+    let hp = diff * diff / denominator;
+    SvfOutputs {
+        lp,
+        hp,
+        bp: diff * coeff / denominator,
+        notch: hp + lp,
+    }
+}
+
+// Just enough complex arithmetic to evaluate the filters' transfer functions
+#[derive(Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn real(re: f64) -> Self {
+        Complex { re, im: 0.0 }
+    }
+
+    // e^(j angle)
+    fn from_angle(angle: f64) -> Self {
+        Complex { re: angle.cos(), im: angle.sin() }
+    }
+
+    fn norm(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, other: Complex) -> Complex {
+        Complex { re: self.re + other.re, im: self.im + other.im }
+    }
+}
 
-        self.band
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, other: Complex) -> Complex {
+        Complex { re: self.re - other.re, im: self.im - other.im }
     }
 }
 
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other: Complex) -> Complex {
+        Complex {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+}
+
+impl Mul<f32> for Complex {
+    type Output = Complex;
+    fn mul(self, scale: f32) -> Complex {
+        Complex { re: self.re * scale as f64, im: self.im * scale as f64 }
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, other: Complex) -> Complex {
+        let denominator = other.re * other.re + other.im * other.im;
+        Complex {
+            re: (self.re * other.re + self.im * other.im) / denominator,
+            im: (self.im * other.re - self.re * other.im) / denominator,
+        }
+    }
+}
+
+// Resonance to damping (1 / Q): 0 keeps the original fixed damping of 0.707
+// (Q 1.41), 1 is close to self-oscillation at 0.05 (Q 20), and the extended
+// range past 1 crosses zero at 1.1 into oscillation
+fn damping_for(resonance: f32) -> f32 {
+    if resonance <= 1.0 {
+        0.707 - resonance * (0.707 - 0.05)
+    } else {
+        0.05 - (resonance - 1.0) * 0.5
+    }
+}
+
+// Brings the bandpass peak (1 / damping) to unity. Self-oscillation keeps the
+// compensation of resonance 1 rather than chasing the damping through zero
+fn band_gain(damping: f32) -> f32 {
+    damping.max(damping_for(1.0))
+}
+
+// Inverse of damping_for; below 0 for Qs under 1.41
+fn resonance_for(damping: f32) -> f32 {
+    if damping >= 0.05 {
+        (0.707 - damping) / (0.707 - 0.05)
+    } else {
+        1.0 + (0.05 - damping) / 0.5
+    }
+}
+
+// (center Hz, gain dB) of the first three formants of a sung A, E, I, O, U
+pub const VOWELS: [[(f32, f32); 3]; 5] = [
+    [(800.0, 0.0), (1150.0, -6.0), (2900.0, -32.0)],
+    [(400.0, 0.0), (1600.0, -24.0), (2700.0, -30.0)],
+    [(250.0, 0.0), (1750.0, -30.0), (2600.0, -16.0)],
+    [(400.0, 0.0), (750.0, -11.0), (2400.0, -21.0)],
+    [(350.0, 0.0), (600.0, -20.0), (2400.0, -32.0)],
+];
+const FORMANT_Q: f32 = 10.0;
+const FORMANT_MAKEUP: f32 = 4.0; // Narrow bands pass little of a saw; +12 dB brings it near the other modes
+
+// (center Hz, linear gain) of each formant at a vowel position
+fn vowel_formants(position: f32) -> [(f32, f32); 3] {
+    let scaled = position * (VOWELS.len() - 1) as f32;
+    let index = (scaled as usize).min(VOWELS.len() - 2);
+    blend_vowels(index, index + 1, scaled - index as f32)
+}
+
+// Formants part way (t) from vowel a to vowel b: centers glide on a log scale
+// and the gains are normalized so every blend comes out about as loud
+pub fn blend_vowels(a: usize, b: usize, t: f32) -> [(f32, f32); 3] {
+    let mut formants = [(0.0, 0.0); 3];
+    let mut power = 0.0;
+    for (i, formant) in formants.iter_mut().enumerate() {
+        let (freq_a, db_a) = VOWELS[a][i];
+        let (freq_b, db_b) = VOWELS[b][i];
+        let gain = 10.0_f32.powf((db_a + (db_b - db_a) * t) / 20.0);
+        *formant = (freq_a * (freq_b / freq_a).powf(t), gain);
+        power += gain * gain;
+    }
+    let norm = 1.0 / power.sqrt();
+    for formant in &mut formants {
+        formant.1 *= norm;
+    }
+    formants
+}
+
+// Vowel filter: three parallel SVF bandpasses on the formants of a vowel
+pub struct FormantFilter {
+    bands: [StateVariableFilter; 3],
+    gains: [f32; 3],
+    vowel: f32,
+}
+
+impl FormantFilter {
+    pub fn new(sample_rate: f32) -> Self {
+        let mut bands = [
+            StateVariableFilter::new(sample_rate),
+            StateVariableFilter::new(sample_rate),
+            StateVariableFilter::new(sample_rate),
+        ];
+        for band in &mut bands {
+            band.set_q(FORMANT_Q);
+        }
+        let mut formant = FormantFilter {
+            bands,
+            gains: [0.0; 3],
+            vowel: -1.0,
+        };
+        formant.set_vowel(0.0);
+        formant
+    }
+
+    // 0 = A, 0.25 = E, 0.5 = I, 0.75 = O, 1 = U
+    pub fn set_vowel(&mut self, position: f32) {
+        let position = if position.is_nan() { 0.0 } else { position.clamp(0.0, 1.0) };
+        if position == self.vowel {
+            return;
+        }
+        self.vowel = position;
+        for (i, (frequency, gain)) in vowel_formants(position).into_iter().enumerate() {
+            self.bands[i].set_cutoff(frequency);
+            self.gains[i] = gain;
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        for band in &mut self.bands {
+            band.set_sample_rate(sample_rate);
+        }
+    }
+
+    fn response(&self, position: f32, z1: Complex) -> Complex {
+        let position = if position.is_nan() { 0.0 } else { position.clamp(0.0, 1.0) };
+        let sample_rate = self.bands[0].sample_rate;
+        let mut sum = Complex::real(0.0);
+        for (frequency, gain) in vowel_formants(position) {
+            let coeff = svf_coefficient(clamp_svf_cutoff(frequency, sample_rate), sample_rate);
+            // The unity bandpass gain is the damping
+            let damping = 1.0 / FORMANT_Q;
+            let band = svf_response(coeff, damping, z1).bp * damping;
+            sum = sum + band * (FORMANT_MAKEUP * gain);
+        }
+        sum
+    }
+
+    pub fn reset(&mut self) {
+        for band in &mut self.bands {
+            band.reset();
+        }
+    }
+
+    pub fn sanitize(&mut self) -> u32 {
+        self.bands.iter_mut().map(|band| band.sanitize() as u32).sum()
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let mut output = 0.0;
+        for (band, gain) in self.bands.iter_mut().zip(&self.gains) {
+            output += band.process(input).bp * FORMANT_MAKEUP * gain;
+        }
+        output
+    }
+}
+
+// Feedback comb tuned so its delay is one period of the note, which makes it
+// ring at the note and its harmonics (plucked string / flute on saw or noise)
+pub struct CombFilter {
+    buffer: Vec<f32>,
+    write_pos: usize,
+    delay: f32, // In samples, fractional
+    feedback: f32,
+    sample_rate: f32,
+}
+
+impl CombFilter {
+    pub fn new(sample_rate: f32) -> Self {
+        CombFilter {
+            buffer: vec![0.0; (sample_rate / COMB_MIN_FREQ) as usize + 2],
+            write_pos: 0,
+            delay: sample_rate / 440.0,
+            feedback: 0.0,
+            sample_rate,
+        }
+    }
+
+    pub fn set_frequency(&mut self, frequency: f32) {
+        let frequency = frequency.clamp(COMB_MIN_FREQ, self.sample_rate * 0.45);
+        self.delay = (self.sample_rate / frequency).min((self.buffer.len() - 2) as f32);
+    }
+
+    // The buffer is sized for the rate, so this clears the loop
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        let frequency = self.sample_rate / self.delay;
+        self.sample_rate = sample_rate;
+        self.buffer = vec![0.0; (sample_rate / COMB_MIN_FREQ) as usize + 2];
+        self.write_pos = 0;
+        self.set_frequency(frequency);
+    }
+
+    // Same 0..MAX_RESONANCE range as the other filters
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.feedback = resonance.clamp(0.0, MAX_RESONANCE) / MAX_RESONANCE * COMB_MAX_FEEDBACK;
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+    }
+
+    // (1 - fb) / (1 - fb z^-D), with z^-D split between the two samples read
+    fn response(&self, w: f64) -> Complex {
+        let whole = self.delay as usize;
+        let frac = self.delay - whole as f32;
+        let delayed = Complex::from_angle(-w * whole as f64) * (1.0 - frac)
+            + Complex::from_angle(-w * (whole + 1) as f64) * frac;
+        Complex::real((1.0 - self.feedback) as f64) / (Complex::real(1.0) - delayed * self.feedback)
+    }
+
+    // NaN keeps circulating in the loop, so the newest sample shows it
+    pub fn sanitize(&mut self) -> bool {
+        let len = self.buffer.len();
+        if self.buffer[(self.write_pos + len - 1) % len].is_finite() {
+            return false;
+        }
+        self.reset();
+        true
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        // Linear interpolation keeps the period exact between whole samples
+        let len = self.buffer.len();
+        let whole = self.delay as usize;
+        let frac = self.delay - whole as f32;
+        let newer = self.buffer[(self.write_pos + len - whole) % len];
+        let older = self.buffer[(self.write_pos + len - whole - 1) % len];
+        let delayed = newer + (older - newer) * frac;
+
+        let output = input + self.feedback * delayed;
+        self.buffer[self.write_pos] = output;
+        self.write_pos = (self.write_pos + 1) % len;
+        // Peaks reach 1 / (1 - feedback); this puts them back at unity
+        output * (1.0 - self.feedback)
+    }
+}
+
+const DC_BLOCK_HZ: f32 = 5.0; // Low enough to stay within 0.1 dB from 40 Hz up
+
+// One-pole highpass that removes DC offset: y = x - x[n-1] + r * y[n-1]
+pub struct DcBlocker {
+    coeff: f32,
+    last_input: f32,
+    last_output: f32,
+}
+
+impl DcBlocker {
+    pub fn new(sample_rate: f32) -> Self {
+        DcBlocker {
+            coeff: 1.0 - 2.0 * std::f32::consts::PI * DC_BLOCK_HZ / sample_rate,
+            last_input: 0.0,
+            last_output: 0.0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.last_input = 0.0;
+        self.last_output = 0.0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = input - self.last_input + self.coeff * self.last_output;
+        self.last_input = input;
+        self.last_output = output;
+        output
+    }
+}
+
+// Four cascaded one-pole stages with tanh saturation and global feedback
+// (after Huovilainen). The saturators bound the state, so it stays stable
+// up to full resonance, where it self-oscillates.
+pub struct LadderFilter {
+    sample_rate: f32,
+    cutoff: f32,
+    resonance: f32,
+    stages: [f32; 4],
+}
+
+impl LadderFilter {
+    pub fn new(sample_rate: f32) -> Self {
+        LadderFilter {
+            sample_rate,
+            cutoff: 20000.0,
+            resonance: 0.0,
+            stages: [0.0; 4],
+        }
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: f32) {
+        let cutoff = if cutoff.is_nan() { 20.0 } else { cutoff };
+        self.cutoff = cutoff.clamp(20.0, 20000.0);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    // Already self-oscillates at 1.0, so the extended SVF range is clamped here
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.resonance = resonance.clamp(0.0, 1.0);
+    }
+
+    pub fn reset(&mut self) {
+        self.stages = [0.0; 4];
+    }
+
+    pub fn sanitize(&mut self) -> bool {
+        if self.stages.iter().any(|stage| !stage.is_finite()) {
+            self.reset();
+            return true;
+        }
+        for stage in &mut self.stages {
+            if stage.abs() < DENORMAL_LIMIT {
+                *stage = 0.0;
+            }
+        }
+        false
+    }
+
+    // With the tanh stages linearized: each stage is g / (1 - (1 - g) z^-1) and the
+    // last one feeds back through a sample of delay
+    fn response(&self, cutoff: f32, z1: Complex) -> Complex {
+        let cutoff = if cutoff.is_nan() { 20.0 } else { cutoff.clamp(20.0, 20000.0) };
+        let g = 1.0 - (-2.0 * std::f32::consts::PI * cutoff / self.sample_rate).exp();
+        let stage = Complex::real(g as f64) / (Complex::real(1.0) - z1 * (1.0 - g));
+        let stages = stage * stage * stage * stage;
+        stages * (1.0 + 2.0 * self.resonance) / (Complex::real(1.0) + z1 * stages * (4.0 * self.resonance))
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let g = 1.0 - (-2.0 * std::f32::consts::PI * self.cutoff / self.sample_rate).exp();
+        // Feedback of 4 puts the loop gain at 1, the self-oscillation point
+        let feedback = 4.0 * self.resonance * self.stages[3];
+        // The feedback pulls the passband down to 1 / (1 + 4k); make half of that
+        // back up so resonance doesn't thin out the bass
+        let input = input * (1.0 + 2.0 * self.resonance);
+
+        let mut x = (input - feedback).tanh();
+        for stage in &mut self.stages {
+            *stage += g * (x - stage.tanh());
+            x = stage.tanh();
+        }
+
+        self.stages[3]
+    }
+}
+
+pub const MAX_FILTER_MODE: u8 = 10;
+const MODE_MORPH: u8 = 7;
+const MODE_FORMANT: u8 = 8;
+const MODE_COMB: u8 = 9;
+const MODE_BAND: u8 = 10;
+const COMB_MIN_FREQ: f32 = 20.0;
+const COMB_MAX_FEEDBACK: f32 = 0.995;
+const FORMANT_VOWELS_PER_OCTAVE: f32 = 0.25; // Cutoff modulation of one octave moves one vowel
+
+const MIN_CUTOFF_HZ: f32 = 20.0;
+const CUTOFF_RANGE: f32 = 1000.0; // 20 Hz to 20 kHz
+
+// Exponential knob curve for the cutoff: hz = 20 * 1000^x, so 0 -> 20 Hz,
+// 0.5 -> ~632 Hz, 1 -> 20 kHz, and equal knob travel is equal musical interval
+pub fn normalized_to_cutoff(x: f32) -> f32 {
+    MIN_CUTOFF_HZ * CUTOFF_RANGE.powf(x.clamp(0.0, 1.0))
+}
+
+pub fn cutoff_to_normalized(hz: f32) -> f32 {
+    ((hz / MIN_CUTOFF_HZ).ln() / CUTOFF_RANGE.ln()).clamp(0.0, 1.0)
+}
+
+// Keyboard tracking pivots around middle C: that note leaves the cutoff alone
+const KEYTRACK_REFERENCE_HZ: f32 = 261.63;
+
+// Cutoff multiplier for a note; amount 1 follows pitch exactly, so an octave up doubles it
+pub fn keytrack_ratio(note_freq: f32, amount: f32) -> f32 {
+    if amount == 0.0 {
+        return 1.0;
+    }
+    (note_freq / KEYTRACK_REFERENCE_HZ).powf(amount)
+}
+
+const MAX_FM_OCTAVES: f32 = 4.0;
+
+// 2^x from the float exponent bits and a cubic for the fraction, within about 0.02%.
+// Only valid for |x| < 126, which the FM amount clamp guarantees
+fn fast_exp2(x: f32) -> f32 {
+    let whole = x.floor();
+    let fraction = x - whole;
+    let mantissa = 1.0 + fraction * (0.695_976 + fraction * (0.224_940 + fraction * 0.079_084));
+    mantissa * f32::from_bits(((whole as i32 + 127) as u32) << 23)
+}
+
+// Audio-rate cutoff modulation, in octaves, from either the filter's own input
+// or a sine at the played note. The SVF already recomputes its coefficient every
+// sample, so FM only adds a polynomial exp2 per sample (and a sine for the note
+// source); at amount 0 none of it runs
+struct FilterFm {
+    amount: f32,
+    from_note: bool,
+    phase: f32,
+    increment: f32,
+    sample_rate: f32,
+}
+
+impl FilterFm {
+    fn new(sample_rate: f32) -> Self {
+        FilterFm {
+            amount: 0.0,
+            from_note: false,
+            phase: 0.0,
+            increment: 440.0 / sample_rate,
+            sample_rate,
+        }
+    }
+
+    fn set_frequency(&mut self, frequency: f32) {
+        self.increment = (frequency / self.sample_rate).clamp(0.0, 0.5);
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: f32) {
+        let frequency = self.increment * self.sample_rate;
+        self.sample_rate = sample_rate;
+        self.set_frequency(frequency);
+    }
+
+    fn modulate(&mut self, cutoff: f32, input: f32) -> f32 {
+        let modulator = if self.from_note {
+            let value = (self.phase * std::f32::consts::TAU).sin();
+            self.phase = (self.phase + self.increment).fract();
+            value
+        } else {
+            // A hot bus would push the cutoff past the amount
+            input.clamp(-1.0, 1.0)
+        };
+        cutoff * fast_exp2(modulator * self.amount)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FilterModel {
+    Svf = 0,
+    Ladder = 1,
+}
+
+impl FilterModel {
+    pub fn from_u8(model: u8) -> Self {
+        match model {
+            1 => FilterModel::Ladder,
+            _ => FilterModel::Svf,
+        }
+    }
+}
+
+// Everything behind the filter setters: the SVF with its optional second
+// stage for 24 dB/oct, and the ladder model
+pub struct FilterChain {
+    svf: StateVariableFilter,
+    stage2: StateVariableFilter,
+    ladder: LadderFilter,
+    formant: FormantFilter,
+    comb: CombFilter,
+    fm: FilterFm,
+    mode: u8,
+    mode_before_formant: u8,
+    morph: f32,
+    vowel: f32,
+    band_low: f32,
+    band_high: f32,
+    cutoff_reference: f32, // The cutoff knob; formant and band modes follow modulation relative to it
+    poles: u8,
+    model: FilterModel,
+}
+
+impl FilterChain {
+    pub fn new(sample_rate: f32) -> Self {
+        FilterChain {
+            svf: StateVariableFilter::new(sample_rate),
+            stage2: StateVariableFilter::new(sample_rate),
+            ladder: LadderFilter::new(sample_rate),
+            formant: FormantFilter::new(sample_rate),
+            comb: CombFilter::new(sample_rate),
+            fm: FilterFm::new(sample_rate),
+            mode: 0,
+            mode_before_formant: 0,
+            morph: 0.0,
+            vowel: 0.0,
+            band_low: 300.0,
+            band_high: 3000.0,
+            cutoff_reference: 20000.0,
+            poles: 2,
+            model: FilterModel::Svf,
+        }
+    }
+
+    // 0 = lowpass, 1 = highpass, 2 = bandpass, 3 = notch, 4 = peak,
+    // 5 = low shelf, 6 = high shelf, 7 = morph (LP -> BP -> HP, see set_morph),
+    // 8 = formant (see set_vowel), 9 = comb (tuned by set_note_frequency),
+    // 10 = band (see set_band_range)
+    pub fn set_mode(&mut self, mode: u8) {
+        self.mode = mode.min(MAX_FILTER_MODE);
+    }
+
+    // Switches to the formant mode, or back to the mode it replaced
+    pub fn set_formant(&mut self, enabled: bool) {
+        if enabled && self.mode != MODE_FORMANT {
+            self.mode_before_formant = self.mode;
+            self.formant.reset();
+            self.mode = MODE_FORMANT;
+        } else if !enabled && self.mode == MODE_FORMANT {
+            self.mode = self.mode_before_formant;
+        }
+    }
+
+    // Formant mode vowel: 0 = A, 0.25 = E, 0.5 = I, 0.75 = O, 1 = U
+    pub fn set_vowel(&mut self, position: f32) {
+        self.vowel = position.clamp(0.0, 1.0);
+    }
+
+    pub fn get_vowel(&self) -> f32 {
+        self.vowel
+    }
+
+    // The comb and the note FM source follow the played note rather than the cutoff
+    pub fn set_note_frequency(&mut self, frequency: f32) {
+        self.comb.set_frequency(frequency);
+        self.fm.set_frequency(frequency);
+    }
+
+    // Audio-rate cutoff modulation of up to 4 octaves, from a sine at the played
+    // note (from_note) or from the signal coming into the filter
+    pub fn set_fm(&mut self, from_note: bool, amount_octaves: f32) {
+        self.fm.from_note = from_note;
+        self.fm.amount = amount_octaves.clamp(0.0, MAX_FM_OCTAVES);
+    }
+
+    // (from_note, amount in octaves)
+    pub fn get_fm(&self) -> (bool, f32) {
+        (self.fm.from_note, self.fm.amount)
+    }
+
+    // Band mode passband: a highpass at low_hz into a lowpass at high_hz
+    pub fn set_band_range(&mut self, low_hz: f32, high_hz: f32) {
+        let low = low_hz.clamp(20.0, 20000.0);
+        let high = high_hz.clamp(20.0, 20000.0);
+        self.band_low = low.min(high);
+        self.band_high = low.max(high);
+    }
+
+    pub fn get_band_range(&self) -> (f32, f32) {
+        (self.band_low, self.band_high)
+    }
+
+    // The cutoff knob. Modulation away from it sweeps the vowel in formant mode
+    // and shifts the whole passband in band mode
+    pub fn set_cutoff_reference(&mut self, cutoff: f32) {
+        self.cutoff_reference = cutoff.clamp(20.0, 20000.0);
+    }
+
+    pub fn get_mode(&self) -> u8 {
+        self.mode
+    }
+
+    // Position of the morph mode: 0 = lowpass, 0.5 = bandpass, 1 = highpass
+    pub fn set_morph(&mut self, position: f32) {
+        self.morph = position.clamp(0.0, 1.0);
+    }
+
+    pub fn get_morph(&self) -> f32 {
+        self.morph
+    }
+
+    // Picks this mode's response out of a single SVF tick
+    fn select<T: Signal>(&self, outputs: SvfOutputs<T>) -> T {
+        match self.mode {
+            1 => outputs.hp,
+            2 => outputs.bp,
+            3 => outputs.notch,
+            MODE_MORPH => outputs.morph(self.morph),
+            _ => outputs.lp,
+        }
+    }
+
+    // 2 = 12 dB/oct, 4 = 24 dB/oct
+    pub fn set_slope(&mut self, poles: u8) {
+        let poles = if poles >= 4 { 4 } else { 2 };
+        if poles != self.poles {
+            // Don't let the second stage start from stale state
+            self.stage2.reset();
+            self.poles = poles;
+        }
+    }
+
+    pub fn get_slope(&self) -> u8 {
+        self.poles
+    }
+
+    pub fn set_model(&mut self, model: u8) {
+        let model = FilterModel::from_u8(model);
+        if model != self.model {
+            self.ladder.reset();
+            self.model = model;
+        }
+    }
+
+    pub fn get_model(&self) -> u8 {
+        self.model as u8
+    }
+
+    // Resonance stays on the first stage so the cascade doesn't double-resonate
+    pub fn set_resonance(&mut self, resonance: f32) {
+        self.svf.set_resonance(resonance);
+        self.ladder.set_resonance(resonance);
+        self.comb.set_resonance(resonance);
+    }
+
+    pub fn get_resonance(&self) -> f32 {
+        self.svf.get_resonance()
+    }
+
+    // The SVF takes the Q itself; the ladder and comb get the equivalent resonance
+    pub fn set_q(&mut self, q: f32) {
+        self.svf.set_q(q);
+        let resonance = self.svf.get_resonance();
+        self.ladder.set_resonance(resonance);
+        self.comb.set_resonance(resonance);
+    }
+
+    pub fn get_q(&self) -> f32 {
+        self.svf.get_q()
+    }
+
+    pub fn get_q_setting(&self) -> Option<f32> {
+        self.svf.get_q_setting()
+    }
+
+    pub fn set_peak_gain_db(&mut self, db: f32) {
+        self.svf.set_peak_gain_db(db);
+    }
+
+    pub fn get_peak_gain_db(&self) -> f32 {
+        self.svf.get_peak_gain_db()
+    }
+
+    pub fn set_shelf_gain_db(&mut self, db: f32) {
+        self.svf.set_shelf_gain_db(db);
+    }
+
+    pub fn set_drive(&mut self, amount: f32) {
+        self.svf.set_drive(amount);
+    }
+
+    pub fn set_smoothing_time(&mut self, ms: f32) {
+        self.svf.set_smoothing_time(ms);
+        self.stage2.set_smoothing_time(ms);
+    }
+
+    pub fn get_drive(&self) -> f32 {
+        self.svf.get_drive()
+    }
+
+    pub fn get_shelf_gain_db(&self) -> f32 {
+        self.svf.get_shelf_gain_db()
+    }
+
+    // Rate the chain runs at, for oversampling; settings carry over
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.svf.set_sample_rate(sample_rate);
+        self.stage2.set_sample_rate(sample_rate);
+        self.ladder.set_sample_rate(sample_rate);
+        self.formant.set_sample_rate(sample_rate);
+        self.comb.set_sample_rate(sample_rate);
+        self.fm.set_sample_rate(sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.svf.reset();
+        self.stage2.reset();
+        self.ladder.reset();
+        self.formant.reset();
+        self.comb.reset();
+        self.fm.phase = 0.0;
+    }
+
+    // Number of filters that had to be cleared
+    pub fn sanitize(&mut self) -> u32 {
+        self.svf.sanitize() as u32
+            + self.stage2.sanitize() as u32
+            + self.ladder.sanitize() as u32
+            + self.formant.sanitize()
+            + self.comb.sanitize() as u32
+    }
+
+    // Only the cutting modes cascade
+    fn cascades(&self) -> bool {
+        self.poles == 4 && (self.mode <= 2 || self.mode == MODE_MORPH)
+    }
+
+    // Magnitude in dB at a frequency for a cutoff, mirroring process() branch for
+    // branch without touching the filter state. Drive counts as its small-signal
+    // gain and the ladder as if its saturators were linear
+    pub fn response_db(&self, frequency: f32, cutoff: f32) -> f32 {
+        let sample_rate = self.svf.sample_rate;
+        let w = 2.0 * std::f64::consts::PI * frequency.clamp(0.0, sample_rate * 0.5) as f64 / sample_rate as f64;
+        let z1 = Complex::from_angle(-w);
+
+        let response = if self.mode == MODE_FORMANT {
+            let sweep = (cutoff / self.cutoff_reference).log2() * FORMANT_VOWELS_PER_OCTAVE;
+            self.formant.response(self.vowel + sweep, z1)
+        } else if self.mode == MODE_COMB {
+            self.comb.response(w)
+        } else if self.mode == MODE_BAND {
+            let shift = cutoff / self.cutoff_reference;
+            self.svf.response(self.band_low * shift, z1).hp
+                * self.svf.small_signal_gain()
+                * self.stage2.response(self.band_high * shift, z1).lp
+        } else if self.model == FilterModel::Ladder {
+            self.ladder.response(cutoff, z1)
+        } else {
+            let outputs = self.svf.response(cutoff, z1);
+            let one = Complex::real(1.0);
+            let first = match self.mode {
+                4 => {
+                    // process_peak scales the raw band by the smoothed damping instead
+                    let damping = self.svf.damping.max(0.0);
+                    one + outputs.bp * ((self.svf.peak_gain - 1.0) * damping / band_gain(self.svf.damping))
+                }
+                5 => one + outputs.lp * (self.svf.shelf_gain - 1.0),
+                6 => one + outputs.hp * (self.svf.shelf_gain - 1.0),
+                _ => self.select(outputs),
+            } * self.svf.small_signal_gain();
+            if self.cascades() {
+                first * self.select(self.stage2.response(cutoff, z1))
+            } else {
+                first
+            }
+        };
+        (20.0 * response.norm().max(1e-6).log10()) as f32
+    }
+
+    pub fn process(&mut self, input: f32, cutoff: f32) -> f32 {
+        let cutoff = if self.fm.amount > 0.0 { self.fm.modulate(cutoff, input) } else { cutoff };
+        if self.mode == MODE_FORMANT {
+            // Replaces either model
+            let sweep = (cutoff / self.cutoff_reference).log2() * FORMANT_VOWELS_PER_OCTAVE;
+            self.formant.set_vowel(self.vowel + sweep);
+            return self.formant.process(input);
+        }
+        if self.mode == MODE_COMB {
+            return self.comb.process(input);
+        }
+        if self.mode == MODE_BAND {
+            // Resonance lands on the low edge, like the first stage of the cascade
+            let shift = cutoff / self.cutoff_reference;
+            self.svf.set_cutoff(self.band_low * shift);
+            self.stage2.set_cutoff(self.band_high * shift);
+            let highpassed = self.svf.process(input).hp;
+            return self.stage2.process(highpassed).lp;
+        }
+        if self.model == FilterModel::Ladder {
+            // Lowpass only; mode and slope don't apply
+            self.ladder.set_cutoff(cutoff);
+            return self.ladder.process(input);
+        }
+
+        self.svf.set_cutoff(cutoff);
+        let output = match self.mode {
+            4 => self.svf.process_peak(input),
+            5 => self.svf.process_low_shelf(input),
+            6 => self.svf.process_high_shelf(input),
+            _ => {
+                let outputs = self.svf.process(input);
+                self.select(outputs)
+            }
+        };
+        if self.cascades() {
+            self.stage2.set_cutoff(cutoff);
+            let outputs = self.stage2.process(output);
+            return self.select(outputs);
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use crate::spectrum::Spectrum;
+
+    const RATE: f32 = 48000.0;
+
+    // Steady-state gain in dB of a sine through the chain at a fixed cutoff
+    fn sine_gain_db(chain: &mut FilterChain, frequency: f32, cutoff: f32) -> f32 {
+        let mut power = 0.0;
+        for i in 0..9600 {
+            let input = (std::f32::consts::TAU * frequency * i as f32 / RATE).sin();
+            let output = chain.process(input, cutoff);
+            if i >= 4800 {
+                power += output * output;
+            }
+        }
+        // A unit sine averages 0.5 power
+        10.0 * (power / 4800.0 / 0.5).log10()
+    }
+
+    #[test]
+    fn notch_dips_at_the_cutoff() {
+        let gain_at = |frequency, resonance| {
+            let mut chain = FilterChain::new(RATE);
+            chain.set_mode(3);
+            chain.set_resonance(resonance);
+            sine_gain_db(&mut chain, frequency, 1000.0)
+        };
+        assert!(gain_at(1000.0, 0.0) < -30.0);
+        for frequency in [100.0, 10000.0] {
+            assert!(gain_at(frequency, 0.0).abs() < 1.0, "{frequency} Hz: {} dB", gain_at(frequency, 0.0));
+        }
+        // Resonance narrows the notch, so a nearby frequency gets through more
+        assert!(gain_at(1000.0, 0.8) < -30.0);
+        assert!(gain_at(800.0, 0.8) > gain_at(800.0, 0.0) + 3.0);
+    }
+
+    // Magnitude in dB of an impulse response at a frequency, by direct DFT
+    fn magnitude_db(response: &[f32], frequency: f32) -> f32 {
+        let w = std::f64::consts::TAU * frequency as f64 / RATE as f64;
+        let (re, im) = response.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, &x)| {
+            (re + x as f64 * (w * n as f64).cos(), im - x as f64 * (w * n as f64).sin())
+        });
+        (20.0 * re.hypot(im).log10()) as f32
+    }
+
+    #[test]
+    fn ladder_rolls_off_at_24_db_per_octave() {
+        let mut ladder = LadderFilter::new(RATE);
+        ladder.set_cutoff(500.0);
+        // A small impulse keeps the tanh stages linear; the response is scaled back up
+        let response: Vec<f32> = (0..8192).map(|n| ladder.process(if n == 0 { 1e-3 } else { 0.0 }) * 1e3).collect();
+        assert!(magnitude_db(&response, 50.0).abs() < 0.5);
+        let slope = magnitude_db(&response, 4000.0) - magnitude_db(&response, 2000.0);
+        assert!((slope + 24.0).abs() < 2.0, "{slope} dB/oct");
+    }
+
+    #[test]
+    fn ladder_is_stable_at_full_resonance() {
+        let mut ladder = LadderFilter::new(RATE);
+        ladder.set_resonance(1.0);
+        for n in 0..RATE as usize {
+            // Swept like the LFO path would, over the whole range
+            ladder.set_cutoff(20.0 * 1000.0_f32.powf((n as f32 / 4800.0).sin() * 0.5 + 0.5));
+            let output = ladder.process(if n == 0 { 1.0 } else { 0.0 });
+            assert!(output.is_finite() && output.abs() < 2.0);
+        }
+    }
+
+    #[test]
+    fn normalized_cutoff_curve() {
+        assert_eq!(normalized_to_cutoff(0.0), 20.0);
+        assert!((normalized_to_cutoff(1.0) - 20000.0).abs() < 0.1);
+        // 20 * sqrt(1000)
+        assert!((normalized_to_cutoff(0.5) - 632.46).abs() < 0.1);
+        for x in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            assert!((cutoff_to_normalized(normalized_to_cutoff(x)) - x).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn sanitize_clears_a_broken_state() {
+        let mut svf = StateVariableFilter::new(RATE);
+        svf.process(1.0);
+        svf.band = f32::NAN;
+        assert!(svf.sanitize());
+        assert!(svf.process(1.0).lp.is_finite());
+        assert!(!svf.sanitize());
+    }
+
+    #[test]
+    fn comb_on_noise_peaks_at_the_note() {
+        let mut chain = FilterChain::new(RATE);
+        chain.set_mode(MODE_COMB);
+        chain.set_resonance(1.0);
+        chain.set_note_frequency(220.0);
+        let mut rng = Rng::new(7);
+        let output: Vec<f32> = (0..65536).map(|_| chain.process(rng.random() * 2.0 - 1.0, 1000.0)).collect();
+
+        // Averaged over eight windows so the noise doesn't hide the shape
+        let mut spectrum = Spectrum::new();
+        let mut power = vec![0.0; 4096];
+        let mut bins = [0.0; 4096];
+        for window in output.chunks(8192) {
+            spectrum.analyze(window, &mut bins);
+            for (sum, db) in power.iter_mut().zip(&bins) {
+                *sum += 10.0_f32.powf(db / 10.0);
+            }
+        }
+        let db_at = |hz: f32| 10.0 * power[(hz * 8192.0 / RATE).round() as usize].log10();
+        let peak = (5..64).max_by(|&a, &b| power[a].total_cmp(&power[b])).unwrap() as f32 * RATE / 8192.0;
+        assert!((peak - 220.0).abs() < 6.0, "{peak} Hz");
+        // Between the harmonics the comb is a trough
+        assert!(db_at(220.0) > db_at(330.0) + 20.0);
+    }
+
+    #[test]
+    fn dc_blocker_removes_offset_and_keeps_the_bass() {
+        let mut blocker = DcBlocker::new(RATE);
+        let settled: Vec<f32> = (0..RATE as usize).map(|_| blocker.process(0.5)).collect();
+        assert!(settled[RATE as usize - 1].abs() < 1e-3, "{}", settled[RATE as usize - 1]);
+
+        let mut blocker = DcBlocker::new(RATE);
+        let mut power = 0.0;
+        for i in 0..RATE as usize {
+            let output = blocker.process((std::f32::consts::TAU * 40.0 * i as f32 / RATE).sin());
+            if i >= RATE as usize / 2 {
+                power += output * output;
+            }
+        }
+        let db = 10.0 * (power / (RATE / 2.0) / 0.5).log10();
+        assert!(db.abs() < 0.1, "{db} dB at 40 Hz");
+    }
+
+    #[test]
+    fn band_mode_passes_inside_the_range() {
+        let gain_at = |frequency| {
+            let mut chain = FilterChain::new(RATE);
+            chain.set_mode(MODE_BAND);
+            chain.set_band_range(300.0, 3000.0);
+            // At the cutoff knob the band sits where it was set
+            sine_gain_db(&mut chain, frequency, 20000.0)
+        };
+        assert!(gain_at(1000.0).abs() < 1.5, "{} dB", gain_at(1000.0));
+        for frequency in [50.0, 15000.0] {
+            assert!(gain_at(frequency) < -20.0, "{frequency} Hz: {} dB", gain_at(frequency));
+        }
+    }
+
+    #[test]
+    fn lowpass_highpass_and_bandpass_pass_at_the_same_level() {
+        for resonance in [0.0, 0.5, 1.0] {
+            let gain_at = |mode, frequency| {
+                let mut chain = FilterChain::new(RATE);
+                chain.set_mode(mode);
+                chain.set_resonance(resonance);
+                sine_gain_db(&mut chain, frequency, 1000.0)
+            };
+            let gains = [gain_at(0, 100.0), gain_at(1, 15000.0), gain_at(2, 1000.0)];
+            let spread = gains.iter().fold(f32::MIN, |a, &b| a.max(b)) - gains.iter().fold(f32::MAX, |a, &b| a.min(b));
+            assert!(gains[0].abs() < 1.0 && spread < 1.0, "resonance {resonance}: {gains:?} dB");
+        }
+    }
+
+    #[test]
+    fn bandpass_bandwidth_follows_q() {
+        for q in [1.0, 4.0, 16.0] {
+            let gain_at = |frequency| {
+                let mut chain = FilterChain::new(RATE);
+                chain.set_mode(2);
+                chain.set_q(q);
+                sine_gain_db(&mut chain, frequency, 1000.0)
+            };
+            // Bisects for the -3 dB point between a frequency inside the band and one outside
+            let edge = |mut inside: f32, mut outside: f32| {
+                for _ in 0..24 {
+                    let middle = (inside * outside).sqrt();
+                    if gain_at(middle) > -3.01 { inside = middle } else { outside = middle }
+                }
+                inside
+            };
+            let bandwidth = edge(1000.0, 20000.0) - edge(1000.0, 20.0);
+            let expected = 1000.0 / q;
+            // The one-sample delay in the recursion widens the widest bands a little
+            assert!((bandwidth - expected).abs() < 0.1 * expected, "Q {q}: {bandwidth} Hz");
+        }
+    }
+
+    #[test]
+    fn full_resonance_sings_a_steady_sine_at_the_cutoff_from_silence() {
+        let mut svf = StateVariableFilter::new(RATE);
+        svf.set_resonance(MAX_RESONANCE);
+        svf.set_cutoff(1000.0);
+        // The seed takes a while to build up into the held level
+        let output: Vec<f32> = (0..4 * RATE as usize).map(|_| svf.process(0.0).lp).collect();
+        let peak = |block: &[f32]| block.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+        let (early, late) = (peak(&output[96000..120000]), peak(&output[168000..]));
+        assert!(early > 0.1 && late < 1.0, "{early} {late}");
+        assert!((late / early - 1.0).abs() < 0.05, "{early} {late}");
+
+        let tail = &output[144000..];
+        let crossings = tail.windows(2).filter(|w| w[0] <= 0.0 && w[1] > 0.0).count();
+        let pitch = crossings as f32 * RATE / tail.len() as f32;
+        assert!((pitch - 1000.0).abs() < 30.0, "{pitch}");
+    }
+}
@@ -0,0 +1,90 @@
+// Level detector: the rectified input through a one-pole that rises at the
+// attack rate and falls at the release rate
+pub struct EnvelopeFollower {
+    level: f32,
+    attack_ms: f32,
+    release_ms: f32,
+    attack_coeff: f32,
+    release_coeff: f32,
+    sample_rate: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new(sample_rate: f32, attack_ms: f32, release_ms: f32) -> Self {
+        let mut follower = EnvelopeFollower {
+            level: 0.0,
+            attack_ms: 0.0,
+            release_ms: 0.0,
+            attack_coeff: 1.0,
+            release_coeff: 1.0,
+            sample_rate,
+        };
+        follower.set_attack(attack_ms);
+        follower.set_release(release_ms);
+        follower
+    }
+
+    pub fn set_attack(&mut self, ms: f32) {
+        self.attack_ms = ms.max(0.0);
+        self.attack_coeff = self.coeff(self.attack_ms);
+    }
+
+    pub fn set_release(&mut self, ms: f32) {
+        self.release_ms = ms.max(0.0);
+        self.release_coeff = self.coeff(self.release_ms);
+    }
+
+    // Same one-pole curve as the parameter smoothers; 0 ms follows instantly
+    fn coeff(&self, ms: f32) -> f32 {
+        1.0 - (-1000.0 / (ms * self.sample_rate)).exp()
+    }
+
+    pub fn get_attack(&self) -> f32 {
+        self.attack_ms
+    }
+
+    pub fn get_release(&self) -> f32 {
+        self.release_ms
+    }
+
+    pub fn get_level(&self) -> f32 {
+        self.level
+    }
+
+    pub fn reset(&mut self) {
+        self.level = 0.0;
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let rectified = input.abs();
+        let coeff = if rectified > self.level { self.attack_coeff } else { self.release_coeff };
+        self.level += (rectified - self.level) * coeff;
+        self.level
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Level after a 10 ms block of a 1 kHz sine at the given amplitude
+    fn level_after_block(amplitude: f32) -> f32 {
+        let mut follower = EnvelopeFollower::new(48000.0, 5.0, 150.0);
+        (0..480).map(|n| follower.process(amplitude * (n as f32 * std::f32::consts::TAU / 48.0).sin())).last().unwrap()
+    }
+
+    #[test]
+    fn loud_block_reads_higher_than_quiet_block() {
+        let (loud, quiet) = (level_after_block(0.8), level_after_block(0.1));
+        assert!(loud > 4.0 * quiet, "{loud} vs {quiet}");
+        assert!(loud > 0.3 && loud <= 0.8, "{loud}");
+    }
+
+    #[test]
+    fn level_falls_at_the_release_rate() {
+        let mut follower = EnvelopeFollower::new(48000.0, 0.0, 100.0);
+        follower.process(1.0);
+        let level = (0..4800).map(|_| follower.process(0.0)).last().unwrap();
+        assert!((level - (-1.0f32).exp()).abs() < 0.01, "{level}");
+    }
+}
@@ -1,9 +1,34 @@
+use crate::rng::Rng;
+use crate::smoother::Smoother;
+
+const MAX_RATE_HZ: f32 = 500.0;
+const DEPTH_SMOOTHING_MS: f32 = 5.0;
+
 #[derive(Clone, Copy)]
 pub enum LfoWaveform {
     Sine = 0,
     Triangle = 1,
     Square = 2,
     SampleHold = 3,
+    SmoothRandom = 4,
+}
+
+// How a destination reads the LFO
+#[derive(Clone, Copy, PartialEq)]
+pub enum LfoPolarity {
+    Legacy = 0,   // Filter only: cutoff * (1 + lfo * depth), the original behavior
+    Bipolar = 1,  // -depth..depth
+    Unipolar = 2, // 0..depth
+}
+
+impl LfoPolarity {
+    pub fn from_u8(mode: u8) -> Self {
+        match mode {
+            0 => LfoPolarity::Legacy,
+            2 => LfoPolarity::Unipolar,
+            _ => LfoPolarity::Bipolar,
+        }
+    }
 }
 
 pub struct Lfo {
@@ -11,10 +36,22 @@ pub struct Lfo {
     phase_increment: f32,
     rate: f32,
     depth: f32,
+    depth_smoother: Smoother,
+    current_depth: f32,
     sample_rate: f32,
     waveform: LfoWaveform,
     sample_hold_value: f32,
     sample_hold_counter: f32,
+    smooth_start: f32,
+    smooth_target: f32,
+    smooth_period: f32,
+    rng: Rng,
+    one_shot: bool,
+    finished: bool,
+    held_output: f32,
+    phase_offset: f32,
+    last_output: f32,
+    last_raw: f32,
 }
 
 impl Lfo {
@@ -24,20 +61,34 @@ impl Lfo {
             phase_increment: 0.0,
             rate: 1.0,
             depth: 0.0,
+            depth_smoother: Smoother::new(sample_rate, 0.0, DEPTH_SMOOTHING_MS),
+            current_depth: 0.0,
             sample_rate,
             waveform: LfoWaveform::Sine,
             sample_hold_value: 0.0,
             sample_hold_counter: 0.0,
+            smooth_start: 0.0,
+            smooth_target: 0.0,
+            smooth_period: 1.0,
+            rng: Rng::next_default(),
+            one_shot: false,
+            finished: false,
+            held_output: 0.0,
+            phase_offset: 0.0,
+            last_output: 0.0,
+            last_raw: 0.0,
         }
     }
 
     pub fn set_rate(&mut self, rate_hz: f32) {
-        self.rate = rate_hz.clamp(0.01, 50.0);
+        // Up to the low audio range for FM-style filter and amp modulation
+        self.rate = rate_hz.clamp(0.01, MAX_RATE_HZ);
         self.phase_increment = self.rate / self.sample_rate;
     }
 
     pub fn set_depth(&mut self, depth: f32) {
         self.depth = depth.clamp(0.0, 1.0);
+        self.depth_smoother.set_target(self.depth);
     }
 
     pub fn set_waveform(&mut self, waveform: u8) {
@@ -46,53 +97,234 @@ impl Lfo {
             1 => LfoWaveform::Triangle,
             2 => LfoWaveform::Square,
             3 => LfoWaveform::SampleHold,
+            4 => LfoWaveform::SmoothRandom,
             _ => LfoWaveform::Sine,
         };
     }
 
+    pub fn get_rate(&self) -> f32 {
+        self.rate
+    }
+
+    pub fn get_depth(&self) -> f32 {
+        self.depth
+    }
+
+    pub fn get_waveform(&self) -> u8 {
+        self.waveform as u8
+    }
+
+    pub fn is_one_shot(&self) -> bool {
+        self.one_shot
+    }
+
+    pub fn get_phase_offset(&self) -> f32 {
+        self.phase_offset
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng.set_seed(seed);
+    }
+
+    // One-shot: run a single cycle after each retrigger, then hold the final value
+    pub fn set_one_shot(&mut self, enabled: bool) {
+        self.one_shot = enabled;
+        if !enabled {
+            self.finished = false;
+        }
+    }
+
+    // Where in the cycle the waveform is read from (0.0-1.0), e.g. 0.25 starts a sine at its peak
+    pub fn set_phase_offset(&mut self, offset: f32) {
+        self.phase_offset = offset.clamp(0.0, 1.0);
+    }
+
+    // Retrigger with a random start phase in 0..spread so parallel LFOs drift apart
+    pub fn retrigger_scattered(&mut self, spread: f32) {
+        self.retrigger();
+        self.phase = self.rng.random() * spread.clamp(0.0, 1.0);
+    }
+
+    pub fn retrigger(&mut self) {
+        self.phase = 0.0;
+        // Random waveforms take the offset as how far into the first step we start. The
+        // counter holds the samples left in the step, so the next process() draws a value
+        // and leaves (1 - offset) of the step; offset 0 draws a full step as before
+        self.sample_hold_counter = -self.phase_offset * self.sample_rate / self.rate;
+        self.finished = false;
+    }
+
     pub fn process(&mut self) -> f32 {
+        self.current_depth = self.depth_smoother.next();
+
+        if self.one_shot && self.finished {
+            self.last_raw = self.held_output;
+            self.last_output = self.held_output * self.current_depth;
+            return self.last_output;
+        }
+
+        let mut phase = self.phase + self.phase_offset;
+        if phase >= 1.0 {
+            phase -= 1.0;
+        }
+
         let output = match self.waveform {
             LfoWaveform::Sine => {
-                (self.phase * 2.0 * std::f32::consts::PI).sin()
+                (phase * 2.0 * std::f32::consts::PI).sin()
             }
             LfoWaveform::Triangle => {
-                if self.phase < 0.5 {
-                    4.0 * self.phase - 1.0
+                if phase < 0.5 {
+                    4.0 * phase - 1.0
                 } else {
-                    3.0 - 4.0 * self.phase
+                    3.0 - 4.0 * phase
                 }
             }
             LfoWaveform::Square => {
-                if self.phase < 0.5 { 1.0 } else { -1.0 }
+                if phase < 0.5 { 1.0 } else { -1.0 }
             }
             LfoWaveform::SampleHold => {
+                // Accumulate the period instead of resetting so fractional step
+                // lengths at high rates keep the average rate exact
+                if self.sample_hold_counter <= 0.0 {
+                    self.sample_hold_value = (self.rng.random() * 2.0) - 1.0;
+                    self.sample_hold_counter += self.sample_rate / self.rate;
+                }
+                self.sample_hold_counter -= 1.0;
+                self.sample_hold_value
+            }
+            LfoWaveform::SmoothRandom => {
+                // Glide from wherever we are now toward a new random target each cycle
                 if self.sample_hold_counter <= 0.0 {
-                    self.sample_hold_value = (rand::random() * 2.0) - 1.0;
-                    self.sample_hold_counter = self.sample_rate / self.rate;
+                    self.smooth_start = self.sample_hold_value;
+                    self.smooth_target = (self.rng.random() * 2.0) - 1.0;
+                    self.smooth_period = self.sample_rate / self.rate;
+                    self.sample_hold_counter += self.smooth_period;
                 }
+                let t = (1.0 - self.sample_hold_counter / self.smooth_period).clamp(0.0, 1.0);
+                let shape = 0.5 - 0.5 * (t * std::f32::consts::PI).cos();
                 self.sample_hold_counter -= 1.0;
+                self.sample_hold_value =
+                    self.smooth_start + (self.smooth_target - self.smooth_start) * shape;
                 self.sample_hold_value
             }
         };
 
         self.phase += self.phase_increment;
         if self.phase >= 1.0 {
+            if self.one_shot {
+                self.finished = true;
+                self.held_output = output;
+            }
             self.phase -= 1.0;
         }
 
-        output * self.depth
+        self.last_raw = output;
+        self.last_output = output * self.current_depth;
+        self.last_output
+    }
+
+    // Last waveform value (-1..1) before depth is applied, for destinations with their own amount
+    pub fn peek_raw(&self) -> f32 {
+        self.last_raw
+    }
+
+    // Last value returned by process(), without advancing anything
+    pub fn peek(&self) -> f32 {
+        self.last_output
+    }
+
+    // Current read position in the cycle (0.0-1.0), including the phase offset
+    pub fn get_phase(&self) -> f32 {
+        (self.phase + self.phase_offset) % 1.0
     }
 }
 
-// Simple PRNG for sample-and-hold
-mod rand {
-    static mut SEED: u32 = 12345;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn random() -> f32 {
-        unsafe {
-            SEED = SEED.wrapping_mul(1103515245).wrapping_add(12345);
-            (SEED >> 16) as f32 / 65536.0
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    // Depth fully ramped in, so outputs are the raw waveform
+    fn lfo_at(rate: f32, waveform: u8) -> Lfo {
+        let mut lfo = Lfo::new(SAMPLE_RATE);
+        lfo.set_rate(rate);
+        lfo.set_waveform(waveform);
+        lfo.set_depth(1.0);
+        for _ in 0..4800 {
+            lfo.process();
         }
+        lfo
     }
-}
 
+    #[test]
+    fn one_shot_runs_a_single_cycle_after_retrigger() {
+        let mut lfo = lfo_at(100.0, 0);
+        lfo.set_one_shot(true);
+        lfo.retrigger();
+        let period = (SAMPLE_RATE / 100.0) as usize;
+        let output: Vec<f32> = (0..period * 4).map(|_| lfo.process()).collect();
+
+        // One peak and one trough, then the value holds
+        let peaks = output.windows(2).filter(|w| w[0] < 0.999 && w[1] >= 0.999).count();
+        let troughs = output.windows(2).filter(|w| w[0] > -0.999 && w[1] <= -0.999).count();
+        assert_eq!((peaks, troughs), (1, 1));
+        let held = output[period];
+        assert!(output[period..].iter().all(|&x| x == held));
+
+        lfo.retrigger();
+        assert_ne!(lfo.process(), held);
+    }
+
+    // Samples until a sample & hold LFO leaves the value drawn at retrigger
+    fn first_step_length(offset: f32) -> usize {
+        let mut lfo = lfo_at(100.0, 3);
+        lfo.set_seed(7);
+        lfo.set_phase_offset(offset);
+        lfo.retrigger();
+        let first = lfo.process();
+        1 + (0..1000).take_while(|_| lfo.process() == first).count()
+    }
+
+    #[test]
+    fn phase_offset_starts_part_way_into_a_random_step() {
+        assert_eq!(first_step_length(0.0), 480);
+        assert_eq!(first_step_length(0.25), 360);
+        assert_eq!(first_step_length(0.75), 120);
+
+        // Smooth random begins its first glide the same fraction in
+        let mut lfo = lfo_at(100.0, 4);
+        lfo.set_phase_offset(0.5);
+        lfo.retrigger();
+        let output = lfo.process();
+        let progress = (output - lfo.smooth_start) / (lfo.smooth_target - lfo.smooth_start);
+        assert!((progress - 0.5).abs() < 0.01, "progress {}", progress);
+    }
+
+    fn rising_zero_crossings(signal: &[f32]) -> usize {
+        signal.windows(2).filter(|w| w[0] <= 0.0 && w[1] > 0.0).count()
+    }
+
+    #[test]
+    fn audio_rate_lfo_runs_at_its_set_frequency() {
+        let mut lfo = lfo_at(300.0, 0);
+        let second: Vec<f32> = (0..SAMPLE_RATE as usize).map(|_| lfo.process()).collect();
+        assert!(rising_zero_crossings(&second).abs_diff(300) <= 1);
+
+        // 160 samples per step: one new value per step, none missed or doubled
+        let mut lfo = lfo_at(300.0, 3);
+        let second: Vec<f32> = (0..SAMPLE_RATE as usize).map(|_| lfo.process()).collect();
+        let steps = second.windows(2).filter(|w| w[0] != w[1]).count();
+        assert!(steps.abs_diff(300) <= 1, "{} steps", steps);
+    }
+
+    #[test]
+    fn depth_changes_ramp_instead_of_stepping() {
+        let mut lfo = lfo_at(2.0, 2);
+        lfo.set_depth(0.0);
+        let output: Vec<f32> = (0..480).map(|_| lfo.process()).collect();
+        let largest_step = output.windows(2).map(|w| (w[1] - w[0]).abs()).fold(0.0, f32::max);
+        assert!(largest_step < 0.01, "step {}", largest_step);
+        assert!(output[479].abs() < 0.2);
+    }
+}
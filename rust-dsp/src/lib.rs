@@ -5,182 +5,531 @@ mod envelope;
 mod voice;
 mod lfo;
 mod effects;
+mod tuning;
+mod filter;
+mod smoother;
+mod rng;
+mod state;
+mod modmatrix;
+mod clock;
+mod arpeggiator;
+mod sequencer;
+mod spectrum;
+mod oversampler;
+mod follower;
 
 use voice::Voice;
-use lfo::Lfo;
+use lfo::{Lfo, LfoPolarity};
 use effects::delay::Delay;
 use effects::reverb::Reverb;
 use effects::tremolo::Tremolo;
 use effects::flanger::Flanger;
+use effects::ringmod::RingMod;
+use effects::gate::Gate;
+use effects::autopan::AutoPan;
+use effects::duck::Duck;
+use effects::formant::Formant;
+use effects::convolution::Convolution;
+use oversampler::Oversampler;
+use follower::EnvelopeFollower;
+use effects::pitchshift::PitchShifter;
+use effects::freqshift::FreqShifter;
+use effects::widener::Widener;
+use effects::noisegate::NoiseGate;
+use effects::ensemble::Ensemble;
+use effects::glide::GlideMode;
+use tuning::Tuning;
+use filter::{cutoff_to_normalized, keytrack_ratio, normalized_to_cutoff, DcBlocker, FilterChain};
+use smoother::Smoother;
+use envelope::Envelope;
+use modmatrix::{ModDest, ModMatrix, ModSource, ModSources, NUM_SOURCES};
+use arpeggiator::Arpeggiator;
+use sequencer::Sequencer;
+use spectrum::Spectrum;
+use clock::{NoteEvent, DEFAULT_TEMPO_BPM};
+use state::{parse_preset, preset_to_json, SynthState};
+use rng::stream_seed;
 
-const SAMPLE_RATE: f32 = 48000.0;
-const MAX_VOICES_PER_ENGINE: usize = 16; // Each engine gets 16 voices
+const SAMPLE_RATE: f32 = 48000.0; // Default; with_sample_rate matches the AudioContext
+const MAX_VOICES_PER_ENGINE: usize = 16; // Each engine gets 16 voices unless built with_max_voices
+const VOICE_LIMIT: usize = 64;
+const DEFAULT_ENGINE_VOLUME: f32 = 0.21; // 70% of 0.3 max
+const DEFAULT_SMOOTHING_MS: f32 = 10.0;
+const AFTERTOUCH_OCTAVES: f32 = 3.0; // Cutoff rise at full pressure
+const ENV_FOLLOW_ATTACK_MS: f32 = 5.0;
+const ENV_FOLLOW_RELEASE_MS: f32 = 150.0;
+
+// Stream ids of the random sources under one seed; voices count up from VOICE_STREAMS
+const LIVE_ENGINE_STREAM: u64 = 0;
+const TIMELINE_ENGINE_STREAM: u64 = 1;
+const LFO_STREAM: u64 = 0;
+const TREMOLO_STREAM: u64 = 1;
+const FLANGER_STREAM: u64 = 2;
+const ARP_STREAM: u64 = 3;
+const AUTOPAN_STREAM: u64 = 4;
+const DELAY_STREAM: u64 = 5;
+const VOICE_STREAMS: u64 = 1 << 32;
 
 // Dual engine system: separate timeline and live performance engines
 struct Engine {
     voices: Vec<Voice>,
+    voice_mutes: Vec<bool>, // Debug aid: muted voices run as usual but are left out of the sum
+    muted_scratch: Vec<f32>,
     lfo: Lfo,
+    filter: FilterChain,
+    filter_enabled: bool,
+    filter_per_voice: bool,
+    cutoff_frames: Vec<f32>, // Modulated cutoff per sample, shared by every voice filter
+    filter_keytrack: f32,
+    filter_resets: u32, // Times a filter state went NaN/Inf and was cleared
+    last_note_freq: f32, // Drives keytracking of the global filter
+    base_filter_cutoff: f32,
+    cutoff_smoother: Smoother,
+    lfo_to_filter: bool,
+    lfo_polarity: LfoPolarity,
+    lfo_filter_octaves: f32,
+    env_follower: EnvelopeFollower, // Level of the voice sum, for auto-wah
+    env_follow_octaves: f32,        // Cutoff rise at full level; 0 = follower off
     delay: Delay,
     reverb: Reverb,
+    convolution: Convolution,
     tremolo: Tremolo,
     flanger: Flanger,
+    ringmod: RingMod,
+    formant: Formant,
+    pitchshift: PitchShifter,
+    freqshift: FreqShifter,
+    gate: Gate,
+    autopan: AutoPan,
+    noisegate: NoiseGate,
+    ensemble: Ensemble,
     delay_enabled: bool,
     reverb_enabled: bool,
+    convolution_enabled: bool,
     tremolo_enabled: bool,
     flanger_enabled: bool,
+    ringmod_enabled: bool,
+    formant_enabled: bool,
+    pitchshift_enabled: bool,
+    freqshift_enabled: bool,
+    gate_enabled: bool,
+    autopan_enabled: bool,
+    noisegate_enabled: bool,
+    ensemble_enabled: bool,
+    effects_bypassed: bool, // Output the dry voice mix; filter and effects run on a discarded copy
+    // Return level of each effect: dry * (1 - level) + wet * level
+    delay_level: f32,
+    reverb_level: f32,
+    tremolo_level: f32,
+    flanger_level: f32,
     detune_cents: f32,
+    retrigger_mode: RetriggerMode,
+    glide_mode: GlideMode,
+    tuning: Tuning,
+    lfo_filter_scale: f32,
+    mod_wheel: f32,
+    mod_wheel_dest: ModWheelDest,
+    aftertouch: Smoother,
+    aftertouch_dest: AftertouchDest,
+    mod_matrix: ModMatrix,
+    mod_frames: Vec<ModSources>,
+    filter_env: Envelope,
+    last_velocity: f32,
+    arp: Arpeggiator,
+    sequencer: Sequencer,
+    note_events: Vec<NoteEvent>,
+    tempo_bpm: f32,
+    next_voice_id: u32, // For MPE notes; 0 is never handed out
+    oversampler: Oversampler, // Runs the global filter and ring mod at 1x, 2x or 4x
+    sample_rate: f32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum AftertouchDest {
+    Cutoff,
+    Gain,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ModWheelDest {
+    None,
+    Vibrato,
+    FilterLfo,
+    Tremolo,
+}
+
+impl ModWheelDest {
+    fn from_u8(dest: u8) -> Self {
+        match dest {
+            1 => ModWheelDest::Vibrato,
+            2 => ModWheelDest::FilterLfo,
+            3 => ModWheelDest::Tremolo,
+            _ => ModWheelDest::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RetriggerMode {
+    Stack,
+    Retrigger,
+    Ignore,
+}
+
+impl RetriggerMode {
+    fn from_u8(mode: u8) -> Self {
+        match mode {
+            1 => RetriggerMode::Retrigger,
+            2 => RetriggerMode::Ignore,
+            _ => RetriggerMode::Stack,
+        }
+    }
 }
 
 impl Engine {
-    fn new(sample_rate: f32) -> Self {
-        let mut voices = Vec::with_capacity(MAX_VOICES_PER_ENGINE);
-        for _ in 0..MAX_VOICES_PER_ENGINE {
+    fn new(sample_rate: f32, max_voices: usize) -> Self {
+        let mut voices = Vec::with_capacity(max_voices);
+        for _ in 0..max_voices {
             voices.push(Voice::new(sample_rate));
         }
 
         Engine {
+            voice_mutes: vec![false; voices.len()],
+            muted_scratch: Vec::new(),
             voices,
             lfo: Lfo::new(sample_rate),
+            filter: FilterChain::new(sample_rate),
+            filter_enabled: false,
+            filter_per_voice: false,
+            cutoff_frames: Vec::new(),
+            filter_keytrack: 0.0,
+            filter_resets: 0,
+            last_note_freq: 261.63,
+            base_filter_cutoff: 20000.0,
+            cutoff_smoother: Smoother::new(sample_rate, 20000.0, DEFAULT_SMOOTHING_MS),
+            lfo_to_filter: false,
+            lfo_polarity: LfoPolarity::Bipolar,
+            lfo_filter_octaves: 1.0,
+            env_follower: EnvelopeFollower::new(sample_rate, ENV_FOLLOW_ATTACK_MS, ENV_FOLLOW_RELEASE_MS),
+            env_follow_octaves: 0.0,
             delay: Delay::new(sample_rate, 2000.0),
             reverb: Reverb::new(sample_rate),
+            convolution: Convolution::new(sample_rate),
             tremolo: Tremolo::new(sample_rate),
             flanger: Flanger::new(sample_rate),
+            ringmod: RingMod::new(sample_rate),
+            formant: Formant::new(sample_rate),
+            pitchshift: PitchShifter::new(sample_rate),
+            freqshift: FreqShifter::new(sample_rate),
+            gate: Gate::new(sample_rate),
+            autopan: AutoPan::new(sample_rate),
+            noisegate: NoiseGate::new(sample_rate),
+            ensemble: Ensemble::new(sample_rate),
             delay_enabled: false,
             reverb_enabled: false,
+            convolution_enabled: false,
             tremolo_enabled: false,
             flanger_enabled: false,
+            ringmod_enabled: false,
+            formant_enabled: false,
+            pitchshift_enabled: false,
+            freqshift_enabled: false,
+            gate_enabled: false,
+            autopan_enabled: false,
+            noisegate_enabled: false,
+            ensemble_enabled: false,
+            effects_bypassed: false,
+            delay_level: 1.0,
+            reverb_level: 1.0,
+            tremolo_level: 1.0,
+            flanger_level: 1.0,
             detune_cents: 0.0,
+            retrigger_mode: RetriggerMode::Stack,
+            glide_mode: GlideMode::Always,
+            tuning: Tuning::new(),
+            lfo_filter_scale: 1.0,
+            mod_wheel: 0.0,
+            mod_wheel_dest: ModWheelDest::None,
+            aftertouch: Smoother::new(sample_rate, 0.0, DEFAULT_SMOOTHING_MS),
+            aftertouch_dest: AftertouchDest::Cutoff,
+            mod_matrix: ModMatrix::new(),
+            mod_frames: Vec::new(),
+            filter_env: Envelope::new(sample_rate),
+            last_velocity: 1.0,
+            arp: Arpeggiator::new(sample_rate),
+            sequencer: Sequencer::new(sample_rate),
+            note_events: Vec::new(),
+            tempo_bpm: DEFAULT_TEMPO_BPM,
+            next_voice_id: 1,
+            oversampler: Oversampler::new(1),
+            sample_rate,
         }
     }
 
-    fn process_voices(&mut self, output: &mut [f32]) {
-        for voice in &mut self.voices {
-            if voice.is_active() {
-                voice.process(output);
-            }
+    // Keys go to the arpeggiator when it is running, straight to the voices otherwise
+    fn note_on(&mut self, midi_note: u8, velocity: f32) {
+        if self.arp.is_enabled() {
+            self.arp.note_on(midi_note, velocity);
+        } else {
+            self.trigger_note(midi_note, velocity);
         }
     }
 
-    fn process_effects(&mut self, buffer: &mut [f32]) {
-        for i in 0..buffer.len() {
-            let mut sample = buffer[i];
+    fn note_off(&mut self, midi_note: u8) {
+        if self.arp.is_enabled() {
+            self.arp.note_off(midi_note);
+        } else {
+            self.release_note(midi_note);
+        }
+    }
 
-            // Apply effects chain
-            if self.flanger_enabled {
-                sample = self.flanger.process(sample);
-            }
-            if self.tremolo_enabled {
-                sample = self.tremolo.process(sample);
-            }
-            if self.delay_enabled {
-                sample = self.delay.process(sample);
-            }
-            if self.reverb_enabled {
-                sample = self.reverb.process(sample);
+    fn set_arp(&mut self, enabled: bool, mode: u8, rate_division: u8) {
+        if self.arp.is_enabled() != enabled {
+            // Switching over drops whatever the old path was holding
+            for voice in &mut self.voices {
+                voice.note_off();
             }
+            self.filter_env.gate_off();
+        }
+        self.arp.set_enabled(enabled);
+        self.arp.set_mode(mode);
+        self.arp.set_division(rate_division);
+    }
+
+    fn set_tempo(&mut self, bpm: f32) {
+        self.tempo_bpm = bpm;
+        self.gate.set_tempo(bpm);
+        self.delay.set_tempo(bpm);
+    }
 
-            buffer[i] = sample;
+    // The oversampled stages are retuned to the higher rate, which clears the comb filter
+    fn set_oversampling(&mut self, factor: u8) {
+        if Oversampler::new(factor).get_factor() == self.oversampler.get_factor() {
+            return;
         }
+        self.oversampler = Oversampler::new(factor);
+        let rate = self.sample_rate * self.oversampler.get_factor() as f32;
+        self.filter.set_sample_rate(rate);
+        self.ringmod.set_sample_rate(rate);
     }
-}
 
-#[wasm_bindgen]
-pub struct AudioEngine {
-    timeline_engine: Engine,
-    live_engine: Engine,
-    timeline_volume: f32,
-    live_volume: f32,
-    master_volume: f32,
-}
+    // Restarts from silence when switched on, so an old level doesn't open the filter
+    fn set_env_follow(&mut self, octaves: f32) {
+        if self.env_follow_octaves == 0.0 {
+            self.env_follower.reset();
+        }
+        self.env_follow_octaves = octaves.clamp(-8.0, 8.0);
+    }
 
-#[wasm_bindgen]
-impl AudioEngine {
-    #[wasm_bindgen(constructor)]
-    pub fn new() -> AudioEngine {
-        AudioEngine {
-            timeline_engine: Engine::new(SAMPLE_RATE),
-            live_engine: Engine::new(SAMPLE_RATE),
-            timeline_volume: 0.21, // 70% of 0.3 max
-            live_volume: 0.21,     // 70% of 0.3 max
-            master_volume: 1.0,    // Master is now just a final gain stage
+    fn set_delay(&mut self, enabled: bool, time_ms: f32, feedback: f32, mix: f32) {
+        if enabled && !self.delay_enabled {
+            self.delay.reset_tone();
+        }
+        self.delay_enabled = enabled;
+        if enabled {
+            self.delay.set_delay_time(time_ms);
+            self.delay.set_feedback(feedback);
+            self.delay.set_mix(mix);
         }
     }
 
-    pub fn process(&mut self, output: &mut [f32]) {
-        let len = output.len();
-        
-        // Process timeline engine
-        let mut timeline_buffer = vec![0.0; len];
-        self.timeline_engine.process_voices(&mut timeline_buffer);
-        self.timeline_engine.process_effects(&mut timeline_buffer);
-        
-        // Process live engine  
-        let mut live_buffer = vec![0.0; len];
-        self.live_engine.process_voices(&mut live_buffer);
-        self.live_engine.process_effects(&mut live_buffer);
-        
-        // Mix both engines with independent volumes
-        for i in 0..len {
-            output[i] = (timeline_buffer[i] * self.timeline_volume + 
-                        live_buffer[i] * self.live_volume) * self.master_volume;
+    // Starts closed when switched on, so stale detector state can't let a burst through
+    fn set_noisegate(&mut self, enabled: bool) {
+        if enabled && !self.noisegate_enabled {
+            self.noisegate.reset();
         }
+        self.noisegate_enabled = enabled;
     }
 
-    // Live performance note methods (use live_engine)
-    pub fn note_on(&mut self, midi_note: u8, velocity: f32) {
-        let mut voice_idx = None;
-        
-        for (i, voice) in self.live_engine.voices.iter().enumerate() {
-            if !voice.is_active() {
-                voice_idx = Some(i);
-                break;
-            }
+    fn set_gate(&mut self, enabled: bool, rate_division: u8) {
+        if enabled && !self.gate_enabled {
+            self.gate.restart();
         }
+        self.gate_enabled = enabled;
+        self.gate.set_division(rate_division);
+    }
 
-        if voice_idx.is_none() {
-            let mut oldest_releasing = None;
-            let mut oldest_releasing_age = 0.0;
-            
-            for (i, voice) in self.live_engine.voices.iter().enumerate() {
-                if voice.is_releasing() && voice.get_age() > oldest_releasing_age {
-                    oldest_releasing = Some(i);
-                    oldest_releasing_age = voice.get_age();
-                }
-            }
-            
-            if oldest_releasing.is_some() {
-                voice_idx = oldest_releasing;
-            } else {
-                let mut oldest_age = 0.0;
-                for (i, voice) in self.live_engine.voices.iter().enumerate() {
-                    if voice.get_age() > oldest_age {
-                        oldest_age = voice.get_age();
-                        voice_idx = Some(i);
-                    }
-                }
-            }
+    // Engine-wide side of a new note: LFO retrigger, filter envelope and keytracking
+    fn start_note(&mut self, midi_note: u8, velocity: f32) {
+        self.lfo.retrigger();
+        self.filter_env.gate_on();
+        self.last_velocity = velocity;
+        self.last_note_freq = self.tuning.note_to_freq(midi_note);
+        self.filter.set_note_frequency(self.last_note_freq);
+    }
+
+    fn trigger_note(&mut self, midi_note: u8, velocity: f32) {
+        let held = if self.retrigger_mode == RetriggerMode::Stack { None } else { self.find_held_voice(midi_note) };
+        // An ignored press leaves the engine untouched too: no LFO or filter envelope restart
+        if held.is_some() && self.retrigger_mode == RetriggerMode::Ignore {
+            return;
         }
+        self.start_note(midi_note, velocity);
 
-        if let Some(idx) = voice_idx {
-            let freq = midi_to_freq(midi_note);
-            self.live_engine.voices[idx].note_on(freq, velocity);
+        if let Some(idx) = held.or_else(|| self.allocate_voice()) {
+            self.voice_note_on(idx, midi_note, velocity);
         }
     }
 
-    pub fn note_off(&mut self, midi_note: u8) {
-        let freq = midi_to_freq(midi_note);
-        for voice in &mut self.live_engine.voices {
-            if (voice.get_frequency() - freq).abs() < 0.1 {
+    fn voice_note_on(&mut self, idx: usize, midi_note: u8, velocity: f32) {
+        // Looked up before the new note counts as held
+        let legato_from = self.legato_glide_source();
+        let freq = self.tuning.note_to_freq(midi_note);
+        self.voices[idx].note_on(midi_note, freq, velocity);
+        if self.glide_mode == GlideMode::Legato {
+            self.voices[idx].set_glide_start(legato_from);
+        }
+    }
+
+    // Pitch of the newest held note, which a legato note glides away from
+    fn legato_glide_source(&self) -> Option<f32> {
+        self.voices
+            .iter()
+            .filter(|voice| voice.is_held())
+            .min_by(|a, b| a.get_age().total_cmp(&b.get_age()))
+            .map(Voice::get_frequency)
+    }
+
+    fn release_note(&mut self, midi_note: u8) {
+        for voice in &mut self.voices {
+            if voice.is_held() && voice.get_note() == midi_note {
                 voice.note_off();
             }
         }
+        self.release_filter_env();
     }
 
-    // Timeline note methods (use timeline_engine)
-    pub fn timeline_note_on(&mut self, midi_note: u8, velocity: f32) {
+    // The filter envelope is shared, so it releases with the last held key
+    fn release_filter_env(&mut self) {
+        if !self.voices.iter().any(|voice| voice.is_held()) {
+            self.filter_env.gate_off();
+        }
+    }
+
+    // MPE notes skip the arpeggiator and always take their own voice, even when the
+    // same note number is already sounding. Returns the voice id, 0 if none is free
+    fn trigger_expressive_note(&mut self, midi_note: u8, velocity: f32) -> u32 {
+        self.start_note(midi_note, velocity);
+        let Some(idx) = self.allocate_voice() else {
+            return 0;
+        };
+        let id = self.next_voice_id;
+        self.next_voice_id = self.next_voice_id.wrapping_add(1).max(1);
+        self.voice_note_on(idx, midi_note, velocity);
+        self.voices[idx].set_id(id);
+        id
+    }
+
+    fn release_expressive_note(&mut self, id: u32) {
+        if let Some(voice) = self.expressive_voice(id) {
+            voice.note_off();
+        }
+        self.release_filter_env();
+    }
+
+    // None once the voice has finished or been taken by another note
+    fn expressive_voice(&mut self, id: u32) -> Option<&mut Voice> {
+        if id == 0 {
+            return None;
+        }
+        self.voices.iter_mut().find(|voice| voice.get_id() == id && voice.is_active())
+    }
+
+    fn set_mod_route(&mut self, source: u8, dest: u8, amount: f32) {
+        if let (Some(source), Some(dest)) = (ModSource::from_u8(source), ModDest::from_u8(dest)) {
+            self.mod_matrix.set_route(source, dest, amount);
+            self.reset_voice_modulation();
+        }
+    }
+
+    fn clear_mod_routes(&mut self) {
+        self.mod_matrix.clear();
+        self.reset_voice_modulation();
+    }
+
+    fn reset_voice_modulation(&mut self) {
+        for voice in &mut self.voices {
+            voice.clear_modulation();
+        }
+    }
+
+    // The wheel scales the depth of one destination; the others stay at full depth
+    fn apply_mod_wheel(&mut self) {
+        let scale_for = |dest: ModWheelDest| {
+            if self.mod_wheel_dest == dest { self.mod_wheel } else { 1.0 }
+        };
+        let vibrato_scale = scale_for(ModWheelDest::Vibrato);
+        self.lfo_filter_scale = scale_for(ModWheelDest::FilterLfo);
+        self.tremolo.set_depth_scale(scale_for(ModWheelDest::Tremolo));
+        for voice in &mut self.voices {
+            voice.set_vibrato_scale(vibrato_scale);
+        }
+    }
+
+    // Filter settings go to the global filter and every voice filter alike,
+    // so switching per-voice mode keeps the sound settings
+    fn configure_filters(&mut self, configure: impl Fn(&mut FilterChain)) {
+        configure(&mut self.filter);
+        for voice in &mut self.voices {
+            configure(voice.filter_mut());
+        }
+    }
+
+    fn set_filter_cutoff(&mut self, cutoff: f32) {
+        self.base_filter_cutoff = cutoff.clamp(20.0, 20000.0);
+        self.cutoff_smoother.set_target(self.base_filter_cutoff);
+        let reference = self.base_filter_cutoff;
+        self.configure_filters(|filter| filter.set_cutoff_reference(reference));
+    }
+
+    // Drawn at the cutoff knob, before keytracking and modulation; flat when the filter is off
+    fn filter_response(&self, frequencies: &[f32]) -> Vec<f32> {
+        if !self.filter_enabled {
+            return vec![0.0; frequencies.len()];
+        }
+        frequencies
+            .iter()
+            .map(|&frequency| self.filter.response_db(frequency, self.base_filter_cutoff))
+            .collect()
+    }
+
+    fn set_filter_keytrack(&mut self, amount: f32) {
+        self.filter_keytrack = amount.clamp(0.0, 2.0);
+        for voice in &mut self.voices {
+            voice.set_filter_keytrack(self.filter_keytrack);
+        }
+    }
+
+    fn set_filter_per_voice(&mut self, enabled: bool) {
+        if enabled != self.filter_per_voice {
+            self.configure_filters(|filter| filter.reset());
+            self.filter_per_voice = enabled;
+        }
+    }
+
+    // Every random source gets its own seed derived from the engine seed
+    fn set_random_seed(&mut self, seed: u64) {
+        self.lfo.set_seed(stream_seed(seed, LFO_STREAM));
+        self.tremolo.set_seed(stream_seed(seed, TREMOLO_STREAM));
+        self.flanger.set_seed(stream_seed(seed, FLANGER_STREAM));
+        self.autopan.set_seed(stream_seed(seed, AUTOPAN_STREAM));
+        self.delay.set_seed(stream_seed(seed, DELAY_STREAM));
+        self.arp.set_seed(stream_seed(seed, ARP_STREAM));
+        for (i, voice) in self.voices.iter_mut().enumerate() {
+            voice.set_seed(stream_seed(seed, VOICE_STREAMS + i as u64));
+        }
+    }
+
+    fn find_held_voice(&self, midi_note: u8) -> Option<usize> {
+        self.voices
+            .iter()
+            .position(|voice| voice.is_held() && voice.get_note() == midi_note)
+    }
+
+    fn allocate_voice(&self) -> Option<usize> {
         let mut voice_idx = None;
-        
-        for (i, voice) in self.timeline_engine.voices.iter().enumerate() {
+
+        for (i, voice) in self.voices.iter().enumerate() {
             if !voice.is_active() {
                 voice_idx = Some(i);
                 break;
@@ -190,19 +539,19 @@ impl AudioEngine {
         if voice_idx.is_none() {
             let mut oldest_releasing = None;
             let mut oldest_releasing_age = 0.0;
-            
-            for (i, voice) in self.timeline_engine.voices.iter().enumerate() {
+
+            for (i, voice) in self.voices.iter().enumerate() {
                 if voice.is_releasing() && voice.get_age() > oldest_releasing_age {
                     oldest_releasing = Some(i);
                     oldest_releasing_age = voice.get_age();
                 }
             }
-            
+
             if oldest_releasing.is_some() {
                 voice_idx = oldest_releasing;
             } else {
                 let mut oldest_age = 0.0;
-                for (i, voice) in self.timeline_engine.voices.iter().enumerate() {
+                for (i, voice) in self.voices.iter().enumerate() {
                     if voice.get_age() > oldest_age {
                         oldest_age = voice.get_age();
                         voice_idx = Some(i);
@@ -211,182 +560,1634 @@ impl AudioEngine {
             }
         }
 
-        if let Some(idx) = voice_idx {
-            let freq = midi_to_freq(midi_note);
-            self.timeline_engine.voices[idx].note_on(freq, velocity);
-        }
+        voice_idx
     }
 
-    pub fn timeline_note_off(&mut self, midi_note: u8) {
-        let freq = midi_to_freq(midi_note);
-        for voice in &mut self.timeline_engine.voices {
-            if (voice.get_frequency() - freq).abs() < 0.1 {
-                voice.note_off();
-            }
+    // Engine-level mod sources are computed once per sample up front so voices
+    // and the filter see the same values
+    fn update_mod_frames(&mut self, len: usize) {
+        self.mod_frames.clear();
+        for _ in 0..len {
+            let mut sources = [0.0; NUM_SOURCES];
+            // The LFO always runs so its position can be shown even when nothing is routed
+            self.lfo.process();
+            sources[ModSource::Lfo1 as usize] = self.lfo.peek_raw();
+            sources[ModSource::ModWheel as usize] = self.mod_wheel;
+            sources[ModSource::Velocity as usize] = self.last_velocity;
+            sources[ModSource::FilterEnv as usize] = self.filter_env.process();
+            sources[ModSource::Aftertouch as usize] = self.aftertouch.next();
+            self.mod_frames.push(sources);
         }
     }
 
-    // Stop all timeline notes (for loop restart)
-    pub fn stop_all_timeline_notes(&mut self) {
-        for voice in &mut self.timeline_engine.voices {
-            if voice.is_active() {
-                voice.note_off();
+    fn render(&mut self, buffer: &mut [f32]) {
+        self.render_chunks(buffer, None);
+    }
+
+    // The delay, reverb and stereo-only effects fan the mono voice mix out to both sides
+    fn render_stereo(&mut self, left: &mut [f32], right: &mut [f32]) {
+        self.render_chunks(left, Some(right));
+    }
+
+    // Renders in chunks split at arpeggiator/sequencer steps so notes land on the exact sample
+    fn render_chunks(&mut self, left: &mut [f32], mut right: Option<&mut [f32]>) {
+        let mut start = 0;
+        while start < left.len() {
+            let until = self.samples_until_note_event();
+            if until == 0 {
+                self.fire_note_events();
+                continue;
+            }
+            let end = start + until.min(left.len() - start);
+            self.process_voices(&mut left[start..end]);
+            if self.effects_bypassed {
+                self.run_bypassed_effects(&left[start..end], right.is_some());
+                if let Some(right) = right.as_deref_mut() {
+                    right[start..end].copy_from_slice(&left[start..end]);
+                }
+            } else {
+                match right.as_deref_mut() {
+                    Some(right) => self.process_effects_stereo(&mut left[start..end], &mut right[start..end]),
+                    None => self.process_effects(&mut left[start..end]),
+                }
             }
+            self.arp.advance(end - start);
+            self.sequencer.advance(end - start);
+            start = end;
         }
+        self.sanitize_filters();
     }
 
-    pub fn set_master_volume(&mut self, volume: f32) {
-        self.master_volume = volume.clamp(0.0, 1.0);
+    // Bypassed effects keep running on a copy of the dry mix, so their LFOs and
+    // tails are where they would be when the bypass is switched off
+    fn run_bypassed_effects(&mut self, dry: &[f32], stereo: bool) {
+        let mut left = dry.to_vec();
+        if stereo {
+            let mut right = dry.to_vec();
+            self.process_effects_stereo(&mut left, &mut right);
+        } else {
+            self.process_effects(&mut left);
+        }
     }
 
-    pub fn set_timeline_volume(&mut self, volume: f32) {
-        // Scale input 0-1 to output 0-0.3 (30% max to prevent clipping/distortion)
-        self.timeline_volume = (volume * 0.3).clamp(0.0, 0.3);
+    fn sanitize_filters(&mut self) {
+        let mut resets = self.filter.sanitize();
+        for voice in &mut self.voices {
+            resets += voice.filter_mut().sanitize();
+        }
+        self.filter_resets = self.filter_resets.wrapping_add(resets);
     }
 
-    pub fn set_live_volume(&mut self, volume: f32) {
-        // Scale input 0-1 to output 0-0.3 (30% max to prevent clipping/distortion)
-        self.live_volume = (volume * 0.3).clamp(0.0, 0.3);
+    fn samples_until_note_event(&self) -> usize {
+        let arp = if self.arp.is_enabled() { self.arp.samples_until_event() } else { usize::MAX };
+        arp.min(self.sequencer.samples_until_event())
     }
 
-    pub fn set_waveform(&mut self, waveform: u8) {
-        for voice in &mut self.live_engine.voices {
-            voice.set_waveform(waveform);
+    fn fire_note_events(&mut self) {
+        let mut events = std::mem::take(&mut self.note_events);
+        if self.arp.is_enabled() {
+            self.arp.fire(self.tempo_bpm, &mut events);
         }
-        // Timeline engine gets updated when pattern parameters are applied
+        self.sequencer.fire(self.tempo_bpm, &mut events);
+        for event in events.drain(..) {
+            match event {
+                NoteEvent::NoteOn(note, velocity) => self.trigger_note(note, velocity),
+                NoteEvent::NoteOff(note) => self.release_note(note),
+            }
+        }
+        self.note_events = events;
     }
 
-    pub fn set_adsr(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
-        for voice in &mut self.live_engine.voices {
-            voice.set_adsr(attack, decay, sustain, release);
+    fn process_voices(&mut self, output: &mut [f32]) {
+        self.update_mod_frames(output.len());
+        self.update_cutoff_frames();
+        // Per-voice filters sit inside the voices, so bypass just leaves them out
+        let cutoffs = if self.filter_enabled && self.filter_per_voice && !self.effects_bypassed {
+            Some(self.cutoff_frames.as_slice())
+        } else {
+            None
+        };
+        for (voice, &muted) in self.voices.iter_mut().zip(&self.voice_mutes) {
+            if !voice.is_active() {
+                continue;
+            }
+            if muted {
+                self.muted_scratch.clear();
+                self.muted_scratch.resize(output.len(), 0.0);
+                voice.process(&mut self.muted_scratch, &self.mod_frames, &self.mod_matrix, cutoffs);
+            } else {
+                voice.process(output, &self.mod_frames, &self.mod_matrix, cutoffs);
+            }
         }
     }
-    
-    // Apply synthesis settings to timeline engine (used when pattern parameters are applied)
-    pub fn set_timeline_waveform(&mut self, waveform: u8) {
-        for voice in &mut self.timeline_engine.voices {
-            voice.set_waveform(waveform);
+
+    fn set_voice_mute(&mut self, index: usize, muted: bool) {
+        if let Some(mute) = self.voice_mutes.get_mut(index) {
+            *mute = muted;
         }
     }
 
-    pub fn set_timeline_adsr(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
-        for voice in &mut self.timeline_engine.voices {
-            voice.set_adsr(attack, decay, sustain, release);
+    fn update_cutoff_frames(&mut self) {
+        self.cutoff_frames.clear();
+        if !self.filter_enabled {
+            return;
         }
-    }
+        // Per-voice filters add the matrix routes and keytracking themselves,
+        // with their own velocity and pitch
+        let cutoff_routes = !self.filter_per_voice && self.mod_matrix.has_routes_to(ModDest::Cutoff);
+        let keytrack = if self.filter_per_voice {
+            1.0
+        } else {
+            keytrack_ratio(self.last_note_freq, self.filter_keytrack)
+        };
 
+        for frame in &self.mod_frames {
+            let lfo_raw = frame[ModSource::Lfo1 as usize];
+            let pressure = frame[ModSource::Aftertouch as usize];
 
-    // Live engine LFO controls
-    pub fn set_lfo_rate(&mut self, rate: f32) {
-        self.live_engine.lfo.set_rate(rate);
+            let mut cutoff = self.cutoff_smoother.next() * keytrack;
+            if self.aftertouch_dest == AftertouchDest::Cutoff && pressure > 0.0 {
+                cutoff *= (pressure * AFTERTOUCH_OCTAVES).exp2();
+            }
+            if self.lfo_to_filter {
+                // Not a matrix route: the polarity modes, the legacy multiplier and the mod
+                // wheel scaling have no route equivalent. Bipolar is an LFO1 -> Cutoff route
+                // of lfo_filter_octaves. Filter sweeps use their own depth in octaves rather
+                // than the LFO depth, so both halves of the cycle sound alike
+                let octaves = self.lfo_filter_octaves * self.lfo_filter_scale;
+                cutoff *= match self.lfo_polarity {
+                    LfoPolarity::Legacy => 1.0 + lfo_raw * self.lfo.get_depth() * self.lfo_filter_scale,
+                    LfoPolarity::Bipolar => (lfo_raw * octaves).exp2(),
+                    LfoPolarity::Unipolar => ((lfo_raw + 1.0) * 0.5 * octaves).exp2(),
+                };
+            }
+            if cutoff_routes {
+                cutoff *= self.mod_matrix.sum(ModDest::Cutoff, frame).exp2();
+            }
+            if self.filter_per_voice && self.env_follow_octaves != 0.0 {
+                // The voice sum isn't known yet: use the level at the end of the last block
+                cutoff *= self.env_follow_ratio(self.env_follower.get_level());
+            }
+            self.cutoff_frames.push(cutoff);
+        }
     }
 
-    pub fn set_lfo_depth(&mut self, depth: f32) {
-        self.live_engine.lfo.set_depth(depth);
+    fn process_effects(&mut self, buffer: &mut [f32]) {
+        let frames = std::mem::take(&mut self.mod_frames);
+        for (i, (out, frame)) in buffer.iter_mut().zip(&frames).enumerate() {
+            let mut sample = self.process_inserts(*out, i, frame);
+            if self.delay_enabled {
+                sample = blend(sample, self.delay.process(sample), self.delay_level);
+            }
+            if self.ensemble_enabled {
+                sample = self.ensemble.process(sample);
+            }
+            if self.reverb_enabled {
+                sample = blend(sample, self.reverb.process(sample), self.reverb_level);
+            }
+            if self.convolution_enabled {
+                sample = self.convolution.process(sample);
+            }
+            if self.noisegate_enabled {
+                sample = self.noisegate.process(sample);
+            }
+            *out = sample;
+        }
+        self.mod_frames = frames;
     }
 
-    pub fn set_lfo_waveform(&mut self, waveform: u8) {
-        self.live_engine.lfo.set_waveform(waveform);
+    // Same chain with the delay and reverb in stereo, followed by the stereo-only effects.
+    // With matching left/right delay settings the sides only differ through the ensemble
+    // and the reverb's second tank
+    fn process_effects_stereo(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let frames = std::mem::take(&mut self.mod_frames);
+        for (i, ((out_l, out_r), frame)) in left.iter_mut().zip(right.iter_mut()).zip(&frames).enumerate() {
+            let sample = self.process_inserts(*out_l, i, frame);
+            let (mut l, mut r) = (sample, sample);
+            if self.delay_enabled {
+                let (wet_l, wet_r) = self.delay.process_stereo(sample);
+                l = blend(sample, wet_l, self.delay_level);
+                r = blend(sample, wet_r, self.delay_level);
+            }
+            if self.ensemble_enabled {
+                (l, r) = self.ensemble.process_stereo(l, r);
+            }
+            if self.reverb_enabled {
+                let (wet_l, wet_r) = self.reverb.process_stereo(l, r);
+                l = blend(l, wet_l, self.reverb_level);
+                r = blend(r, wet_r, self.reverb_level);
+            }
+            if self.convolution_enabled {
+                (l, r) = self.convolution.process_stereo(l, r);
+            }
+            if self.noisegate_enabled {
+                (l, r) = self.noisegate.process_stereo(l, r);
+            }
+            if self.autopan_enabled {
+                (l, r) = self.autopan.process(l, r);
+            }
+            *out_l = l;
+            *out_r = r;
+        }
+        self.mod_frames = frames;
     }
 
-    // Timeline engine LFO controls
-    pub fn set_timeline_lfo_rate(&mut self, rate: f32) {
-        self.timeline_engine.lfo.set_rate(rate);
+    // Cutoff multiplier from the follower level, capped at full scale
+    fn env_follow_ratio(&self, level: f32) -> f32 {
+        (level.min(1.0) * self.env_follow_octaves).exp2()
     }
 
-    pub fn set_timeline_lfo_depth(&mut self, depth: f32) {
-        self.timeline_engine.lfo.set_depth(depth);
-    }
+    // Everything ahead of the delay: aftertouch gain, the filter and the mono effects
+    fn process_inserts(&mut self, input: f32, i: usize, frame: &ModSources) -> f32 {
+        let mut sample = input;
+        let follow = if self.env_follow_octaves != 0.0 {
+            let level = self.env_follower.process(input);
+            self.env_follow_ratio(level)
+        } else {
+            1.0
+        };
+        let pressure = frame[ModSource::Aftertouch as usize];
 
-    pub fn set_timeline_lfo_waveform(&mut self, waveform: u8) {
-        self.timeline_engine.lfo.set_waveform(waveform);
-    }
+        if self.aftertouch_dest == AftertouchDest::Gain {
+            // Swell up to +6 dB at full pressure
+            sample *= 1.0 + pressure;
+        }
 
+        // The nonlinear stages, run at the oversampled rate
+        let global_filter = self.filter_enabled && !self.filter_per_voice;
+        if global_filter || self.ringmod_enabled {
+            // Only filled while the filter is on
+            let cutoff = if global_filter { self.cutoff_frames[i] * follow } else { 0.0 };
+            let (filter, ringmod, ringmod_enabled) = (&mut self.filter, &mut self.ringmod, self.ringmod_enabled);
+            sample = self.oversampler.process(sample, |mut x| {
+                if global_filter {
+                    x = filter.process(x, cutoff);
+                }
+                if ringmod_enabled {
+                    x = ringmod.process(x);
+                }
+                x
+            });
+        }
 
-    // Live engine detune
-    pub fn set_detune(&mut self, cents: f32) {
-        self.live_engine.detune_cents = cents;
-        for voice in &mut self.live_engine.voices {
-            voice.set_detune(cents);
+        if self.formant_enabled {
+            sample = self.formant.process(sample);
         }
+        if self.pitchshift_enabled {
+            sample = self.pitchshift.process(sample);
+        }
+        if self.freqshift_enabled {
+            sample = self.freqshift.process(sample);
+        }
+        if self.flanger_enabled {
+            sample = blend(sample, self.flanger.process(sample), self.flanger_level);
+        }
+        if self.tremolo_enabled {
+            sample = blend(sample, self.tremolo.process(sample), self.tremolo_level);
+        }
+        if self.gate_enabled {
+            sample = self.gate.process(sample);
+        }
+        sample
     }
+}
 
-    pub fn set_glide_time(&mut self, time_ms: f32) {
-        for voice in &mut self.live_engine.voices {
+// A parameter change scheduled at a sample offset within a process block
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct AutomationEvent {
+    pub sample_offset: u32,
+    pub param_id: u8,
+    pub value: f32,
+}
+
+#[wasm_bindgen]
+impl AutomationEvent {
+    #[wasm_bindgen(constructor)]
+    pub fn new(sample_offset: u32, param_id: u8, value: f32) -> AutomationEvent {
+        AutomationEvent {
+            sample_offset,
+            param_id,
+            value,
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct AudioEngine {
+    timeline_engine: Engine,
+    live_engine: Engine,
+    timeline_volume: f32,
+    live_volume: f32,
+    master_volume: Smoother,
+    duck: Duck, // Sidechain-style dip on the master bus, keyed by played notes
+    duck_enabled: bool,
+    effects_bypassed: bool,
+    dc_blockers: [DcBlocker; 2], // Left (and mono), right
+    dc_blocker_enabled: bool,
+    widener: Widener, // Stereo output only
+    scope_buffer: Vec<f32>, // Copy of the last processed block for the oscilloscope
+    scope_trigger: bool,
+    spectrum: Spectrum,
+    sample_rate: f32,
+}
+
+impl Default for AudioEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl AudioEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> AudioEngine {
+        AudioEngine::with_config(SAMPLE_RATE, MAX_VOICES_PER_ENGINE)
+    }
+
+    // Run at the AudioContext rate (e.g. 44100) so pitch, times and delays stay correct
+    pub fn with_sample_rate(sample_rate: f32) -> AudioEngine {
+        AudioEngine::with_config(sample_rate, MAX_VOICES_PER_ENGINE)
+    }
+
+    // Polyphony of each engine (1-64): fewer for low-power devices, more for big pads.
+    // Past the limit the usual stealing picks the oldest releasing, then oldest voice
+    pub fn with_max_voices(max: usize) -> AudioEngine {
+        AudioEngine::with_config(SAMPLE_RATE, max)
+    }
+
+    // Sample rate (8000-192000 Hz) and voices per engine together
+    pub fn with_config(sample_rate: f32, max_voices: usize) -> AudioEngine {
+        let sample_rate = if sample_rate.is_finite() { sample_rate.clamp(8000.0, 192000.0) } else { SAMPLE_RATE };
+        let max_voices = max_voices.clamp(1, VOICE_LIMIT);
+        AudioEngine {
+            timeline_engine: Engine::new(sample_rate, max_voices),
+            live_engine: Engine::new(sample_rate, max_voices),
+            timeline_volume: DEFAULT_ENGINE_VOLUME,
+            live_volume: DEFAULT_ENGINE_VOLUME,
+            master_volume: Smoother::new(sample_rate, 1.0, DEFAULT_SMOOTHING_MS), // Master is now just a final gain stage
+            duck: Duck::new(sample_rate),
+            duck_enabled: false,
+            effects_bypassed: false,
+            dc_blockers: [DcBlocker::new(sample_rate), DcBlocker::new(sample_rate)],
+            dc_blocker_enabled: true,
+            widener: Widener::new(),
+            scope_buffer: Vec::new(),
+            scope_trigger: false,
+            spectrum: Spectrum::new(),
+            sample_rate,
+        }
+    }
+
+    pub fn process(&mut self, output: &mut [f32]) {
+        self.mix(output);
+        self.capture_scope(output);
+    }
+
+    fn mix(&mut self, output: &mut [f32]) {
+        let len = output.len();
+        
+        // Process timeline engine
+        let mut timeline_buffer = vec![0.0; len];
+        self.timeline_engine.render(&mut timeline_buffer);
+        
+        // Process live engine  
+        let mut live_buffer = vec![0.0; len];
+        self.live_engine.render(&mut live_buffer);
+        
+        // Mix both engines with independent volumes
+        for i in 0..len {
+            let master = self.master_volume.next() * self.duck_gain();
+            let sum = timeline_buffer[i] * self.timeline_volume + live_buffer[i] * self.live_volume;
+            output[i] = self.block_dc(0, sum) * master;
+        }
+    }
+
+    // Like the effects, it keeps running but is left out while bypassed
+    fn block_dc(&mut self, channel: usize, sample: f32) -> f32 {
+        if !self.dc_blocker_enabled {
+            return sample;
+        }
+        let blocked = self.dc_blockers[channel].process(sample);
+        if self.effects_bypassed { sample } else { blocked }
+    }
+
+    fn widen(&self, left: f32, right: f32) -> (f32, f32) {
+        if self.effects_bypassed {
+            return (left, right);
+        }
+        self.widener.process(left, right)
+    }
+
+    fn duck_gain(&mut self) -> f32 {
+        if !self.duck_enabled {
+            return 1.0;
+        }
+        // Keeps moving while bypassed, like the engine effects
+        let gain = self.duck.next();
+        if self.effects_bypassed { 1.0 } else { gain }
+    }
+
+    // Stereo output; the mono process() skips the stereo-only effects (autopan, width)
+    pub fn process_stereo(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let len = left.len().min(right.len());
+        let (left, right) = (&mut left[..len], &mut right[..len]);
+
+        let mut timeline_left = vec![0.0; len];
+        let mut timeline_right = vec![0.0; len];
+        self.timeline_engine.render_stereo(&mut timeline_left, &mut timeline_right);
+
+        let mut live_left = vec![0.0; len];
+        let mut live_right = vec![0.0; len];
+        self.live_engine.render_stereo(&mut live_left, &mut live_right);
+
+        for i in 0..len {
+            let master = self.master_volume.next() * self.duck_gain();
+            let sum_left = timeline_left[i] * self.timeline_volume + live_left[i] * self.live_volume;
+            let sum_right = timeline_right[i] * self.timeline_volume + live_right[i] * self.live_volume;
+            let (wide_left, wide_right) = self.widen(sum_left, sum_right);
+            left[i] = self.block_dc(0, wide_left) * master;
+            right[i] = self.block_dc(1, wide_right) * master;
+        }
+
+        // The scope and spectrum follow the mid signal
+        let mid: Vec<f32> = left.iter().zip(right.iter()).map(|(l, r)| 0.5 * (l + r)).collect();
+        self.capture_scope(&mid);
+    }
+
+    // Offline rendering: events are applied at their sample offset inside the block
+    // Param ids: 0 = master volume, 1 = live volume, 2 = timeline volume,
+    // 3 = filter cutoff, 4 = filter resonance, 5 = LFO rate, 6 = LFO depth,
+    // 7 = detune, 8 = glide time, 9 = master tune
+    pub fn process_with_automation(&mut self, output: &mut [f32], events: Vec<AutomationEvent>) {
+        let mut events = events;
+        events.sort_by_key(|event| event.sample_offset);
+
+        let mut start = 0;
+        for event in &events {
+            let offset = (event.sample_offset as usize).min(output.len());
+            if offset > start {
+                self.mix(&mut output[start..offset]);
+                start = offset;
+            }
+            self.apply_automation(event.param_id, event.value);
+        }
+
+        if start < output.len() {
+            self.mix(&mut output[start..]);
+        }
+        self.capture_scope(output);
+    }
+
+    fn apply_automation(&mut self, param_id: u8, value: f32) {
+        match param_id {
+            0 => self.set_master_volume(value),
+            1 => self.set_live_volume(value),
+            2 => self.set_timeline_volume(value),
+            3 => self.set_filter_cutoff(value),
+            4 => self.set_filter_resonance(value),
+            5 => self.set_lfo_rate(value),
+            6 => self.set_lfo_depth(value),
+            7 => self.set_detune(value),
+            8 => self.set_glide_time(value),
+            9 => self.set_master_tune(value),
+            _ => {}
+        }
+    }
+
+    // Live performance note methods (use live_engine)
+    pub fn note_on(&mut self, midi_note: u8, velocity: f32) {
+        self.live_engine.note_on(midi_note, velocity);
+        self.trigger_duck();
+    }
+
+    pub fn note_off(&mut self, midi_note: u8) {
+        self.live_engine.note_off(midi_note);
+    }
+
+    // MPE: every note gets its own voice and id for per-note bend, pressure and slide.
+    // Returns 0 when no voice could be started (ids that finished are ignored)
+    pub fn note_on_mpe(&mut self, midi_note: u8, velocity: f32) -> u32 {
+        let id = self.live_engine.trigger_expressive_note(midi_note, velocity);
+        self.trigger_duck();
+        id
+    }
+
+    pub fn note_off_mpe(&mut self, id: u32) {
+        self.live_engine.release_expressive_note(id);
+    }
+
+    // Pitch bend of one MPE note, +-48 semitones
+    pub fn set_voice_bend(&mut self, id: u32, semitones: f32) {
+        if let Some(voice) = self.live_engine.expressive_voice(id) {
+            voice.set_bend(semitones);
+        }
+    }
+
+    // Pressure and slide (0-1) are mod sources 6 and 7, routed with set_mod_route,
+    // e.g. to cutoff (per note with per-voice filters) or amplitude
+    pub fn set_voice_pressure(&mut self, id: u32, pressure: f32) {
+        if let Some(voice) = self.live_engine.expressive_voice(id) {
+            voice.set_pressure(pressure);
+        }
+    }
+
+    pub fn set_voice_slide(&mut self, id: u32, slide: f32) {
+        if let Some(voice) = self.live_engine.expressive_voice(id) {
+            voice.set_slide(slide);
+        }
+    }
+
+    // Timeline note methods (use timeline_engine)
+    pub fn timeline_note_on(&mut self, midi_note: u8, velocity: f32) {
+        self.timeline_engine.note_on(midi_note, velocity);
+        self.trigger_duck();
+    }
+
+    fn trigger_duck(&mut self) {
+        if self.duck_enabled {
+            self.duck.trigger();
+        }
+    }
+
+    pub fn timeline_note_off(&mut self, midi_note: u8) {
+        self.timeline_engine.note_off(midi_note);
+    }
+
+    // Retrigger behavior when a note that is already held is pressed again
+    // 0 = stack a new voice, 1 = retrigger the existing voice, 2 = ignore
+    pub fn set_retrigger_mode(&mut self, mode: u8) {
+        self.live_engine.retrigger_mode = RetriggerMode::from_u8(mode);
+    }
+
+    pub fn set_timeline_retrigger_mode(&mut self, mode: u8) {
+        self.timeline_engine.retrigger_mode = RetriggerMode::from_u8(mode);
+    }
+
+    // 0 = glide into every note from the voice's last pitch, 1 = legato only: a note
+    // glides from the newest held note and starts at pitch when nothing is held
+    pub fn set_glide_mode(&mut self, mode: u8) {
+        self.live_engine.glide_mode = GlideMode::from_u8(mode);
+    }
+
+    pub fn set_timeline_glide_mode(&mut self, mode: u8) {
+        self.timeline_engine.glide_mode = GlideMode::from_u8(mode);
+    }
+
+    // Stop all timeline notes (for loop restart)
+    pub fn stop_all_timeline_notes(&mut self) {
+        for voice in &mut self.timeline_engine.voices {
+            if voice.is_active() {
+                voice.note_off();
+            }
+        }
+    }
+
+    // Tempo for everything synced to the beat, applied to both engines
+    pub fn set_tempo(&mut self, bpm: f32) {
+        let bpm = bpm.clamp(20.0, 300.0);
+        self.live_engine.set_tempo(bpm);
+        self.timeline_engine.set_tempo(bpm);
+    }
+
+    // Arpeggiator on the live engine: held keys are played one at a time
+    // Mode: 0 = up, 1 = down, 2 = up/down, 3 = random
+    // Rate division: 0 = 1/1, 1 = 1/2, 2 = 1/4, 3 = 1/8, 4 = 1/16, 5 = 1/32,
+    // 6-8 = dotted 1/4, 1/8, 1/16, 9-11 = triplet 1/4, 1/8, 1/16
+    pub fn set_arp(&mut self, enabled: bool, mode: u8, rate_division: u8) {
+        self.live_engine.set_arp(enabled, mode, rate_division);
+    }
+
+    // Step sequencer on the live engine; each step is a MIDI note or -1 for a rest
+    pub fn load_sequence(&mut self, steps: &[i8]) {
+        self.live_engine.sequencer.load(steps);
+    }
+
+    // Rate division uses the same values as set_arp
+    pub fn set_sequencer(&mut self, enabled: bool, rate_division: u8) {
+        self.live_engine.sequencer.set_enabled(enabled);
+        self.live_engine.sequencer.set_division(rate_division);
+    }
+
+    // Makes every random source reproducible for offline renders
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.live_engine.set_random_seed(stream_seed(seed, LIVE_ENGINE_STREAM));
+        self.timeline_engine.set_random_seed(stream_seed(seed, TIMELINE_ENGINE_STREAM));
+    }
+
+    // MIDI CC1, 0.0-1.0, scales the depth of the chosen destination on the live engine
+    pub fn set_mod_wheel(&mut self, value: f32) {
+        self.live_engine.mod_wheel = value.clamp(0.0, 1.0);
+        self.live_engine.apply_mod_wheel();
+    }
+
+    // 0 = none, 1 = voice vibrato depth, 2 = LFO-to-filter depth, 3 = tremolo depth
+    pub fn set_mod_wheel_dest(&mut self, dest: u8) {
+        self.live_engine.mod_wheel_dest = ModWheelDest::from_u8(dest);
+        self.live_engine.apply_mod_wheel();
+    }
+
+    // Channel (mono) aftertouch, 0.0-1.0; affects every sounding voice of the live engine equally
+    pub fn set_aftertouch(&mut self, pressure: f32) {
+        self.live_engine.aftertouch.set_target(pressure.clamp(0.0, 1.0));
+    }
+
+    // 0 = filter cutoff (up to +3 octaves), 1 = gain swell (up to +6 dB)
+    pub fn set_aftertouch_dest(&mut self, dest: u8) {
+        self.live_engine.aftertouch_dest = match dest {
+            1 => AftertouchDest::Gain,
+            _ => AftertouchDest::Cutoff,
+        };
+    }
+
+    // Modulation matrix. Sources: 0 = LFO1, 1 = LFO2 (voice vibrato LFO), 2 = mod wheel,
+    // 3 = velocity, 4 = filter envelope, 5 = aftertouch, 6 = MPE pressure, 7 = MPE slide.
+    // Destinations: 0 = pitch (semitones), 1 = cutoff (octaves), 2 = amplitude,
+    // 3 = pulse width, 4 = FM amount. Amount 0 removes the route.
+    pub fn set_mod_route(&mut self, source: u8, dest: u8, amount: f32) {
+        self.live_engine.set_mod_route(source, dest, amount);
+    }
+
+    pub fn clear_mod_routes(&mut self) {
+        self.live_engine.clear_mod_routes();
+    }
+
+    pub fn set_timeline_mod_route(&mut self, source: u8, dest: u8, amount: f32) {
+        self.timeline_engine.set_mod_route(source, dest, amount);
+    }
+
+    pub fn clear_timeline_mod_routes(&mut self) {
+        self.timeline_engine.clear_mod_routes();
+    }
+
+    // Shared envelope retriggered on every note_on, a mod matrix source
+    pub fn set_filter_envelope(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
+        self.live_engine.filter_env.set_adsr(attack, decay, sustain, release);
+    }
+
+    pub fn set_timeline_filter_envelope(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
+        self.timeline_engine.filter_env.set_adsr(attack, decay, sustain, release);
+    }
+
+    // Global tuning applies to both engines so the whole instrument shifts together
+    pub fn set_master_tune(&mut self, cents: f32) {
+        self.live_engine.tuning.set_master_tune(cents);
+        self.timeline_engine.tuning.set_master_tune(cents);
+    }
+
+    pub fn set_transpose(&mut self, semitones: i8) {
+        self.live_engine.tuning.set_transpose(semitones);
+        self.timeline_engine.tuning.set_transpose(semitones);
+    }
+
+    // 12 cent offsets from equal temperament, one per pitch class starting at C
+    pub fn set_tuning_table(&mut self, cents_per_note: &[f32]) {
+        self.live_engine.tuning.set_pitch_class_cents(cents_per_note);
+        self.timeline_engine.tuning.set_pitch_class_cents(cents_per_note);
+    }
+
+    // 128 absolute frequencies in Hz, one per MIDI note
+    pub fn set_tuning_frequencies(&mut self, frequencies: &[f32]) {
+        self.live_engine.tuning.set_note_frequencies(frequencies);
+        self.timeline_engine.tuning.set_note_frequencies(frequencies);
+    }
+
+    // Back to 12-TET
+    pub fn reset_tuning(&mut self) {
+        self.live_engine.tuning.reset();
+        self.timeline_engine.tuning.reset();
+    }
+
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume.set_target(volume.clamp(0.0, 1.0));
+    }
+
+    // A/B against the raw voice mix: outputs the voices times master volume only,
+    // skipping the filter and every effect. The effects keep processing in the
+    // background, so switching back resumes their tails and LFOs rather than stale state.
+    pub fn set_effects_bypass(&mut self, bypassed: bool) {
+        self.effects_bypassed = bypassed;
+        self.live_engine.effects_bypassed = bypassed;
+        self.timeline_engine.effects_bypassed = bypassed;
+    }
+
+    // 5 Hz highpass on the summed output, before master volume, so offsets from
+    // asymmetric FM, flanger feedback or long reverbs don't eat headroom. On by default
+    pub fn set_dc_blocker(&mut self, enabled: bool) {
+        if enabled && !self.dc_blocker_enabled {
+            for blocker in &mut self.dc_blockers {
+                blocker.reset();
+            }
+        }
+        self.dc_blocker_enabled = enabled;
+    }
+
+    // Stereo width of the summed output: 0 = mono, 1 = unchanged, up to 2 = wider
+    pub fn set_width(&mut self, width: f32) {
+        self.widener.set_width(width);
+    }
+
+    pub fn get_width(&self) -> f32 {
+        self.widener.get_width()
+    }
+
+    // Pumping effect: each note_on dips the master gain by amount (0-1),
+    // recovering over release_ms (10-2000), applied after master volume
+    pub fn set_sidechain_duck(&mut self, enabled: bool, amount: f32, release_ms: f32) {
+        if !enabled {
+            self.duck.reset();
+        }
+        self.duck_enabled = enabled;
+        self.duck.set_amount(amount);
+        self.duck.set_release(release_ms);
+    }
+
+    // Ramp time for master volume and filter cutoff changes
+    pub fn set_smoothing_time(&mut self, ms: f32) {
+        self.master_volume.set_time(ms);
+        self.live_engine.cutoff_smoother.set_time(ms);
+        self.timeline_engine.cutoff_smoother.set_time(ms);
+        self.live_engine.configure_filters(|filter| filter.set_smoothing_time(ms));
+        self.timeline_engine.configure_filters(|filter| filter.set_smoothing_time(ms));
+    }
+
+    pub fn set_timeline_volume(&mut self, volume: f32) {
+        // Scale input 0-1 to output 0-0.3 (30% max to prevent clipping/distortion)
+        self.timeline_volume = (volume * 0.3).clamp(0.0, 0.3);
+    }
+
+    pub fn set_live_volume(&mut self, volume: f32) {
+        // Scale input 0-1 to output 0-0.3 (30% max to prevent clipping/distortion)
+        self.live_volume = (volume * 0.3).clamp(0.0, 0.3);
+    }
+
+    pub fn set_waveform(&mut self, waveform: u8) {
+        for voice in &mut self.live_engine.voices {
+            voice.set_waveform(waveform);
+        }
+        // Timeline engine gets updated when pattern parameters are applied
+    }
+
+    pub fn set_adsr(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
+        for voice in &mut self.live_engine.voices {
+            voice.set_adsr(attack, decay, sustain, release);
+        }
+    }
+
+    // ADSR with a silent delay before the attack and a hold at full level after it,
+    // all times in seconds like set_adsr. set_adsr leaves delay and hold as they are
+    pub fn set_dahdsr(&mut self, delay: f32, attack: f32, hold: f32, decay: f32, sustain: f32, release: f32) {
+        for voice in &mut self.live_engine.voices {
+            voice.set_adsr(attack, decay, sustain, release);
+            voice.set_delay_hold(delay * 1000.0, hold * 1000.0);
+        }
+    }
+    
+    // Apply synthesis settings to timeline engine (used when pattern parameters are applied)
+    pub fn set_timeline_waveform(&mut self, waveform: u8) {
+        for voice in &mut self.timeline_engine.voices {
+            voice.set_waveform(waveform);
+        }
+    }
+
+    pub fn set_timeline_adsr(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
+        for voice in &mut self.timeline_engine.voices {
+            voice.set_adsr(attack, decay, sustain, release);
+        }
+    }
+
+    pub fn set_timeline_dahdsr(&mut self, delay: f32, attack: f32, hold: f32, decay: f32, sustain: f32, release: f32) {
+        for voice in &mut self.timeline_engine.voices {
+            voice.set_adsr(attack, decay, sustain, release);
+            voice.set_delay_hold(delay * 1000.0, hold * 1000.0);
+        }
+    }
+
+
+    // Live engine LFO controls
+    pub fn set_lfo_rate(&mut self, rate: f32) {
+        self.live_engine.lfo.set_rate(rate);
+    }
+
+    pub fn set_lfo_depth(&mut self, depth: f32) {
+        self.live_engine.lfo.set_depth(depth);
+    }
+
+    pub fn set_lfo_waveform(&mut self, waveform: u8) {
+        self.live_engine.lfo.set_waveform(waveform);
+    }
+
+    // Pure reads for UI animation, updated once per processed sample
+    pub fn get_lfo_value(&self) -> f32 {
+        self.live_engine.lfo.peek()
+    }
+
+    pub fn get_lfo_phase(&self) -> f32 {
+        self.live_engine.lfo.get_phase()
+    }
+
+    pub fn set_lfo_one_shot(&mut self, enabled: bool) {
+        self.live_engine.lfo.set_one_shot(enabled);
+    }
+
+    pub fn set_lfo_phase(&mut self, offset: f32) {
+        self.live_engine.lfo.set_phase_offset(offset);
+    }
+
+    // Timeline engine LFO controls
+    pub fn set_timeline_lfo_rate(&mut self, rate: f32) {
+        self.timeline_engine.lfo.set_rate(rate);
+    }
+
+    pub fn set_timeline_lfo_depth(&mut self, depth: f32) {
+        self.timeline_engine.lfo.set_depth(depth);
+    }
+
+    pub fn set_timeline_lfo_waveform(&mut self, waveform: u8) {
+        self.timeline_engine.lfo.set_waveform(waveform);
+    }
+
+    pub fn get_timeline_lfo_value(&self) -> f32 {
+        self.timeline_engine.lfo.peek()
+    }
+
+    pub fn get_timeline_lfo_phase(&self) -> f32 {
+        self.timeline_engine.lfo.get_phase()
+    }
+
+    pub fn set_timeline_lfo_one_shot(&mut self, enabled: bool) {
+        self.timeline_engine.lfo.set_one_shot(enabled);
+    }
+
+    pub fn set_timeline_lfo_phase(&mut self, offset: f32) {
+        self.timeline_engine.lfo.set_phase_offset(offset);
+    }
+
+
+    // Live engine detune
+    pub fn set_detune(&mut self, cents: f32) {
+        self.live_engine.detune_cents = cents;
+        for voice in &mut self.live_engine.voices {
+            voice.set_detune(cents);
+        }
+    }
+
+    // 0 = ADSR, 1 = one-shot AD for drums and plucks: after the attack the decay
+    // runs to 0 and the voice frees itself, held or not (sustain and release unused)
+    pub fn set_envelope_mode(&mut self, mode: u8) {
+        for voice in &mut self.live_engine.voices {
+            voice.set_envelope_mode(mode);
+        }
+    }
+
+    // Soft notes play low_wave and hard ones high_wave, crossfaded by velocity.
+    // set_waveform goes back to a single waveform
+    pub fn set_velocity_layer(&mut self, low_wave: u8, high_wave: u8) {
+        for voice in &mut self.live_engine.voices {
+            voice.set_velocity_layer(low_wave, high_wave);
+        }
+    }
+
+    // Harder hits attack faster: attack * (1 - amount * velocity), taken at note on
+    pub fn set_velocity_to_attack(&mut self, amount: f32) {
+        for voice in &mut self.live_engine.voices {
+            voice.set_velocity_to_attack(amount);
+        }
+    }
+
+    pub fn set_glide_time(&mut self, time_ms: f32) {
+        for voice in &mut self.live_engine.voices {
             voice.set_glide_time(time_ms);
         }
     }
 
-    // Timeline engine detune
-    pub fn set_timeline_detune(&mut self, cents: f32) {
-        self.timeline_engine.detune_cents = cents;
-        for voice in &mut self.timeline_engine.voices {
-            voice.set_detune(cents);
-        }
+    // FM waveform: modulator frequency as a multiple of the carrier, and modulation index
+    pub fn set_fm_ratio(&mut self, ratio: f32) {
+        for voice in &mut self.live_engine.voices {
+            let (_, index) = voice.get_fm();
+            voice.set_fm(ratio, index);
+        }
+    }
+
+    pub fn set_fm_index(&mut self, index: f32) {
+        for voice in &mut self.live_engine.voices {
+            let (ratio, _) = voice.get_fm();
+            voice.set_fm(ratio, index);
+        }
+    }
+
+    // Per-voice vibrato so chord notes don't wobble in lockstep (depth 0 disables it)
+    pub fn set_voice_vibrato(&mut self, rate_hz: f32, depth_cents: f32, fade_ms: f32) {
+        for voice in &mut self.live_engine.voices {
+            voice.set_vibrato(rate_hz, depth_cents, fade_ms);
+        }
+    }
+
+    // Timeline engine detune
+    pub fn set_timeline_detune(&mut self, cents: f32) {
+        self.timeline_engine.detune_cents = cents;
+        for voice in &mut self.timeline_engine.voices {
+            voice.set_detune(cents);
+        }
+    }
+
+    pub fn set_timeline_envelope_mode(&mut self, mode: u8) {
+        for voice in &mut self.timeline_engine.voices {
+            voice.set_envelope_mode(mode);
+        }
+    }
+
+    pub fn set_timeline_velocity_layer(&mut self, low_wave: u8, high_wave: u8) {
+        for voice in &mut self.timeline_engine.voices {
+            voice.set_velocity_layer(low_wave, high_wave);
+        }
+    }
+
+    pub fn set_timeline_velocity_to_attack(&mut self, amount: f32) {
+        for voice in &mut self.timeline_engine.voices {
+            voice.set_velocity_to_attack(amount);
+        }
+    }
+
+    pub fn set_timeline_glide_time(&mut self, time_ms: f32) {
+        for voice in &mut self.timeline_engine.voices {
+            voice.set_glide_time(time_ms);
+        }
+    }
+
+    pub fn set_timeline_fm_ratio(&mut self, ratio: f32) {
+        for voice in &mut self.timeline_engine.voices {
+            let (_, index) = voice.get_fm();
+            voice.set_fm(ratio, index);
+        }
+    }
+
+    pub fn set_timeline_fm_index(&mut self, index: f32) {
+        for voice in &mut self.timeline_engine.voices {
+            let (ratio, _) = voice.get_fm();
+            voice.set_fm(ratio, index);
+        }
+    }
+
+    pub fn set_timeline_voice_vibrato(&mut self, rate_hz: f32, depth_cents: f32, fade_ms: f32) {
+        for voice in &mut self.timeline_engine.voices {
+            voice.set_vibrato(rate_hz, depth_cents, fade_ms);
+        }
+    }
+
+    // ==== LIVE ENGINE FILTER CONTROL ====
+
+    pub fn set_filter_enabled(&mut self, enabled: bool) {
+        self.live_engine.filter_enabled = enabled;
+    }
+
+    pub fn set_filter_cutoff(&mut self, cutoff: f32) {
+        self.live_engine.set_filter_cutoff(cutoff);
+    }
+
+    // Cutoff as a 0-1 knob position on the curve 20 Hz * 1000^x (0.5 is ~632 Hz)
+    pub fn set_filter_cutoff_normalized(&mut self, x: f32) {
+        self.set_filter_cutoff(normalized_to_cutoff(x));
+    }
+
+    pub fn get_filter_cutoff_normalized(&self) -> f32 {
+        cutoff_to_normalized(self.live_engine.base_filter_cutoff)
+    }
+
+    // Shim over set_filter_q: 0-1 maps to Q = 1 / (0.707 - 0.657 * resonance),
+    // i.e. Q 1.41 to 20; 1.1-1.2 makes the filter self-oscillate at the cutoff
+    pub fn set_filter_resonance(&mut self, resonance: f32) {
+        self.live_engine.configure_filters(|filter| filter.set_resonance(resonance));
+    }
+
+    // Filter Q, 0.5-25; the SVF damping is 1 / Q
+    pub fn set_filter_q(&mut self, q: f32) {
+        self.live_engine.configure_filters(|filter| filter.set_q(q));
+    }
+
+    // Infinite while self-oscillating
+    pub fn get_filter_q(&self) -> f32 {
+        self.live_engine.filter.get_q()
+    }
+
+    // 0 = lowpass, 1 = highpass, 2 = bandpass, 3 = notch, 4 = peak,
+    // 5 = low shelf, 6 = high shelf, 7 = morph, 8 = formant,
+    // 9 = comb tuned to the last note, with resonance as feedback (cutoff unused),
+    // 10 = band (see set_bandpass_range)
+    pub fn set_filter_mode(&mut self, mode: u8) {
+        self.live_engine.configure_filters(|filter| filter.set_mode(mode));
+    }
+
+    // Morph mode position: 0 = lowpass, 0.5 = bandpass, 1 = highpass, crossfading in between
+    pub fn set_filter_morph(&mut self, position: f32) {
+        self.live_engine.configure_filters(|filter| filter.set_morph(position));
+    }
+
+    // Vowel filter in the filter slot: vowel_morph 0 = A, 0.25 = E, 0.5 = I, 0.75 = O, 1 = U.
+    // Cutoff modulation (LFO, envelope, matrix) sweeps the vowel, one vowel per octave.
+    // Disabling returns to the mode it replaced.
+    pub fn set_formant(&mut self, enabled: bool, vowel_morph: f32) {
+        self.live_engine.configure_filters(|filter| {
+            filter.set_formant(enabled);
+            filter.set_vowel(vowel_morph);
+        });
+        if enabled {
+            self.live_engine.filter_enabled = true;
+        }
+    }
+
+    // Audio-rate cutoff FM of up to 4 octaves. source_voice_relative = true uses a sine
+    // at the played note (each voice's own note with per-voice filters, otherwise the
+    // last note); false modulates the cutoff with the signal entering the filter.
+    // Amount 0 turns it off and skips the per-sample work
+    pub fn set_filter_fm(&mut self, source_voice_relative: bool, amount: f32) {
+        self.live_engine.configure_filters(|filter| filter.set_fm(source_voice_relative, amount));
+    }
+
+    // Explicit passband for filter mode 10: highpass at low_hz in series with a
+    // lowpass at high_hz. Cutoff modulation shifts the whole band
+    pub fn set_bandpass_range(&mut self, low_hz: f32, high_hz: f32) {
+        self.live_engine.configure_filters(|filter| filter.set_band_range(low_hz, high_hz));
+    }
+
+    // Magnitude in dB of the current filter (mode, model, cutoff, resonance, slope)
+    // at each frequency, for drawing the curve. Reads the settings only
+    pub fn get_filter_response(&self, frequencies: &[f32]) -> Vec<f32> {
+        self.live_engine.filter_response(frequencies)
+    }
+
+    pub fn set_filter_peak_gain(&mut self, db: f32) {
+        self.live_engine.configure_filters(|filter| filter.set_peak_gain_db(db));
+    }
+
+    pub fn set_filter_shelf_gain(&mut self, db: f32) {
+        self.live_engine.configure_filters(|filter| filter.set_shelf_gain_db(db));
+    }
+
+    // Input saturation of the state variable filter, 0 = clean to 1 = heavy
+    pub fn set_filter_drive(&mut self, amount: f32) {
+        self.live_engine.configure_filters(|filter| filter.set_drive(amount));
+    }
+
+    // 2 = 12 dB/oct, 4 = 24 dB/oct (lowpass, highpass, bandpass and morph only)
+    pub fn set_filter_slope(&mut self, poles: u8) {
+        self.live_engine.configure_filters(|filter| filter.set_slope(poles));
+    }
+
+    // 0 = state variable, 1 = ladder (24 dB/oct lowpass; mode and slope don't apply)
+    pub fn set_filter_model(&mut self, model: u8) {
+        self.live_engine.configure_filters(|filter| filter.set_model(model));
+    }
+
+    // Each voice gets its own filter, fed before the voices are summed
+    pub fn set_filter_per_voice(&mut self, enabled: bool) {
+        self.live_engine.set_filter_per_voice(enabled);
+    }
+
+    // Cutoff follows the played note: 0 = off, 1 = an octave up doubles the cutoff (max 2)
+    pub fn set_filter_keytrack(&mut self, amount: f32) {
+        self.live_engine.set_filter_keytrack(amount);
+    }
+
+    pub fn set_lfo_to_filter(&mut self, enabled: bool) {
+        self.live_engine.lfo_to_filter = enabled;
+    }
+
+    // 0 = legacy cutoff * (1 + lfo), 1 = bipolar octaves, 2 = unipolar octaves (upward only)
+    pub fn set_lfo_polarity(&mut self, mode: u8) {
+        self.live_engine.lfo_polarity = LfoPolarity::from_u8(mode);
+    }
+
+    // Cutoff swing of the LFO in octaves: cutoff * 2^(lfo * octaves)
+    pub fn set_lfo_filter_depth(&mut self, octaves: f32) {
+        self.live_engine.lfo_filter_octaves = octaves.clamp(0.0, 8.0);
+    }
+
+    // Auto-wah: the level of the voice sum moves the cutoff by up to this many
+    // octaves (-8..8, negative closes it); 0 turns the follower off
+    pub fn set_env_follow_to_cutoff(&mut self, octaves: f32) {
+        self.live_engine.set_env_follow(octaves);
+    }
+
+    // How fast the follower rises and falls, in ms
+    pub fn set_env_follow_times(&mut self, attack_ms: f32, release_ms: f32) {
+        self.live_engine.env_follower.set_attack(attack_ms.clamp(0.1, 500.0));
+        self.live_engine.env_follower.set_release(release_ms.clamp(1.0, 5000.0));
+    }
+
+    // ==== TIMELINE ENGINE FILTER CONTROL ====
+
+    pub fn set_timeline_env_follow_to_cutoff(&mut self, octaves: f32) {
+        self.timeline_engine.set_env_follow(octaves);
+    }
+
+    pub fn set_timeline_env_follow_times(&mut self, attack_ms: f32, release_ms: f32) {
+        self.timeline_engine.env_follower.set_attack(attack_ms.clamp(0.1, 500.0));
+        self.timeline_engine.env_follower.set_release(release_ms.clamp(1.0, 5000.0));
+    }
+
+    pub fn set_timeline_filter_enabled(&mut self, enabled: bool) {
+        self.timeline_engine.filter_enabled = enabled;
+    }
+
+    pub fn set_timeline_filter_cutoff(&mut self, cutoff: f32) {
+        self.timeline_engine.set_filter_cutoff(cutoff);
+    }
+
+    pub fn set_timeline_filter_cutoff_normalized(&mut self, x: f32) {
+        self.set_timeline_filter_cutoff(normalized_to_cutoff(x));
+    }
+
+    pub fn get_timeline_filter_cutoff_normalized(&self) -> f32 {
+        cutoff_to_normalized(self.timeline_engine.base_filter_cutoff)
+    }
+
+    pub fn set_timeline_filter_resonance(&mut self, resonance: f32) {
+        self.timeline_engine.configure_filters(|filter| filter.set_resonance(resonance));
+    }
+
+    pub fn set_timeline_filter_q(&mut self, q: f32) {
+        self.timeline_engine.configure_filters(|filter| filter.set_q(q));
+    }
+
+    pub fn get_timeline_filter_q(&self) -> f32 {
+        self.timeline_engine.filter.get_q()
+    }
+
+    pub fn set_timeline_filter_mode(&mut self, mode: u8) {
+        self.timeline_engine.configure_filters(|filter| filter.set_mode(mode));
+    }
+
+    pub fn set_timeline_filter_morph(&mut self, position: f32) {
+        self.timeline_engine.configure_filters(|filter| filter.set_morph(position));
+    }
+
+    pub fn set_timeline_filter_fm(&mut self, source_voice_relative: bool, amount: f32) {
+        self.timeline_engine.configure_filters(|filter| filter.set_fm(source_voice_relative, amount));
+    }
+
+    pub fn set_timeline_formant(&mut self, enabled: bool, vowel_morph: f32) {
+        self.timeline_engine.configure_filters(|filter| {
+            filter.set_formant(enabled);
+            filter.set_vowel(vowel_morph);
+        });
+        if enabled {
+            self.timeline_engine.filter_enabled = true;
+        }
+    }
+
+    pub fn set_timeline_bandpass_range(&mut self, low_hz: f32, high_hz: f32) {
+        self.timeline_engine.configure_filters(|filter| filter.set_band_range(low_hz, high_hz));
+    }
+
+    pub fn get_timeline_filter_response(&self, frequencies: &[f32]) -> Vec<f32> {
+        self.timeline_engine.filter_response(frequencies)
+    }
+
+    pub fn set_timeline_filter_peak_gain(&mut self, db: f32) {
+        self.timeline_engine.configure_filters(|filter| filter.set_peak_gain_db(db));
+    }
+
+    pub fn set_timeline_filter_shelf_gain(&mut self, db: f32) {
+        self.timeline_engine.configure_filters(|filter| filter.set_shelf_gain_db(db));
+    }
+
+    pub fn set_timeline_filter_drive(&mut self, amount: f32) {
+        self.timeline_engine.configure_filters(|filter| filter.set_drive(amount));
+    }
+
+    pub fn set_timeline_filter_slope(&mut self, poles: u8) {
+        self.timeline_engine.configure_filters(|filter| filter.set_slope(poles));
+    }
+
+    pub fn set_timeline_filter_model(&mut self, model: u8) {
+        self.timeline_engine.configure_filters(|filter| filter.set_model(model));
+    }
+
+    pub fn set_timeline_filter_per_voice(&mut self, enabled: bool) {
+        self.timeline_engine.set_filter_per_voice(enabled);
+    }
+
+    pub fn set_timeline_filter_keytrack(&mut self, amount: f32) {
+        self.timeline_engine.set_filter_keytrack(amount);
+    }
+
+    pub fn set_timeline_lfo_to_filter(&mut self, enabled: bool) {
+        self.timeline_engine.lfo_to_filter = enabled;
+    }
+
+    pub fn set_timeline_lfo_polarity(&mut self, mode: u8) {
+        self.timeline_engine.lfo_polarity = LfoPolarity::from_u8(mode);
+    }
+
+    pub fn set_timeline_lfo_filter_depth(&mut self, octaves: f32) {
+        self.timeline_engine.lfo_filter_octaves = octaves.clamp(0.0, 8.0);
+    }
+
+    // ==== LIVE ENGINE EFFECTS CONTROL ====
+
+    // Mix is an equal-power crossfade: 0 = dry, 1 = echoes only (set_delay_kill_dry
+    // drops the dry at any mix)
+    pub fn set_delay(&mut self, enabled: bool, time_ms: f32, feedback: f32, mix: f32) {
+        self.live_engine.set_delay(enabled, time_ms, feedback, mix);
+    }
+
+    // Separate echo times per side for the stereo output; set_delay sets both
+    pub fn set_delay_time_lr(&mut self, left_ms: f32, right_ms: f32) {
+        self.live_engine.delay.set_delay_time_lr(left_ms, right_ms);
+    }
+
+    // Tape-style repeats: lowpass in the feedback loop (20000 = off) and wow 0-1
+    pub fn set_delay_feedback_tone(&mut self, cutoff: f32) {
+        self.live_engine.delay.set_feedback_tone(cutoff);
+    }
+
+    pub fn set_delay_wow(&mut self, amount: f32) {
+        self.live_engine.delay.set_wow(amount);
+    }
+
+    // Longest delay time, 100-10000 ms (2000 by default). Resizing keeps the
+    // echoes that are running; longer times cost more memory
+    pub fn set_delay_max_time(&mut self, ms: f32) {
+        self.live_engine.delay.set_max_delay(ms);
+    }
+
+    // Wet-only output, for using the delay as a send
+    pub fn set_delay_kill_dry(&mut self, enabled: bool) {
+        self.live_engine.delay.set_kill_dry(enabled);
+    }
+
+    // Keeps the echoes down while playing and lets them bloom in the gaps:
+    // amount 0-1 (0 = off), release 10-2000 ms
+    pub fn set_delay_ducking(&mut self, amount: f32, release_ms: f32) {
+        self.live_engine.delay.set_ducking(amount, release_ms);
+    }
+
+    // Loops what the delay holds indefinitely, without recording new input
+    pub fn set_delay_freeze(&mut self, enabled: bool) {
+        self.live_engine.delay.set_freeze(enabled);
+    }
+
+    // Reverse echoes: each delay time's worth of input plays back backwards.
+    // Switching clears what the delay holds
+    pub fn set_delay_reverse(&mut self, enabled: bool) {
+        self.live_engine.delay.set_reverse(enabled);
+    }
+
+    // Rhythmic pattern of up to 4 echoes (extra entries are ignored, missing levels
+    // are 1) on the one delay line; empty times go back to the single echo.
+    // Feedback comes from the tap chosen by set_delay_feedback_tap (default: last)
+    pub fn set_delay_taps(&mut self, times_ms: &[f32], levels: &[f32]) {
+        self.live_engine.delay.set_taps(times_ms, levels);
+    }
+
+    // -1 = left, 1 = right per tap; heard through process_stereo
+    pub fn set_delay_tap_pans(&mut self, pans: &[f32]) {
+        self.live_engine.delay.set_tap_pans(pans);
+    }
+
+    // 0-3; anything past the last tap uses the last one
+    pub fn set_delay_feedback_tap(&mut self, index: u8) {
+        self.live_engine.delay.set_feedback_tap(index);
+    }
+
+    // Tape echo character: wow depth in ms (0-3, 0 = steady) and rate in Hz, with
+    // a little random flutter on top, and saturation 0-1 of the repeats
+    pub fn set_delay_character(&mut self, wow_depth: f32, wow_rate: f32, saturation: f32) {
+        self.live_engine.delay.set_wow_flutter(wow_depth, wow_rate);
+        self.live_engine.delay.set_saturation(saturation);
+    }
+
+    // Delay time as a note division of the tempo (divisions as in set_arp),
+    // following set_tempo; disabling goes back to the ms times
+    pub fn set_delay_sync(&mut self, enabled: bool, division: u8) {
+        self.live_engine.delay.set_sync(enabled.then_some(division));
+    }
+
+    // Echoes alternate left and right on the stereo output; mono output is unchanged
+    pub fn set_delay_ping_pong(&mut self, enabled: bool) {
+        self.live_engine.delay.set_ping_pong(enabled);
+    }
+
+    // How time changes land, over ~50 ms: 0 = crossfade (no pitch change), 1 = tape-style slew
+    pub fn set_delay_time_change_mode(&mut self, mode: u8) {
+        self.live_engine.delay.set_time_change_mode(mode);
+    }
+
+    pub fn set_reverb(&mut self, enabled: bool, room_size: f32, damping: f32) {
+        self.live_engine.reverb_enabled = enabled;
+        if enabled {
+            self.live_engine.reverb.set_room_size(room_size);
+            self.live_engine.reverb.set_damping(damping);
+        }
+    }
+
+    // Delay before the reverb onset, 0-200 ms; changes crossfade over 30 ms
+    pub fn set_reverb_predelay(&mut self, ms: f32) {
+        self.live_engine.reverb.set_predelay(ms);
+    }
+
+    // Freeze pedal: sustains the current reverb tail under new playing until released.
+    // Locking and releasing both ramp over 100 ms; not saved with presets
+    pub fn set_reverb_freeze(&mut self, enabled: bool) {
+        self.live_engine.reverb.set_freeze(enabled);
+    }
+
+    // Early reflections against the diffuse tail: 0 = reflections only, 1 = tail only
+    // (the default). The reflections spread out over up to 80 ms as the room grows
+    pub fn set_reverb_early_late_balance(&mut self, balance: f32) {
+        self.live_engine.reverb.set_early_late_balance(balance);
+    }
+
+    // Stereo spread of the reverb tail in process_stereo, 0 = mono to 1 = full width.
+    // The mono process() always hears a single tank
+    pub fn set_reverb_width(&mut self, width: f32) {
+        self.live_engine.reverb.set_width(width);
+    }
+
+    // Equal-power dry/wet of the reverb, 0 = dry to 1 = tail only; defaults to a light 0.04
+    pub fn set_reverb_mix(&mut self, mix: f32) {
+        self.live_engine.reverb.set_mix(mix);
+    }
+
+    // Soft saturation on the reverb's comb sum, so hot input stays bounded
+    pub fn set_reverb_saturation(&mut self, enabled: bool) {
+        self.live_engine.reverb.set_saturation(enabled);
+    }
+
+    // Octave-up shimmer in the reverb feedback, 0 = off to 1 = full rising wash
+    pub fn set_reverb_shimmer(&mut self, amount: f32) {
+        self.live_engine.reverb.set_shimmer(amount);
+    }
+
+    // Impulse response for the convolution reverb, at the engine sample rate (max 3 s).
+    // Not part of presets; load it again after restoring one
+    pub fn load_reverb_ir(&mut self, samples: &[f32]) {
+        self.live_engine.convolution.load_ir(samples);
+    }
+
+    // Convolution with the loaded IR, after the algorithmic reverb; mix 0 = dry, 1 = wet only
+    pub fn set_convolution_reverb(&mut self, enabled: bool, mix: f32) {
+        self.live_engine.convolution_enabled = enabled;
+        if enabled {
+            self.live_engine.convolution.set_mix(mix);
+        }
+    }
+
+    // Last in the effect chain, so it also cuts reverb and delay tails below the threshold.
+    // Threshold in dB (-96 to 0); attack, hold and release in ms
+    pub fn set_noisegate(&mut self, enabled: bool, threshold_db: f32, attack_ms: f32, hold_ms: f32, release_ms: f32) {
+        self.live_engine.set_noisegate(enabled);
+        self.live_engine.noisegate.set_threshold_db(threshold_db);
+        self.live_engine.noisegate.set_attack_ms(attack_ms);
+        self.live_engine.noisegate.set_hold_ms(hold_ms);
+        self.live_engine.noisegate.set_release_ms(release_ms);
+    }
+
+    // String-machine ensemble between the delay and reverb; intensity 0 = dry to 1 = even blend.
+    // The mono output hears the mid, the stereo output the full spread
+    pub fn set_ensemble(&mut self, enabled: bool, intensity: f32) {
+        self.live_engine.ensemble_enabled = enabled;
+        self.live_engine.ensemble.set_intensity(intensity);
+    }
+
+    // Sweep width of the ensemble lines, 0 = static to 1 = ±3 ms
+    pub fn set_ensemble_depth(&mut self, depth: f32) {
+        self.live_engine.ensemble.set_depth(depth);
+    }
+
+    pub fn set_tremolo(&mut self, enabled: bool, rate: f32, depth: f32) {
+        self.live_engine.tremolo_enabled = enabled;
+        if enabled {
+            self.live_engine.tremolo.set_rate(rate);
+            self.live_engine.tremolo.set_depth(depth);
+        }
+    }
+
+    pub fn set_flanger(&mut self, enabled: bool, rate: f32, depth: f32, feedback: f32, mix: f32) {
+        self.live_engine.flanger_enabled = enabled;
+        if enabled {
+            self.live_engine.flanger.set_lfo_rate(rate);
+            self.live_engine.flanger.set_delay_range(depth);
+            self.live_engine.flanger.set_feedback(feedback);
+            self.live_engine.flanger.set_mix(mix);
+        }
+    }
+
+    // Ring modulation by a sine carrier; mix 1 leaves only the sum and difference tones
+    pub fn set_ringmod(&mut self, enabled: bool, frequency: f32, mix: f32) {
+        self.live_engine.ringmod_enabled = enabled;
+        if enabled {
+            self.live_engine.ringmod.set_frequency(frequency);
+            self.live_engine.ringmod.set_mix(mix);
+        }
+    }
+
+    // Runs the global filter and ring mod at 1x, 2x or 4x the sample rate (other
+    // values mean 1x), so filter drive and ring mod sidebands alias less and the
+    // filter cutoff can open further. Costs 2-4x CPU on those stages and adds
+    // ~0.5 ms (2x) or ~0.7 ms (4x) of latency while they are on
+    pub fn set_oversampling(&mut self, factor: u8) {
+        self.live_engine.set_oversampling(factor);
+    }
+
+    // Vowel resonances after the filter: vowel 0 = A, 1 = E, 2 = I, 3 = O, 4 = U,
+    // morph 0-1 glides toward the next vowel (U wraps to A).
+    // set_formant is the formant mode of the filter, which the cutoff LFO can sweep
+    pub fn set_formant_effect(&mut self, enabled: bool, vowel: u8, morph: f32) {
+        self.live_engine.formant_enabled = enabled;
+        if enabled {
+            self.live_engine.formant.set_vowel(vowel);
+            self.live_engine.formant.set_morph(morph);
+        }
+    }
+
+    // Shifts the mixed signal by -24 to +24 semitones and blends it with the dry (mix 0-1)
+    pub fn set_pitchshift(&mut self, enabled: bool, semitones: f32, mix: f32) {
+        self.live_engine.pitchshift_enabled = enabled;
+        if enabled {
+            self.live_engine.pitchshift.set_semitones(semitones);
+            self.live_engine.pitchshift.set_mix(mix);
+        }
+    }
+
+    // Moves every partial by shift_hz (negative = down), for inharmonic, metallic tones; mix 0-1
+    pub fn set_freqshift(&mut self, enabled: bool, shift_hz: f32, mix: f32) {
+        self.live_engine.freqshift_enabled = enabled;
+        if enabled {
+            self.live_engine.freqshift.set_shift_hz(shift_hz);
+            self.live_engine.freqshift.set_mix(mix);
+        }
+    }
+
+    // Tempo-synced on/off pattern of up to 16 steps; rate division as in set_arp
+    pub fn set_gate(&mut self, enabled: bool, rate_division: u8) {
+        self.live_engine.set_gate(enabled, rate_division);
+    }
+
+    // One byte per step, 0 = muted, anything else = open (wasm-bindgen can't pass bool slices)
+    pub fn set_gate_pattern(&mut self, steps: &[u8]) {
+        let steps: Vec<bool> = steps.iter().map(|&step| step != 0).collect();
+        self.live_engine.gate.set_pattern(&steps);
+    }
+
+    // Sweeps the sound between left and right; only heard through process_stereo
+    pub fn set_autopan(&mut self, enabled: bool, rate: f32, depth: f32) {
+        self.live_engine.autopan_enabled = enabled;
+        if enabled {
+            self.live_engine.autopan.set_rate(rate);
+            self.live_engine.autopan.set_depth(depth);
+        }
+    }
+
+    // Effect return levels (0 = effect transparent, 1 = full effect output)
+    pub fn set_delay_level(&mut self, level: f32) {
+        self.live_engine.delay_level = level.clamp(0.0, 1.0);
+    }
+
+    pub fn set_reverb_level(&mut self, level: f32) {
+        self.live_engine.reverb_level = level.clamp(0.0, 1.0);
+    }
+
+    pub fn set_tremolo_level(&mut self, level: f32) {
+        self.live_engine.tremolo_level = level.clamp(0.0, 1.0);
+    }
+
+    pub fn set_flanger_level(&mut self, level: f32) {
+        self.live_engine.flanger_level = level.clamp(0.0, 1.0);
+    }
+
+    // ==== TIMELINE ENGINE EFFECTS CONTROL ====
+
+    pub fn set_timeline_delay(&mut self, enabled: bool, time_ms: f32, feedback: f32, mix: f32) {
+        self.timeline_engine.set_delay(enabled, time_ms, feedback, mix);
+    }
+
+    pub fn set_timeline_delay_time_lr(&mut self, left_ms: f32, right_ms: f32) {
+        self.timeline_engine.delay.set_delay_time_lr(left_ms, right_ms);
+    }
+
+    pub fn set_timeline_delay_feedback_tone(&mut self, cutoff: f32) {
+        self.timeline_engine.delay.set_feedback_tone(cutoff);
+    }
+
+    pub fn set_timeline_delay_wow(&mut self, amount: f32) {
+        self.timeline_engine.delay.set_wow(amount);
+    }
+
+    pub fn set_timeline_delay_max_time(&mut self, ms: f32) {
+        self.timeline_engine.delay.set_max_delay(ms);
+    }
+
+    pub fn set_timeline_delay_kill_dry(&mut self, enabled: bool) {
+        self.timeline_engine.delay.set_kill_dry(enabled);
+    }
+
+    pub fn set_timeline_delay_ducking(&mut self, amount: f32, release_ms: f32) {
+        self.timeline_engine.delay.set_ducking(amount, release_ms);
+    }
+
+    pub fn set_timeline_delay_freeze(&mut self, enabled: bool) {
+        self.timeline_engine.delay.set_freeze(enabled);
+    }
+
+    pub fn set_timeline_delay_reverse(&mut self, enabled: bool) {
+        self.timeline_engine.delay.set_reverse(enabled);
+    }
+
+    pub fn set_timeline_delay_taps(&mut self, times_ms: &[f32], levels: &[f32]) {
+        self.timeline_engine.delay.set_taps(times_ms, levels);
+    }
+
+    pub fn set_timeline_delay_tap_pans(&mut self, pans: &[f32]) {
+        self.timeline_engine.delay.set_tap_pans(pans);
+    }
+
+    pub fn set_timeline_delay_feedback_tap(&mut self, index: u8) {
+        self.timeline_engine.delay.set_feedback_tap(index);
+    }
+
+    pub fn set_timeline_delay_character(&mut self, wow_depth: f32, wow_rate: f32, saturation: f32) {
+        self.timeline_engine.delay.set_wow_flutter(wow_depth, wow_rate);
+        self.timeline_engine.delay.set_saturation(saturation);
+    }
+
+    pub fn set_timeline_delay_sync(&mut self, enabled: bool, division: u8) {
+        self.timeline_engine.delay.set_sync(enabled.then_some(division));
+    }
+
+    pub fn set_timeline_delay_ping_pong(&mut self, enabled: bool) {
+        self.timeline_engine.delay.set_ping_pong(enabled);
+    }
+
+    pub fn set_timeline_delay_time_change_mode(&mut self, mode: u8) {
+        self.timeline_engine.delay.set_time_change_mode(mode);
     }
 
-    pub fn set_timeline_glide_time(&mut self, time_ms: f32) {
-        for voice in &mut self.timeline_engine.voices {
-            voice.set_glide_time(time_ms);
+    pub fn set_timeline_reverb(&mut self, enabled: bool, room_size: f32, damping: f32) {
+        self.timeline_engine.reverb_enabled = enabled;
+        if enabled {
+            self.timeline_engine.reverb.set_room_size(room_size);
+            self.timeline_engine.reverb.set_damping(damping);
         }
     }
 
-    // ==== LIVE ENGINE EFFECTS CONTROL ====
+    pub fn set_timeline_reverb_predelay(&mut self, ms: f32) {
+        self.timeline_engine.reverb.set_predelay(ms);
+    }
 
-    pub fn set_delay(&mut self, enabled: bool, time_ms: f32, feedback: f32, mix: f32) {
-        self.live_engine.delay_enabled = enabled;
-        if enabled {
-            self.live_engine.delay.set_delay_time(time_ms);
-            self.live_engine.delay.set_feedback(feedback);
-            self.live_engine.delay.set_mix(mix);
-        }
+    pub fn set_timeline_reverb_freeze(&mut self, enabled: bool) {
+        self.timeline_engine.reverb.set_freeze(enabled);
     }
 
-    pub fn set_reverb(&mut self, enabled: bool, room_size: f32, damping: f32) {
-        self.live_engine.reverb_enabled = enabled;
-        if enabled {
-            self.live_engine.reverb.set_room_size(room_size);
-            self.live_engine.reverb.set_damping(damping);
-        }
+    pub fn set_timeline_reverb_early_late_balance(&mut self, balance: f32) {
+        self.timeline_engine.reverb.set_early_late_balance(balance);
     }
 
-    pub fn set_tremolo(&mut self, enabled: bool, rate: f32, depth: f32) {
-        self.live_engine.tremolo_enabled = enabled;
-        if enabled {
-            self.live_engine.tremolo.set_rate(rate);
-            self.live_engine.tremolo.set_depth(depth);
-        }
+    pub fn set_timeline_reverb_width(&mut self, width: f32) {
+        self.timeline_engine.reverb.set_width(width);
     }
 
-    pub fn set_flanger(&mut self, enabled: bool, rate: f32, depth: f32, feedback: f32, mix: f32) {
-        self.live_engine.flanger_enabled = enabled;
-        if enabled {
-            self.live_engine.flanger.set_lfo_rate(rate);
-            self.live_engine.flanger.set_delay_range(depth);
-            self.live_engine.flanger.set_feedback(feedback);
-            self.live_engine.flanger.set_mix(mix);
-        }
+    pub fn set_timeline_reverb_mix(&mut self, mix: f32) {
+        self.timeline_engine.reverb.set_mix(mix);
     }
 
-    // ==== TIMELINE ENGINE EFFECTS CONTROL ====
+    pub fn set_timeline_reverb_saturation(&mut self, enabled: bool) {
+        self.timeline_engine.reverb.set_saturation(enabled);
+    }
 
-    pub fn set_timeline_delay(&mut self, enabled: bool, time_ms: f32, feedback: f32, mix: f32) {
-        self.timeline_engine.delay_enabled = enabled;
-        if enabled {
-            self.timeline_engine.delay.set_delay_time(time_ms);
-            self.timeline_engine.delay.set_feedback(feedback);
-            self.timeline_engine.delay.set_mix(mix);
-        }
+    pub fn set_timeline_reverb_shimmer(&mut self, amount: f32) {
+        self.timeline_engine.reverb.set_shimmer(amount);
     }
 
-    pub fn set_timeline_reverb(&mut self, enabled: bool, room_size: f32, damping: f32) {
-        self.timeline_engine.reverb_enabled = enabled;
+    pub fn load_timeline_reverb_ir(&mut self, samples: &[f32]) {
+        self.timeline_engine.convolution.load_ir(samples);
+    }
+
+    pub fn set_timeline_convolution_reverb(&mut self, enabled: bool, mix: f32) {
+        self.timeline_engine.convolution_enabled = enabled;
         if enabled {
-            self.timeline_engine.reverb.set_room_size(room_size);
-            self.timeline_engine.reverb.set_damping(damping);
+            self.timeline_engine.convolution.set_mix(mix);
         }
     }
 
+    pub fn set_timeline_noisegate(&mut self, enabled: bool, threshold_db: f32, attack_ms: f32, hold_ms: f32, release_ms: f32) {
+        self.timeline_engine.set_noisegate(enabled);
+        self.timeline_engine.noisegate.set_threshold_db(threshold_db);
+        self.timeline_engine.noisegate.set_attack_ms(attack_ms);
+        self.timeline_engine.noisegate.set_hold_ms(hold_ms);
+        self.timeline_engine.noisegate.set_release_ms(release_ms);
+    }
+
+    pub fn set_timeline_ensemble(&mut self, enabled: bool, intensity: f32) {
+        self.timeline_engine.ensemble_enabled = enabled;
+        self.timeline_engine.ensemble.set_intensity(intensity);
+    }
+
+    pub fn set_timeline_ensemble_depth(&mut self, depth: f32) {
+        self.timeline_engine.ensemble.set_depth(depth);
+    }
+
     pub fn set_timeline_tremolo(&mut self, enabled: bool, rate: f32, depth: f32) {
         self.timeline_engine.tremolo_enabled = enabled;
         if enabled {
@@ -405,12 +2206,848 @@ impl AudioEngine {
         }
     }
 
+    pub fn set_timeline_ringmod(&mut self, enabled: bool, frequency: f32, mix: f32) {
+        self.timeline_engine.ringmod_enabled = enabled;
+        if enabled {
+            self.timeline_engine.ringmod.set_frequency(frequency);
+            self.timeline_engine.ringmod.set_mix(mix);
+        }
+    }
+
+    pub fn set_timeline_oversampling(&mut self, factor: u8) {
+        self.timeline_engine.set_oversampling(factor);
+    }
+
+    pub fn set_timeline_formant_effect(&mut self, enabled: bool, vowel: u8, morph: f32) {
+        self.timeline_engine.formant_enabled = enabled;
+        if enabled {
+            self.timeline_engine.formant.set_vowel(vowel);
+            self.timeline_engine.formant.set_morph(morph);
+        }
+    }
+
+    pub fn set_timeline_pitchshift(&mut self, enabled: bool, semitones: f32, mix: f32) {
+        self.timeline_engine.pitchshift_enabled = enabled;
+        if enabled {
+            self.timeline_engine.pitchshift.set_semitones(semitones);
+            self.timeline_engine.pitchshift.set_mix(mix);
+        }
+    }
+
+    pub fn set_timeline_freqshift(&mut self, enabled: bool, shift_hz: f32, mix: f32) {
+        self.timeline_engine.freqshift_enabled = enabled;
+        if enabled {
+            self.timeline_engine.freqshift.set_shift_hz(shift_hz);
+            self.timeline_engine.freqshift.set_mix(mix);
+        }
+    }
+
+    pub fn set_timeline_gate(&mut self, enabled: bool, rate_division: u8) {
+        self.timeline_engine.set_gate(enabled, rate_division);
+    }
+
+    pub fn set_timeline_gate_pattern(&mut self, steps: &[u8]) {
+        let steps: Vec<bool> = steps.iter().map(|&step| step != 0).collect();
+        self.timeline_engine.gate.set_pattern(&steps);
+    }
+
+    pub fn set_timeline_autopan(&mut self, enabled: bool, rate: f32, depth: f32) {
+        self.timeline_engine.autopan_enabled = enabled;
+        if enabled {
+            self.timeline_engine.autopan.set_rate(rate);
+            self.timeline_engine.autopan.set_depth(depth);
+        }
+    }
+
+    pub fn set_timeline_delay_level(&mut self, level: f32) {
+        self.timeline_engine.delay_level = level.clamp(0.0, 1.0);
+    }
+
+    pub fn set_timeline_reverb_level(&mut self, level: f32) {
+        self.timeline_engine.reverb_level = level.clamp(0.0, 1.0);
+    }
+
+    pub fn set_timeline_tremolo_level(&mut self, level: f32) {
+        self.timeline_engine.tremolo_level = level.clamp(0.0, 1.0);
+    }
+
+    pub fn set_timeline_flanger_level(&mut self, level: f32) {
+        self.timeline_engine.flanger_level = level.clamp(0.0, 1.0);
+    }
+
+    // Every engine parameter as a plain JS object (see state::SynthState)
+    pub fn get_state(&self) -> Result<JsValue, JsValue> {
+        serde_wasm_bindgen::to_value(&self.snapshot()).map_err(JsValue::from)
+    }
+
+    pub fn set_state(&mut self, state: JsValue) -> Result<(), JsValue> {
+        let state: SynthState = serde_wasm_bindgen::from_value(state)?;
+        self.restore(&state);
+        Ok(())
+    }
+
+    // JSON text for localStorage, tagged with a format version
+    pub fn export_preset(&self) -> String {
+        preset_to_json(&self.snapshot())
+    }
+
+    // Values are clamped by the regular setters; malformed JSON is an error
+    pub fn import_preset(&mut self, json: &str) -> Result<(), JsValue> {
+        let state = parse_preset(json).map_err(|e| JsValue::from_str(&e))?;
+        self.restore(&state);
+        Ok(())
+    }
+
+    fn capture_scope(&mut self, output: &[f32]) {
+        self.scope_buffer.clear();
+        self.scope_buffer.extend_from_slice(output);
+    }
+
+    // Copies the last processed block; with the trigger on it starts at the first
+    // rising zero crossing so periodic waveforms hold still between frames
+    pub fn copy_scope_buffer(&self, dest: &mut [f32]) {
+        let start = if self.scope_trigger { self.find_scope_trigger(dest.len()) } else { 0 };
+        let source = &self.scope_buffer[start..];
+        let count = source.len().min(dest.len());
+        dest[..count].copy_from_slice(&source[..count]);
+        dest[count..].fill(0.0);
+    }
+
+    pub fn set_scope_trigger(&mut self, enabled: bool) {
+        self.scope_trigger = enabled;
+    }
+
+    // Prefers crossings that still leave a full window after them; falls back to the block start
+    fn find_scope_trigger(&self, width: usize) -> usize {
+        let buffer = &self.scope_buffer;
+        let last = buffer.len().saturating_sub(width).max(1).min(buffer.len());
+        (1..last)
+            .find(|&i| buffer[i - 1] <= 0.0 && buffer[i] > 0.0)
+            .unwrap_or(0)
+    }
+
+    // Magnitudes in dB of the last processed block, bin k centred on
+    // k * sample_rate / (2 * bins.len()) when bins.len() is a power of two
+    pub fn get_spectrum(&mut self, bins: &mut [f32]) {
+        self.spectrum.analyze(&self.scope_buffer, bins);
+    }
+
+    // One entry per live voice, 0 for the silent ones, for note visualizers
+    pub fn voice_frequencies(&self) -> Vec<f32> {
+        self.live_engine.voices.iter().map(Voice::get_frequency).collect()
+    }
+
+    pub fn voice_amplitudes(&self) -> Vec<f32> {
+        self.live_engine.voices.iter().map(Voice::get_amplitude).collect()
+    }
+
+    pub fn timeline_voice_frequencies(&self) -> Vec<f32> {
+        self.timeline_engine.voices.iter().map(Voice::get_frequency).collect()
+    }
+
+    pub fn timeline_voice_amplitudes(&self) -> Vec<f32> {
+        self.timeline_engine.voices.iter().map(Voice::get_amplitude).collect()
+    }
+
+    // Debugging aid for voice interactions: a muted voice keeps playing (envelope, glide,
+    // filter) but is left out of the mix. Indices match voice_frequencies; not saved
+    pub fn set_voice_mute(&mut self, index: usize, muted: bool) {
+        self.live_engine.set_voice_mute(index, muted);
+    }
+
+    pub fn clear_voice_mutes(&mut self) {
+        self.live_engine.voice_mutes.fill(false);
+    }
+
+    pub fn set_timeline_voice_mute(&mut self, index: usize, muted: bool) {
+        self.timeline_engine.set_voice_mute(index, muted);
+    }
+
+    pub fn clear_timeline_voice_mutes(&mut self) {
+        self.timeline_engine.voice_mutes.fill(false);
+    }
+
+    // How often a filter blew up (NaN/Inf) and was cleared, across both engines
+    pub fn get_filter_resets(&self) -> u32 {
+        self.live_engine.filter_resets + self.timeline_engine.filter_resets
+    }
+
     pub fn get_sample_rate(&self) -> f32 {
-        SAMPLE_RATE
+        self.sample_rate
+    }
+
+    // Voices per engine, as set by with_max_voices
+    pub fn get_max_voices(&self) -> usize {
+        self.live_engine.voices.len()
     }
 }
 
-fn midi_to_freq(midi: u8) -> f32 {
-    440.0 * 2.0_f32.powf((midi as f32 - 69.0) / 12.0)
+// Crossfade between the signal before and after an effect
+fn blend(dry: f32, wet: f32, level: f32) -> f32 {
+    dry * (1.0 - level) + wet * level
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn active_voices(engine: &AudioEngine) -> usize {
+        engine.live_engine.voices.iter().filter(|voice| voice.is_active()).count()
+    }
+
+    #[test]
+    fn retrigger_modes_on_a_held_note() {
+        for (mode, expected) in [(0, 2), (1, 1), (2, 1)] {
+            let mut engine = AudioEngine::new();
+            engine.set_retrigger_mode(mode);
+            engine.set_lfo_rate(2.0);
+            let mut block = [0.0; 4800];
+            engine.note_on(60, 0.8);
+            engine.process(&mut block);
+            let (phase, level) = (engine.live_engine.lfo.get_phase(), engine.live_engine.filter_env.get_value());
+            engine.note_on(60, 0.3);
+            if mode == 2 {
+                // The ignored press doesn't restart the LFO, and the filter envelope carries
+                // on down its decay instead of climbing a fresh attack
+                assert!(phase > 0.0);
+                assert_eq!(engine.live_engine.lfo.get_phase(), phase);
+                engine.process(&mut [0.0; 1]);
+                assert!(engine.live_engine.filter_env.get_value() < level);
+                assert_eq!(engine.live_engine.last_velocity, 0.8);
+            }
+            engine.process(&mut block);
+            assert_eq!(active_voices(&engine), expected, "mode {}", mode);
+        }
+    }
+
+    fn sounding_frequency(engine: &AudioEngine) -> f32 {
+        engine.voice_frequencies().into_iter().find(|&freq| freq > 0.0).unwrap_or(0.0)
+    }
+
+    #[test]
+    fn master_tune_down_a_semitone_plays_the_note_below() {
+        let mut reference = AudioEngine::new();
+        reference.note_on(68, 0.8);
+
+        let mut engine = AudioEngine::new();
+        engine.set_master_tune(-100.0);
+        engine.note_on(69, 0.8);
+        let expected = sounding_frequency(&reference);
+        assert!((sounding_frequency(&engine) - expected).abs() < 1e-3 * expected);
+    }
+
+    #[test]
+    fn transpose_stacks_with_master_tune() {
+        let mut reference = AudioEngine::new();
+        reference.note_on(60, 0.8);
+
+        let mut engine = AudioEngine::new();
+        engine.set_transpose(12);
+        engine.set_master_tune(100.0);
+        engine.note_on(47, 0.8);
+        let expected = sounding_frequency(&reference);
+        assert!((sounding_frequency(&engine) - expected).abs() < 1e-3 * expected);
+    }
+
+    // Same seed on every engine so random sources can't make two renders differ
+    fn seeded_engine() -> AudioEngine {
+        let mut engine = AudioEngine::new();
+        engine.set_random_seed(1);
+        engine
+    }
+
+    #[test]
+    fn automation_applies_from_its_sample_offset() {
+        let render = |events: Vec<AutomationEvent>| {
+            let mut engine = seeded_engine();
+            engine.set_waveform(1);
+            engine.set_filter_enabled(true);
+            engine.set_filter_cutoff(2000.0);
+            // Let the cutoff ramp settle first
+            engine.process(&mut [0.0; 2048]);
+            engine.note_on(60, 0.8);
+            let mut output = vec![0.0; 512];
+            engine.process_with_automation(&mut output, events);
+            output
+        };
+        let plain = render(Vec::new());
+        let automated = render(vec![AutomationEvent::new(128, 3, 200.0)]);
+
+        let first_change = plain.iter().zip(&automated).position(|(a, b)| a != b);
+        assert_eq!(first_change, Some(128));
+    }
+
+    // Random LFOs, vibrato and the random arpeggio all draw from the seeded sources
+    fn render_seeded(seed: u64) -> Vec<f32> {
+        let mut engine = AudioEngine::new();
+        engine.set_random_seed(seed);
+        engine.set_lfo_waveform(3);
+        engine.set_lfo_rate(20.0);
+        engine.set_lfo_depth(1.0);
+        engine.set_lfo_to_filter(true);
+        engine.set_filter_enabled(true);
+        engine.set_filter_cutoff(1000.0);
+        engine.set_voice_vibrato(6.0, 30.0, 0.0);
+        engine.set_arp(true, 3, 4);
+        for note in [60, 64, 67] {
+            engine.note_on(note, 0.8);
+        }
+        let mut output = vec![0.0; 48000];
+        for block in output.chunks_mut(128) {
+            engine.process(block);
+        }
+        output
+    }
+
+    #[test]
+    fn same_seed_renders_bit_identical_output() {
+        let first = render_seeded(42);
+        assert!(first.iter().any(|&x| x != 0.0));
+        assert!(first.iter().zip(&render_seeded(42)).all(|(a, b)| a.to_bits() == b.to_bits()));
+        assert_ne!(first, render_seeded(43));
+    }
+
+    // Twenty held notes reach voices past the default sixteen; the delay adds wow and flutter
+    fn render_seeded_wide(seed: u64) -> Vec<f32> {
+        let mut engine = AudioEngine::with_max_voices(24);
+        engine.set_random_seed(seed);
+        engine.set_voice_vibrato(6.0, 30.0, 0.0);
+        engine.set_delay(true, 250.0, 0.4, 0.5);
+        engine.set_delay_character(3.0, 2.0, 0.0);
+        for note in 40..60 {
+            engine.note_on(note, 0.5);
+        }
+        let mut output = vec![0.0; 24000];
+        for block in output.chunks_mut(128) {
+            engine.process(block);
+        }
+        output
+    }
+
+    #[test]
+    fn same_seed_is_bit_identical_past_sixteen_voices_and_through_the_delay() {
+        let first = render_seeded_wide(42);
+        assert!(first.iter().zip(&render_seeded_wide(42)).all(|(a, b)| a.to_bits() == b.to_bits()));
+        assert_ne!(first, render_seeded_wide(43));
+    }
+
+    #[test]
+    fn every_random_source_gets_its_own_seed() {
+        let mut seeds = Vec::new();
+        for engine in [LIVE_ENGINE_STREAM, TIMELINE_ENGINE_STREAM] {
+            let seed = stream_seed(7, engine);
+            for source in [LFO_STREAM, TREMOLO_STREAM, FLANGER_STREAM, ARP_STREAM, AUTOPAN_STREAM] {
+                seeds.push(stream_seed(seed, source));
+            }
+            // The delay splits its stream again into wow and flutter
+            let delay = stream_seed(seed, DELAY_STREAM);
+            seeds.extend([stream_seed(delay, 0), stream_seed(delay, 1)]);
+            seeds.extend((0..VOICE_LIMIT as u64).map(|i| stream_seed(seed, VOICE_STREAMS + i)));
+        }
+        let count = seeds.len();
+        seeds.sort_unstable();
+        seeds.dedup();
+        assert_eq!(seeds.len(), count);
+    }
+
+    // Lowest and highest modulated cutoff over a full LFO cycle, around a 1 kHz base
+    fn lfo_cutoff_range(engine: &mut AudioEngine) -> (f32, f32) {
+        engine.set_filter_enabled(true);
+        engine.set_filter_cutoff(1000.0);
+        engine.set_lfo_rate(50.0);
+        engine.set_lfo_depth(1.0);
+        engine.set_lfo_to_filter(true);
+        cutoff_range_over_cycle(engine)
+    }
+
+    // Settles the cutoff and depth ramps, then looks at one 960-sample cycle
+    fn cutoff_range_over_cycle(engine: &mut AudioEngine) -> (f32, f32) {
+        engine.process(&mut [0.0; 4800]);
+        engine.process(&mut [0.0; 960]);
+        let frames = &engine.live_engine.cutoff_frames;
+        let low = frames.iter().copied().fold(f32::MAX, f32::min);
+        let high = frames.iter().copied().fold(f32::MIN, f32::max);
+        (low, high)
+    }
+
+    fn assert_close(actual: (f32, f32), expected: (f32, f32)) {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.01 * b;
+        assert!(close(actual.0, expected.0) && close(actual.1, expected.1), "{:?} vs {:?}", actual, expected);
+    }
+
+    #[test]
+    fn lfo_polarity_shapes_the_cutoff_sweep() {
+        let mut engine = AudioEngine::new();
+        engine.set_lfo_polarity(1);
+        assert_close(lfo_cutoff_range(&mut engine), (500.0, 2000.0));
+
+        let mut engine = AudioEngine::new();
+        engine.set_lfo_polarity(2);
+        assert_close(lfo_cutoff_range(&mut engine), (1000.0, 2000.0));
+
+        // Legacy multiplier: the bottom of the cycle slams into the floor
+        let mut engine = AudioEngine::new();
+        engine.set_lfo_polarity(0);
+        let (low, high) = lfo_cutoff_range(&mut engine);
+        assert!(low < 20.0 && (high - 2000.0).abs() < 20.0, "{} {}", low, high);
+    }
+
+    fn render_note(engine: &mut AudioEngine, samples: usize) -> Vec<f32> {
+        engine.note_on(60, 0.8);
+        let mut output = vec![0.0; samples];
+        for block in output.chunks_mut(128) {
+            engine.process(block);
+        }
+        output
+    }
+
+    #[test]
+    fn zero_return_level_makes_effects_transparent() {
+        let mut dry = seeded_engine();
+        let mut engine = seeded_engine();
+        engine.set_delay(true, 50.0, 0.5, 0.5);
+        engine.set_reverb(true, 0.8, 0.3);
+        engine.set_tremolo(true, 6.0, 1.0);
+        engine.set_flanger(true, 1.0, 5.0, 0.5, 0.5);
+        for set_level in [
+            AudioEngine::set_delay_level,
+            AudioEngine::set_reverb_level,
+            AudioEngine::set_tremolo_level,
+            AudioEngine::set_flanger_level,
+        ] {
+            set_level(&mut engine, 0.0);
+        }
+        assert_eq!(render_note(&mut engine, 9600), render_note(&mut dry, 9600));
+
+        engine.set_reverb_level(0.5);
+        assert_ne!(render_note(&mut engine, 9600), render_note(&mut dry, 9600));
+    }
+
+    // Interpolated positions of the rising zero crossings
+    fn rising_crossings(signal: &[f32]) -> Vec<f32> {
+        signal
+            .windows(2)
+            .enumerate()
+            .filter(|(_, w)| w[0] <= 0.0 && w[1] > 0.0)
+            .map(|(i, w)| i as f32 + w[0] / (w[0] - w[1]))
+            .collect()
+    }
+
+    // Spread in Hz of the instantaneous pitch, from the spacing of rising zero crossings
+    fn pitch_spread(signal: &[f32], sample_rate: f32) -> f32 {
+        let crossings = rising_crossings(signal);
+        let pitches: Vec<f32> = crossings.windows(2).map(|c| sample_rate / (c[1] - c[0])).collect();
+        let low = pitches.iter().copied().fold(f32::MAX, f32::min);
+        let high = pitches.iter().copied().fold(f32::MIN, f32::max);
+        high - low
+    }
+
+    #[test]
+    fn mod_wheel_brings_in_vibrato() {
+        let spread_at = |wheel: f32| {
+            let mut engine = seeded_engine();
+            engine.set_voice_vibrato(5.0, 50.0, 0.0);
+            engine.set_mod_wheel_dest(1);
+            engine.set_mod_wheel(wheel);
+            let output = render_note(&mut engine, 48000);
+            pitch_spread(&output[4800..], SAMPLE_RATE)
+        };
+        let (still, half, full) = (spread_at(0.0), spread_at(0.5), spread_at(1.0));
+        assert!(still < 0.5, "{}", still);
+        assert!(half > 5.0 && full > 1.5 * half, "{} {}", half, full);
+    }
+
+    #[test]
+    fn lfo_filter_depth_is_in_octaves_and_ignores_lfo_depth() {
+        let mut engine = AudioEngine::new();
+        engine.set_lfo_filter_depth(2.0);
+        assert_close(lfo_cutoff_range(&mut engine), (250.0, 4000.0));
+
+        // The LFO's own depth is for other destinations
+        engine.set_lfo_depth(0.1);
+        assert_close(cutoff_range_over_cycle(&mut engine), (250.0, 4000.0));
+    }
+
+    #[test]
+    fn aftertouch_opens_the_cutoff() {
+        let mut engine = AudioEngine::new();
+        engine.set_filter_enabled(true);
+        engine.set_filter_cutoff(1000.0);
+        assert_close(cutoff_range_over_cycle(&mut engine), (1000.0, 1000.0));
+
+        engine.set_aftertouch(0.5);
+        assert_close(cutoff_range_over_cycle(&mut engine), (2828.4, 2828.4));
+        engine.set_aftertouch(1.0);
+        assert_close(cutoff_range_over_cycle(&mut engine), (8000.0, 8000.0));
+
+        // The gain destination leaves the cutoff alone
+        engine.set_aftertouch_dest(1);
+        assert_close(cutoff_range_over_cycle(&mut engine), (1000.0, 1000.0));
+    }
+
+    #[test]
+    fn lfo1_cutoff_route_matches_the_lfo_to_filter_flag() {
+        let sweep = |engine: &mut AudioEngine| {
+            engine.set_filter_enabled(true);
+            engine.set_filter_cutoff(1000.0);
+            engine.set_lfo_rate(50.0);
+            engine.set_lfo_depth(1.0);
+            cutoff_range_over_cycle(engine);
+            engine.live_engine.cutoff_frames.clone()
+        };
+        let mut flag = AudioEngine::new();
+        flag.set_lfo_polarity(1);
+        flag.set_lfo_filter_depth(0.5);
+        flag.set_lfo_to_filter(true);
+        let mut route = AudioEngine::new();
+        route.set_mod_route(ModSource::Lfo1 as u8, ModDest::Cutoff as u8, 0.5);
+
+        let (flag, route) = (sweep(&mut flag), sweep(&mut route));
+        assert!(flag.iter().any(|&cutoff| cutoff > 1400.0));
+        assert!(flag.iter().zip(&route).all(|(a, b)| (a - b).abs() < 1e-3 * a));
+    }
+
+    #[test]
+    fn scope_copies_the_last_block() {
+        let mut engine = seeded_engine();
+        engine.note_on(60, 0.8);
+        engine.process(&mut [0.0; 512]);
+        let mut block = [0.0; 512];
+        engine.process(&mut block);
+        assert!(block.iter().any(|&x| x != 0.0));
+
+        let mut scope = [1.0; 512];
+        engine.copy_scope_buffer(&mut scope);
+        assert_eq!(scope, block);
+
+        // Triggered, the window starts on a rising zero crossing
+        engine.set_scope_trigger(true);
+        let mut scope = [0.0; 256];
+        engine.copy_scope_buffer(&mut scope);
+        let start = (1..512).find(|&i| block[i - 1] <= 0.0 && block[i] > 0.0).unwrap();
+        assert_eq!(scope[..], block[start..start + 256]);
+    }
+
+    #[test]
+    fn keytrack_doubles_the_cutoff_an_octave_up() {
+        let cutoff_for = |note| {
+            let mut engine = AudioEngine::new();
+            engine.set_filter_enabled(true);
+            engine.set_filter_cutoff(1000.0);
+            engine.set_filter_keytrack(1.0);
+            engine.note_on(note, 0.8);
+            engine.process(&mut [0.0; 4800]);
+            *engine.live_engine.cutoff_frames.last().unwrap()
+        };
+        let ratio = cutoff_for(72) / cutoff_for(60);
+        assert!((ratio - 2.0).abs() < 1e-3, "{ratio}");
+    }
+
+    // Largest sample-to-sample step of a note through a resonant filter across a cutoff jump
+    fn largest_jump_across_cutoff_step(smoothing_ms: f32) -> f32 {
+        let mut engine = AudioEngine::new();
+        engine.set_filter_enabled(true);
+        engine.set_filter_resonance(0.9);
+        engine.set_filter_cutoff(200.0);
+        engine.set_smoothing_time(smoothing_ms);
+        render_note(&mut engine, 9600);
+        engine.set_filter_cutoff(8000.0);
+        let mut output = [0.0; 2400];
+        engine.process(&mut output);
+        output.windows(2).map(|w| (w[1] - w[0]).abs()).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn cutoff_step_is_smoothed() {
+        let (smoothed, stepped) = (largest_jump_across_cutoff_step(10.0), largest_jump_across_cutoff_step(0.0));
+        // A smoothed sweep moves no faster than the note itself
+        assert!(smoothed < 0.05, "{smoothed}");
+        assert!(smoothed < 0.5 * stepped, "{smoothed} vs {stepped}");
+    }
+
+    #[test]
+    fn resonant_cutoff_sweep_stays_finite() {
+        let mut engine = AudioEngine::new();
+        engine.set_waveform(1);
+        engine.set_filter_enabled(true);
+        engine.set_filter_resonance(filter::MAX_RESONANCE);
+        engine.note_on(48, 1.0);
+        // 20 Hz to 20 kHz and back down, a block at a time, over four seconds
+        let blocks = 4 * 48000 / 128;
+        let mut block = [0.0; 128];
+        for b in 0..blocks {
+            let sweep = 1.0 - (2.0 * b as f32 / blocks as f32 - 1.0).abs();
+            engine.set_filter_cutoff(20.0 * 1000.0_f32.powf(sweep));
+            engine.process(&mut block);
+            assert!(block.iter().all(|x| x.is_finite()), "block {b}");
+        }
+        assert_eq!(engine.get_filter_resets(), 0);
+    }
+
+    #[test]
+    fn note_on_ducks_the_master() {
+        let mut plain = seeded_engine();
+        let mut ducked = seeded_engine();
+        ducked.set_sidechain_duck(true, 1.0, 100.0);
+        let (plain, ducked) = (render_note(&mut plain, 9600), render_note(&mut ducked, 9600));
+        let energy = |signal: &[f32]| signal.iter().map(|x| x * x).sum::<f32>();
+        // Silenced just after the 1 ms attack, back to full level once released
+        assert!(energy(&ducked[96..480]) < 0.05 * energy(&plain[96..480]));
+        assert!(ducked[4900..].iter().zip(&plain[4900..]).all(|(a, b)| (a - b).abs() < 1e-6));
+    }
+
+    #[test]
+    fn four_voices_steal_on_the_fifth_note() {
+        let mut engine = AudioEngine::with_max_voices(4);
+        assert_eq!(engine.live_engine.voices.len(), 4);
+        for note in 60..64 {
+            engine.note_on(note, 0.8);
+            engine.process(&mut [0.0; 128]);
+        }
+        engine.note_on(64, 0.8);
+        assert_eq!(active_voices(&engine), 4);
+        // The oldest note gave up its voice
+        let mut sounding = engine.voice_frequencies();
+        sounding.sort_by(f32::total_cmp);
+        for (freq, note) in sounding.into_iter().zip(61..65) {
+            assert!((freq - engine.live_engine.tuning.note_to_freq(note)).abs() < 0.01, "{freq} Hz");
+        }
+    }
+
+    #[test]
+    fn a4_is_440_hz_at_44100() {
+        let mut engine = AudioEngine::with_sample_rate(44100.0);
+        assert_eq!(engine.get_sample_rate(), 44100.0);
+        engine.note_on(69, 0.8);
+        let mut output = vec![0.0; 44100];
+        engine.process(&mut output);
+        let crossings = rising_crossings(&output[4410..]);
+        let pitch = 44100.0 * (crossings.len() - 1) as f32 / (crossings[crossings.len() - 1] - crossings[0]);
+        assert!((pitch - 440.0).abs() < 0.1, "{pitch} Hz");
+    }
+
+    #[test]
+    fn bypass_outputs_the_dry_mix() {
+        let mut dry = seeded_engine();
+        dry.set_dc_blocker(false);
+        let mut engine = seeded_engine();
+        engine.set_filter_enabled(true);
+        engine.set_filter_cutoff(300.0);
+        engine.set_delay(true, 50.0, 0.5, 0.5);
+        engine.set_reverb(true, 0.8, 0.3);
+        engine.set_tremolo(true, 6.0, 1.0);
+        engine.set_sidechain_duck(true, 1.0, 100.0);
+        engine.set_effects_bypass(true);
+        for engine in [&mut dry, &mut engine] {
+            engine.set_master_volume(0.5);
+        }
+        let (dry, bypassed) = (render_note(&mut dry, 9600), render_note(&mut engine, 9600));
+        assert!(dry.iter().any(|&x| x != 0.0));
+        assert_eq!(dry, bypassed);
+    }
+
+    #[test]
+    fn dc_blocker_stage_removes_offset_and_passes_a_sine() {
+        let mut engine = AudioEngine::new();
+        let settled = (0..48000).map(|_| engine.block_dc(0, 0.5)).last().unwrap();
+        assert!(settled.abs() < 1e-3, "{settled}");
+        let sine = |n: usize| (2.0 * std::f32::consts::PI * 440.0 * n as f32 / SAMPLE_RATE).sin();
+        let passed = (0..48000).map(|n| (engine.block_dc(0, sine(n)) - sine(n)).abs()).skip(24000).fold(0.0, f32::max);
+        assert!(passed < 0.05, "{passed}");
+
+        engine.set_dc_blocker(false);
+        assert_eq!(engine.block_dc(0, 0.5), 0.5);
+    }
+
+    #[test]
+    fn two_notes_show_their_frequencies_and_levels() {
+        let mut engine = AudioEngine::new();
+        engine.note_on(69, 0.8);
+        engine.note_on(81, 0.8);
+        engine.process(&mut [0.0; 512]);
+        let (frequencies, amplitudes) = (engine.voice_frequencies(), engine.voice_amplitudes());
+        assert_eq!(frequencies.len(), MAX_VOICES_PER_ENGINE);
+        for expected in [440.0, 880.0] {
+            let voice = frequencies.iter().position(|&f| (f - expected).abs() < 0.01).unwrap();
+            assert!(amplitudes[voice] > 0.0);
+        }
+        assert_eq!(amplitudes.iter().filter(|&&a| a > 0.0).count(), 2);
+    }
+
+    #[test]
+    fn two_mpe_notes_bend_independently() {
+        let mut engine = AudioEngine::new();
+        let low = engine.note_on_mpe(69, 0.8);
+        let high = engine.note_on_mpe(69, 0.8);
+        engine.set_voice_bend(low, -12.0);
+        engine.set_voice_bend(high, 7.0);
+        engine.process(&mut [0.0; 4800]);
+        let mut sounding: Vec<f32> = engine.voice_frequencies().into_iter().filter(|&f| f > 0.0).collect();
+        sounding.sort_by(f32::total_cmp);
+        assert_eq!(sounding.len(), 2);
+        assert!((sounding[0] - 220.0).abs() < 0.01, "{}", sounding[0]);
+        assert!((sounding[1] - 440.0 * (7.0f32 / 12.0).exp2()).abs() < 0.01, "{}", sounding[1]);
+    }
+
+    // Samples, to a 16-sample block, until the envelope first reaches full level
+    fn attack_samples(velocity: f32) -> usize {
+        let mut engine = AudioEngine::new();
+        engine.set_adsr(0.1, 0.1, 0.5, 0.1);
+        engine.set_velocity_to_attack(0.5);
+        engine.note_on(60, velocity);
+        let mut block = [0.0; 16];
+        (1..)
+            .find(|_| {
+                engine.process(&mut block);
+                engine.voice_amplitudes().iter().any(|&a| a >= 0.99)
+            })
+            .unwrap()
+            * block.len()
+    }
+
+    #[test]
+    fn harder_hits_attack_faster() {
+        let (hard, soft) = (attack_samples(1.0), attack_samples(0.1));
+        assert!(hard < soft, "{hard} vs {soft}");
+        // Half of the 100 ms attack at full velocity, 95% of it at 0.1
+        assert!(hard.abs_diff(2400) <= 32 && soft.abs_diff(4560) <= 32, "{hard} vs {soft}");
+    }
+
+    #[test]
+    fn one_shot_envelope_frees_a_held_note() {
+        let mut engine = AudioEngine::new();
+        engine.set_adsr(0.01, 0.1, 0.7, 0.5);
+        engine.set_envelope_mode(1);
+        engine.note_on(60, 0.8);
+        let mut block = [0.0; 4800];
+        engine.process(&mut block);
+        assert_eq!(active_voices(&engine), 1);
+        // 10 ms up, 100 ms down to silence, all without a note off
+        engine.process(&mut block);
+        assert_eq!(active_voices(&engine), 0);
+        engine.process(&mut block);
+        assert!(block.iter().all(|&x| x.abs() < 1e-4));
+    }
+
+    // Amplitude of one frequency in a signal holding a whole number of its cycles
+    fn amplitude_at(signal: &[f32], frequency: f32) -> f32 {
+        let (re, im) = signal.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, &x)| {
+            let phase = 2.0 * PI * frequency * n as f32 / SAMPLE_RATE;
+            (re + x * phase.cos(), im + x * phase.sin())
+        });
+        2.0 * (re * re + im * im).sqrt() / signal.len() as f32
+    }
+
+    // Level of the second harmonic against the fundamental for A4 at the given velocity,
+    // layered sine (low) to saw (high)
+    fn layered_second_harmonic_db(velocity: f32) -> f32 {
+        let mut engine = AudioEngine::new();
+        engine.set_velocity_layer(0, 1);
+        engine.note_on(69, velocity);
+        let mut output = vec![0.0; 9600];
+        engine.process(&mut output);
+        // The last 4800 samples hold exactly 44 cycles
+        let tail = &output[4800..];
+        20.0 * (amplitude_at(tail, 880.0) / amplitude_at(tail, 440.0)).log10()
+    }
+
+    #[test]
+    fn velocity_crossfades_the_layers() {
+        // sine + v (saw - sine): the rising saw brings -2 / pi of fundamental (against the
+        // sine's phase) and 1 / pi of second harmonic. Velocity 0 would be a silent sine,
+        // so the softest hit is nearly pure sine and the hardest a pure saw
+        for velocity in [0.05, 0.5, 1.0] {
+            let expected = 20.0 * (velocity / PI / (1.0 - velocity - 2.0 * velocity / PI).abs()).log10();
+            let measured = layered_second_harmonic_db(velocity);
+            assert!((measured - expected).abs() < 0.1, "{velocity}: {measured} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn muting_a_voice_removes_only_its_note() {
+        let mut engine = AudioEngine::new();
+        engine.set_waveform(0);
+        engine.note_on(69, 0.8);
+        engine.note_on(81, 0.8);
+        let mut output = vec![0.0; 4800];
+        engine.process(&mut output);
+        let (a4, a5) = (amplitude_at(&output, 440.0), amplitude_at(&output, 880.0));
+
+        let a4_voice = engine.voice_frequencies().iter().position(|&f| f == 440.0).unwrap();
+        engine.set_voice_mute(a4_voice, true);
+        engine.process(&mut output);
+        assert!(amplitude_at(&output, 440.0) < 1e-3 * a4);
+        assert!(amplitude_at(&output, 880.0) > 0.8 * a5);
+        // Still running underneath, so unmuting brings it straight back
+        assert!(engine.voice_amplitudes()[a4_voice] > 0.0);
+        engine.clear_voice_mutes();
+        engine.process(&mut output);
+        assert!(amplitude_at(&output, 440.0) > 0.8 * a4);
+    }
+
+    fn held_note_frequency(engine: &AudioEngine, note: u8) -> f32 {
+        let voice = engine.live_engine.voices.iter().find(|voice| voice.is_held() && voice.get_note() == note);
+        voice.unwrap().get_frequency()
+    }
+
+    #[test]
+    fn legato_glide_only_between_overlapping_notes() {
+        let mut engine = AudioEngine::new();
+        engine.set_glide_time(100.0);
+        engine.set_glide_mode(1);
+        let mut block = [0.0; 480];
+        // After silence: straight to pitch
+        engine.note_on(57, 0.8);
+        engine.process(&mut block);
+        assert_eq!(held_note_frequency(&engine, 57), 220.0);
+        // Overlapping: 10 ms into a 100 ms glide up from the held A3
+        engine.note_on(69, 0.8);
+        engine.process(&mut block);
+        let gliding = held_note_frequency(&engine, 69);
+        assert!(gliding > 220.0 && gliding < 300.0, "{gliding}");
+        engine.note_off(57);
+        engine.note_off(69);
+        engine.process(&mut block);
+        engine.note_on(81, 0.8);
+        engine.process(&mut block);
+        assert_eq!(held_note_frequency(&engine, 81), 880.0);
+    }
+
+    // Master gain per sample over the 10 ms after switching from silence to full volume,
+    // as the ratio to an identical engine left at full volume
+    fn master_gain_after_unmute(smoothing_ms: f32) -> Vec<f32> {
+        let (mut engine, mut reference) = (seeded_engine(), seeded_engine());
+        for engine in [&mut engine, &mut reference] {
+            engine.set_smoothing_time(smoothing_ms);
+            engine.set_waveform(0);
+            engine.note_on(69, 0.8);
+        }
+        engine.set_master_volume(0.0);
+        let (mut output, mut expected) = (vec![0.0; 480], vec![0.0; 480]);
+        // Down to silence within a second, the reference rendering alongside
+        let silent = (0..100).any(|_| {
+            engine.process(&mut output);
+            reference.process(&mut expected);
+            output.iter().all(|&x| x.abs() < 1e-6)
+        });
+        assert!(silent);
+
+        engine.set_master_volume(1.0);
+        engine.process(&mut output);
+        reference.process(&mut expected);
+        output.iter().zip(&expected).filter(|(_, e)| e.abs() > 0.01).map(|(o, e)| o / e).collect()
+    }
+
+    #[test]
+    fn master_volume_ramps_up_from_silence() {
+        let gains = master_gain_after_unmute(DEFAULT_SMOOTHING_MS);
+        assert!(gains.windows(2).all(|w| w[1] >= w[0] - 1e-4));
+        // A 10 ms ramp: plenty of steps in between, about two thirds up by the end
+        assert!(gains.iter().filter(|&&g| g > 0.01 && g < 0.99).count() > 100);
+        assert!(gains[0] < 0.05 && gains[gains.len() - 1] > 0.5);
+
+        let gains = master_gain_after_unmute(0.0);
+        assert!(gains.iter().all(|&g| (g - 1.0).abs() < 1e-4));
+    }
+}
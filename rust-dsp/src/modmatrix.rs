@@ -0,0 +1,131 @@
+// Data-driven modulation routing: any source can drive any destination by an amount
+
+pub const NUM_SOURCES: usize = 8;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ModSource {
+    Lfo1 = 0,       // Engine LFO, -1..1 before depth
+    Lfo2 = 1,       // Per-voice vibrato LFO, -1..1 (engine-level destinations read 0)
+    ModWheel = 2,   // 0..1
+    Velocity = 3,   // 0..1, per voice (engine-level destinations read the last note)
+    FilterEnv = 4,  // 0..1, engine filter envelope retriggered by note_on
+    Aftertouch = 5, // 0..1
+    Pressure = 6,   // 0..1, MPE per-note pressure (engine-level destinations read 0)
+    Slide = 7,      // 0..1, MPE per-note slide (CC74), likewise
+}
+
+// Units per 1.0 of summed modulation:
+// Pitch = semitones, Cutoff = octaves, Amplitude = gain offset (1 + x),
+// PulseWidth = fraction of the widest swing (0.05..0.95), FmAmount = added FM index
+#[derive(Clone, Copy, PartialEq)]
+pub enum ModDest {
+    Pitch = 0,
+    Cutoff = 1,
+    Amplitude = 2,
+    PulseWidth = 3,
+    FmAmount = 4,
+}
+
+impl ModSource {
+    pub fn from_u8(source: u8) -> Option<Self> {
+        match source {
+            0 => Some(ModSource::Lfo1),
+            1 => Some(ModSource::Lfo2),
+            2 => Some(ModSource::ModWheel),
+            3 => Some(ModSource::Velocity),
+            4 => Some(ModSource::FilterEnv),
+            5 => Some(ModSource::Aftertouch),
+            6 => Some(ModSource::Pressure),
+            7 => Some(ModSource::Slide),
+            _ => None,
+        }
+    }
+}
+
+impl ModDest {
+    pub fn from_u8(dest: u8) -> Option<Self> {
+        match dest {
+            0 => Some(ModDest::Pitch),
+            1 => Some(ModDest::Cutoff),
+            2 => Some(ModDest::Amplitude),
+            3 => Some(ModDest::PulseWidth),
+            4 => Some(ModDest::FmAmount),
+            _ => None,
+        }
+    }
+
+    // Destinations evaluated inside each voice rather than on the engine bus
+    fn is_per_voice(self) -> bool {
+        self != ModDest::Cutoff
+    }
+}
+
+// Value of every source for one sample, indexed by ModSource
+pub type ModSources = [f32; NUM_SOURCES];
+
+#[derive(Clone, Copy)]
+pub struct ModRoute {
+    pub source: ModSource,
+    pub dest: ModDest,
+    pub amount: f32,
+}
+
+pub struct ModMatrix {
+    routes: Vec<ModRoute>,
+    voice_routes: bool,
+    uses_lfo2: bool,
+}
+
+impl ModMatrix {
+    pub fn new() -> Self {
+        ModMatrix {
+            routes: Vec::new(),
+            voice_routes: false,
+            uses_lfo2: false,
+        }
+    }
+
+    // One route per source/destination pair; an amount of 0 removes it
+    pub fn set_route(&mut self, source: ModSource, dest: ModDest, amount: f32) {
+        let amount = amount.clamp(-24.0, 24.0);
+        self.routes.retain(|route| !(route.source == source && route.dest == dest));
+        if amount != 0.0 {
+            self.routes.push(ModRoute { source, dest, amount });
+        }
+        self.update_flags();
+    }
+
+    pub fn clear(&mut self) {
+        self.routes.clear();
+        self.update_flags();
+    }
+
+    pub fn routes(&self) -> &[ModRoute] {
+        &self.routes
+    }
+
+    fn update_flags(&mut self) {
+        self.voice_routes = self.routes.iter().any(|route| route.dest.is_per_voice());
+        self.uses_lfo2 = self.routes.iter().any(|route| route.source == ModSource::Lfo2);
+    }
+
+    pub fn has_voice_routes(&self) -> bool {
+        self.voice_routes
+    }
+
+    pub fn uses_lfo2(&self) -> bool {
+        self.uses_lfo2
+    }
+
+    pub fn has_routes_to(&self, dest: ModDest) -> bool {
+        self.routes.iter().any(|route| route.dest == dest)
+    }
+
+    pub fn sum(&self, dest: ModDest, sources: &ModSources) -> f32 {
+        self.routes
+            .iter()
+            .filter(|route| route.dest == dest)
+            .map(|route| sources[route.source as usize] * route.amount)
+            .sum()
+    }
+}
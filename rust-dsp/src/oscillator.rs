@@ -6,8 +6,14 @@ pub enum Waveform {
     Triangle = 3,
     FM = 4,
     Piano = 5,
+    // Integrated bandlimited impulse train: alias-free up to the top of the keyboard,
+    // at the cost of two sines per sample, several times the PolyBLEP saw
+    BlitSaw = 9,
 }
 
+// Leak of the BLIT integrator, so rounding errors can't build up into DC
+const BLIT_LEAK: f32 = 0.9995;
+
 pub struct Oscillator {
     phase: f32,
     phase_increment: f32,
@@ -15,6 +21,13 @@ pub struct Oscillator {
     sample_rate: f32,
     waveform: Waveform,
     detune: f32,
+    pulse_width: f32,
+    fm_index: f32,      // Effective index: base plus matrix modulation
+    fm_base_index: f32,
+    fm_index_mod: f32,
+    fm_ratio: f32,      // Modulator frequency as a multiple of the carrier
+    modulator_phase: f32,
+    blit_state: f32, // Integrator of the BLIT saw
 }
 
 impl Oscillator {
@@ -26,6 +39,13 @@ impl Oscillator {
             sample_rate,
             waveform: Waveform::Sine,
             detune: 0.0,
+            pulse_width: 0.5,
+            fm_index: 0.3,
+            fm_base_index: 0.3,
+            fm_index_mod: 0.0,
+            fm_ratio: 2.0,
+            modulator_phase: 0.0,
+            blit_state: -0.5,
         }
     }
 
@@ -43,6 +63,7 @@ impl Oscillator {
             3 => Waveform::Triangle,
             4 => Waveform::FM,
             5 => Waveform::Piano,
+            9 => Waveform::BlitSaw,
             _ => Waveform::Sine,
         };
     }
@@ -53,6 +74,38 @@ impl Oscillator {
         self.phase_increment = detuned_freq / self.sample_rate;
     }
 
+    // Modulation offsets from the mod matrix, applied on top of the base shape
+    pub fn set_pulse_width_mod(&mut self, amount: f32) {
+        self.pulse_width = (0.5 + amount * 0.45).clamp(0.05, 0.95);
+    }
+
+    pub fn set_fm_index_mod(&mut self, amount: f32) {
+        self.fm_index_mod = amount;
+        self.fm_index = (self.fm_base_index + amount).clamp(0.0, 10.0);
+    }
+
+    // Two-operator FM settings for the FM waveform
+    pub fn set_fm_ratio(&mut self, ratio: f32) {
+        self.fm_ratio = ratio.clamp(0.125, 16.0);
+    }
+
+    pub fn set_fm_index(&mut self, index: f32) {
+        self.fm_base_index = index.clamp(0.0, 10.0);
+        self.set_fm_index_mod(self.fm_index_mod);
+    }
+
+    pub fn get_fm_ratio(&self) -> f32 {
+        self.fm_ratio
+    }
+
+    pub fn get_fm_index(&self) -> f32 {
+        self.fm_base_index
+    }
+
+    pub fn get_waveform(&self) -> u8 {
+        self.waveform as u8
+    }
+
     pub fn process(&mut self) -> f32 {
         let output = match self.waveform {
             Waveform::Sine => self.sine(),
@@ -61,12 +114,17 @@ impl Oscillator {
             Waveform::Triangle => self.triangle(),
             Waveform::FM => self.fm(),
             Waveform::Piano => self.piano(),
+            Waveform::BlitSaw => self.blit_saw(),
         };
 
         self.phase += self.phase_increment;
         if self.phase >= 1.0 {
             self.phase -= 1.0;
         }
+        self.modulator_phase += self.phase_increment * self.fm_ratio;
+        if self.modulator_phase >= 1.0 {
+            self.modulator_phase -= self.modulator_phase.floor();
+        }
 
         output
     }
@@ -86,9 +144,10 @@ impl Oscillator {
     fn square(&self) -> f32 {
         // PolyBLEP anti-aliased square
         let t = self.phase;
-        let mut output = if t < 0.5 { 1.0 } else { -1.0 };
+        let width = self.pulse_width;
+        let mut output = if t < width { 1.0 } else { -1.0 };
         output += self.poly_blep(t);
-        output -= self.poly_blep((t + 0.5) % 1.0);
+        output -= self.poly_blep((t + 1.0 - width) % 1.0);
         output
     }
 
@@ -111,10 +170,10 @@ impl Oscillator {
     }
 
     fn fm(&self) -> f32 {
-        // Wurlitzer-style FM synthesis
+        // Two-operator FM; the defaults (ratio 2, index 0.3) give the Wurlitzer-like tone
         let carrier = self.phase * 2.0 * std::f32::consts::PI;
-        let modulator = carrier * 2.0;
-        (carrier + 0.3 * modulator.sin()).sin()
+        let modulator = self.modulator_phase * 2.0 * std::f32::consts::PI;
+        (carrier + self.fm_index * modulator.sin()).sin()
     }
 
     fn piano(&self) -> f32 {
@@ -127,6 +186,22 @@ impl Oscillator {
         output / 1.875 // Normalize
     }
 
+    fn blit_saw(&mut self) -> f32 {
+        // One impulse per cycle made of every odd-count harmonic below Nyquist
+        // (sin(pi M t) / (P sin(pi t)), area 1), minus its mean, integrated
+        let period = 1.0 / self.phase_increment.max(1e-6);
+        let harmonics = 2.0 * (period * 0.5).floor() + 1.0;
+        let denominator = (std::f32::consts::PI * self.phase).sin();
+        let blit = if denominator.abs() < 1e-6 {
+            harmonics / period
+        } else {
+            (std::f32::consts::PI * harmonics * self.phase).sin() / (period * denominator)
+        };
+        self.blit_state = self.blit_state * BLIT_LEAK + blit - 1.0 / period;
+        // The integral jumps up and ramps down; flipped and doubled it matches the -1..1 rising saw
+        -2.0 * self.blit_state
+    }
+
     fn poly_blep(&self, t: f32) -> f32 {
         let dt = self.phase_increment;
         if t < dt {
@@ -142,6 +217,54 @@ impl Oscillator {
 
     pub fn reset_phase(&mut self) {
         self.phase = 0.0;
+        self.modulator_phase = 0.0;
+        // The first impulse lifts it to +0.5, so the ramp starts centered
+        self.blit_state = -0.5;
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spectrum::Spectrum;
+
+    // Sidebands above -40 dB; the carrier sits on bin 32 of a 4096-point FFT and
+    // ratio 1 puts every sideband on a multiple of it
+    fn sidebands(index: f32) -> usize {
+        let mut osc = Oscillator::new(48000.0);
+        osc.set_waveform(4);
+        osc.set_frequency(375.0);
+        osc.set_fm_ratio(1.0);
+        osc.set_fm_index(index);
+        let output: Vec<f32> = (0..4096).map(|_| osc.process()).collect();
+        let mut bins = [0.0; 2048];
+        Spectrum::new().analyze(&output, &mut bins);
+        (1..64).filter(|k| bins[32 * k] > -40.0).count()
+    }
+
+    #[test]
+    fn higher_fm_index_adds_sidebands() {
+        let (narrow, wide) = (sidebands(0.5), sidebands(4.0));
+        assert!(narrow >= 2 && wide > narrow + 3, "{narrow} vs {wide}");
+    }
+
+    // Energy in dB of everything off the harmonic series of a 3515.625 Hz saw, which sits
+    // on bin 600 of an 8192-point FFT so its harmonics land on multiples of 600
+    fn aliasing_db(waveform: u8) -> f32 {
+        let mut osc = Oscillator::new(48000.0);
+        osc.set_waveform(waveform);
+        osc.set_frequency(3515.625);
+        let output: Vec<f32> = (0..16384).map(|_| osc.process()).collect();
+        let mut bins = [0.0; 4096];
+        Spectrum::new().analyze(&output[8192..], &mut bins);
+        let off_harmonic = |k: usize| (k % 600).min(600 - k % 600) > 3;
+        let power: f32 = (4..4096).filter(|&k| off_harmonic(k)).map(|k| 10f32.powf(bins[k] / 10.0)).sum();
+        10.0 * power.log10()
+    }
+
+    #[test]
+    fn blit_saw_aliases_less_than_the_polyblep_saw() {
+        let (polyblep, blit) = (aliasing_db(1), aliasing_db(9));
+        assert!(blit < polyblep - 40.0, "{blit} vs {polyblep}");
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Hands out a different default seed to every instance
+static NEXT_SEED: AtomicU64 = AtomicU64::new(12345);
+
+// Seed of one numbered stream of a base seed: the stream id scrambled into the seed,
+// then scrambled again so nested streams (a stream of a stream) can't cancel out
+pub fn stream_seed(seed: u64, stream: u64) -> u64 {
+    split_mix(seed ^ split_mix(stream))
+}
+
+// SplitMix64 step, a bijection that spreads nearby inputs over the whole range
+fn split_mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Small xorshift64* generator owned by each user (LFOs, noise sources)
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Rng { state: 0 };
+        rng.set_seed(seed);
+        rng
+    }
+
+    pub fn next_default() -> Self {
+        Rng::new(NEXT_SEED.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        // Scrambled so nearby seeds give unrelated sequences (and never a zero state)
+        let z = split_mix(seed);
+        self.state = if z == 0 { 0x9E37_79B9_7F4A_7C15 } else { z };
+    }
+
+    // Uniform in 0.0..1.0
+    pub fn random(&mut self) -> f32 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let value = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (value >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
@@ -0,0 +1,149 @@
+use crate::clock::{division_to_samples, NoteEvent};
+
+const STEP_VELOCITY: f32 = 1.0;
+
+// Plays a fixed pattern of MIDI notes (-1 = rest) from the engine clock, each
+// step releasing before the next one starts
+pub struct Sequencer {
+    enabled: bool,
+    steps: Vec<i8>,
+    division: u8,
+    position: usize,
+    sounding: Option<u8>,
+    samples_to_step: f32,
+    samples_to_release: f32,
+    gate: f32, // Fraction of the step the note is held for
+    sample_rate: f32,
+}
+
+impl Sequencer {
+    pub fn new(sample_rate: f32) -> Self {
+        Sequencer {
+            enabled: false,
+            steps: Vec::new(),
+            division: 4,
+            position: 0,
+            sounding: None,
+            samples_to_step: 0.0,
+            samples_to_release: 0.0,
+            gate: 0.5,
+            sample_rate,
+        }
+    }
+
+    pub fn load(&mut self, steps: &[i8]) {
+        self.steps = steps.to_vec();
+        if self.position >= self.steps.len() {
+            self.position = 0;
+        }
+    }
+
+    pub fn get_steps(&self) -> &[i8] {
+        &self.steps
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if enabled && !self.enabled {
+            // Always start from the top of the pattern
+            self.position = 0;
+            self.samples_to_step = 0.0;
+        }
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_division(&mut self, division: u8) {
+        self.division = division;
+    }
+
+    pub fn get_division(&self) -> u8 {
+        self.division
+    }
+
+    fn is_running(&self) -> bool {
+        self.enabled && !self.steps.is_empty()
+    }
+
+    pub fn samples_until_event(&self) -> usize {
+        let mut until = f32::MAX;
+        if self.sounding.is_some() {
+            until = if self.is_running() { self.samples_to_release } else { 0.0 };
+        }
+        if self.is_running() {
+            until = until.min(self.samples_to_step);
+        }
+        if until == f32::MAX {
+            usize::MAX
+        } else {
+            until.max(0.0).ceil() as usize
+        }
+    }
+
+    pub fn advance(&mut self, samples: usize) {
+        self.samples_to_step -= samples as f32;
+        self.samples_to_release -= samples as f32;
+    }
+
+    pub fn fire(&mut self, bpm: f32, events: &mut Vec<NoteEvent>) {
+        if let Some(note) = self.sounding {
+            if !self.is_running() || self.samples_to_release <= 0.0 || self.samples_to_step <= 0.0 {
+                events.push(NoteEvent::NoteOff(note));
+                self.sounding = None;
+            }
+        }
+
+        if self.is_running() && self.samples_to_step <= 0.0 {
+            let step = self.steps[self.position % self.steps.len()];
+            if step >= 0 {
+                events.push(NoteEvent::NoteOn(step as u8, STEP_VELOCITY));
+                self.sounding = Some(step as u8);
+            }
+            self.position = (self.position + 1) % self.steps.len();
+
+            let step_samples = division_to_samples(self.division, bpm, self.sample_rate).max(1.0);
+            self.samples_to_step += step_samples;
+            self.samples_to_release = self.samples_to_step - step_samples * (1.0 - self.gate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn four_steps_cycle_and_rests_stay_silent() {
+        let pattern = [60, -1, 64, 67];
+        let mut sequencer = Sequencer::new(48000.0);
+        sequencer.load(&pattern);
+        sequencer.set_division(4);
+        sequencer.set_enabled(true);
+        let step = division_to_samples(4, 120.0, 48000.0) as usize;
+
+        // Note sounding at every sample over two passes of the pattern
+        let mut sounding = Vec::new();
+        let mut note = None;
+        let mut events = Vec::new();
+        for _ in 0..8 * step {
+            sequencer.fire(120.0, &mut events);
+            for event in events.drain(..) {
+                note = match event {
+                    NoteEvent::NoteOn(n, _) => Some(n),
+                    NoteEvent::NoteOff(_) => None,
+                };
+            }
+            sounding.push(note);
+            sequencer.advance(1);
+        }
+
+        for (i, held) in sounding.chunks(step).enumerate() {
+            let expected = u8::try_from(pattern[i % 4]).ok();
+            // Held for the first half of the step, released for the rest
+            assert!(held[..step / 2].iter().all(|&n| n == expected), "step {i}");
+            assert!(held[step / 2..].iter().all(|n| n.is_none()), "step {i}");
+        }
+    }
+}
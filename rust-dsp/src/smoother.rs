@@ -0,0 +1,57 @@
+// One-pole parameter smoother so setter calls between blocks ramp instead of stepping
+pub struct Smoother {
+    current: f32,
+    target: f32,
+    coeff: f32,
+    time_ms: f32,
+    sample_rate: f32,
+}
+
+impl Smoother {
+    pub fn new(sample_rate: f32, initial: f32, time_ms: f32) -> Self {
+        let mut smoother = Smoother {
+            current: initial,
+            target: initial,
+            coeff: 1.0,
+            time_ms,
+            sample_rate,
+        };
+        smoother.set_time(time_ms);
+        smoother
+    }
+
+    pub fn set_time(&mut self, time_ms: f32) {
+        self.time_ms = time_ms;
+        let samples = time_ms.max(0.0) * self.sample_rate / 1000.0;
+        self.coeff = if samples < 1.0 {
+            1.0
+        } else {
+            1.0 - (-1.0 / samples).exp()
+        };
+    }
+
+    // Keeps the same time in ms at the new rate
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.set_time(self.time_ms);
+    }
+
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target;
+    }
+
+    // Jumps straight to a value with no ramp
+    pub fn reset(&mut self, value: f32) {
+        self.current = value;
+        self.target = value;
+    }
+
+    pub fn get_target(&self) -> f32 {
+        self.target
+    }
+
+    pub fn next(&mut self) -> f32 {
+        self.current += (self.target - self.current) * self.coeff;
+        self.current
+    }
+}
@@ -0,0 +1,112 @@
+use std::f32::consts::PI;
+
+const MIN_DB: f32 = -120.0;
+
+// Hann-windowed magnitude spectrum, only computed on request.
+// The scratch buffers are kept so repeated calls don't allocate.
+pub struct Spectrum {
+    re: Vec<f32>,
+    im: Vec<f32>,
+}
+
+impl Spectrum {
+    pub fn new() -> Self {
+        Spectrum {
+            re: Vec::new(),
+            im: Vec::new(),
+        }
+    }
+
+    // Writes magnitudes in dB (a full-scale sine reads 0 dB) for the lowest
+    // bins.len() bins of an FFT twice that size, rounded up to a power of two.
+    // The newest samples are used; short input is zero padded at the front.
+    pub fn analyze(&mut self, input: &[f32], bins: &mut [f32]) {
+        if bins.is_empty() {
+            return;
+        }
+        let size = (bins.len() * 2).next_power_of_two();
+        let used = input.len().min(size);
+        let pad = size - used;
+
+        self.re.clear();
+        self.re.resize(size, 0.0);
+        self.im.clear();
+        self.im.resize(size, 0.0);
+
+        let mut window_sum = 0.0;
+        for i in 0..size {
+            let window = 0.5 - 0.5 * (2.0 * PI * i as f32 / size as f32).cos();
+            window_sum += window;
+            if i >= pad {
+                self.re[i] = input[input.len() - used + (i - pad)] * window;
+            }
+        }
+
+        fft(&mut self.re, &mut self.im);
+
+        let scale = 2.0 / window_sum;
+        for (k, bin) in bins.iter_mut().enumerate() {
+            let magnitude = (self.re[k] * self.re[k] + self.im[k] * self.im[k]).sqrt() * scale;
+            *bin = if magnitude > 0.0 { (20.0 * magnitude.log10()).max(MIN_DB) } else { MIN_DB };
+        }
+    }
+}
+
+// In-place iterative radix-2 FFT; the length must be a power of two
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sine_peaks_in_its_bin() {
+        let sine: Vec<f32> = (0..2048).map(|i| (2.0 * PI * 1000.0 * i as f32 / 48000.0).sin()).collect();
+        let mut bins = [0.0; 1024];
+        Spectrum::new().analyze(&sine, &mut bins);
+
+        // 1000 Hz * 2048 / 48000 = bin 42.7
+        let peak = (0..bins.len()).max_by(|&a, &b| bins[a].total_cmp(&bins[b])).unwrap();
+        assert_eq!(peak, 43);
+        // Full scale reads 0 dB, less the Hann scalloping between bins
+        assert!(bins[peak] > -1.5 && bins[peak] < 0.1, "{} dB", bins[peak]);
+        // The Hann window keeps the leakage down away from the peak
+        assert!(bins[..33].iter().chain(&bins[54..]).all(|&db| db < -60.0));
+    }
+}
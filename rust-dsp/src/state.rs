@@ -0,0 +1,788 @@
+use serde::{Deserialize, Serialize};
+
+use crate::effects::glide::GlideMode;
+use crate::lfo::LfoPolarity;
+use crate::clock::DEFAULT_TEMPO_BPM;
+use crate::effects::gate::MAX_GATE_STEPS;
+use crate::{AudioEngine, Engine, RetriggerMode, DEFAULT_ENGINE_VOLUME};
+
+// Bump when the preset layout changes and add a migration step in parse_preset
+pub const PRESET_VERSION: u32 = 1;
+
+// Snapshot of every user-facing parameter, in the same units the setters take
+// Missing fields fall back to engine defaults and unknown fields are ignored
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct SynthState {
+    pub master_volume: f32,
+    pub live_volume: f32,
+    pub timeline_volume: f32,
+    pub master_tune: f32,
+    pub transpose: i8,
+    pub tempo: f32,
+    pub duck_enabled: bool,
+    pub duck_amount: f32,
+    pub duck_release: f32,
+    pub dc_blocker: bool,
+    pub width: f32,
+    pub live: EngineState,
+    pub timeline: EngineState,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct EngineState {
+    pub waveform: u8,
+    pub velocity_layer: u8, // High-velocity waveform; u8::MAX = waveform only
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+    pub detune: f32,
+    pub glide_time: f32,
+    pub glide_mode: u8,
+    pub envelope_delay: f32, // ms
+    pub envelope_hold: f32,  // ms
+    pub velocity_to_attack: f32,
+    pub envelope_mode: u8,
+    pub fm_ratio: f32,
+    pub fm_index: f32,
+    pub retrigger_mode: u8,
+    pub vibrato_rate: f32,
+    pub vibrato_depth: f32,
+    pub vibrato_fade: f32,
+    pub filter_enabled: bool,
+    pub filter_cutoff: f32,
+    pub filter_resonance: f32,
+    pub filter_q: Option<f32>, // None = set from filter_resonance
+    pub filter_mode: u8,
+    pub filter_morph: f32,
+    pub filter_vowel: f32,
+    pub filter_band_low: f32,
+    pub filter_band_high: f32,
+    pub filter_fm_from_note: bool,
+    pub filter_fm_amount: f32,
+    pub filter_peak_gain: f32,
+    pub filter_shelf_gain: f32,
+    pub filter_drive: f32,
+    pub filter_slope: u8,
+    pub filter_model: u8,
+    pub filter_per_voice: bool,
+    pub filter_keytrack: f32,
+    pub lfo_to_filter: bool,
+    pub lfo_polarity: u8,
+    pub lfo_filter_depth: f32,
+    pub lfo_rate: f32,
+    pub lfo_depth: f32,
+    pub lfo_waveform: u8,
+    pub lfo_one_shot: bool,
+    pub lfo_phase: f32,
+    pub env_follow: f32,
+    pub env_follow_attack: f32,
+    pub env_follow_release: f32,
+    pub delay_enabled: bool,
+    pub delay_max_time: f32,
+    pub delay_time: f32,
+    pub delay_time_right: Option<f32>, // None = same as delay_time
+    pub delay_feedback: f32,
+    pub delay_mix: f32,
+    pub delay_feedback_tone: f32,
+    pub delay_wow: f32,
+    pub delay_wow_rate: f32,
+    pub delay_saturation: f32,
+    pub delay_time_change: u8,
+    pub delay_ping_pong: bool,
+    pub delay_sync: Option<u8>, // None = delay_time in ms
+    pub delay_kill_dry: bool,
+    pub delay_reverse: bool,
+    pub delay_ducking: f32,
+    pub delay_ducking_release: f32,
+    pub delay_taps: Vec<DelayTapState>, // Empty = single echo
+    pub delay_feedback_tap: u8,
+    pub ensemble_enabled: bool,
+    pub ensemble_intensity: f32,
+    pub ensemble_depth: f32,
+    pub reverb_enabled: bool,
+    pub reverb_room_size: f32,
+    pub reverb_damping: f32,
+    pub reverb_saturation: bool,
+    pub reverb_shimmer: f32,
+    pub reverb_mix: f32,
+    pub reverb_predelay: f32, // ms
+    pub reverb_width: f32,
+    pub reverb_early_late: f32,
+    pub convolution_enabled: bool, // The IR itself isn't saved
+    pub convolution_mix: f32,
+    pub tremolo_enabled: bool,
+    pub tremolo_rate: f32,
+    pub tremolo_depth: f32,
+    pub flanger_enabled: bool,
+    pub flanger_rate: f32,
+    pub flanger_depth: f32,
+    pub flanger_feedback: f32,
+    pub flanger_mix: f32,
+    pub ringmod_enabled: bool,
+    pub ringmod_frequency: f32,
+    pub ringmod_mix: f32,
+    pub formant_enabled: bool,
+    pub formant_vowel: u8,
+    pub formant_morph: f32,
+    pub pitchshift_enabled: bool,
+    pub pitchshift_semitones: f32,
+    pub pitchshift_mix: f32,
+    pub freqshift_enabled: bool,
+    pub freqshift_hz: f32,
+    pub freqshift_mix: f32,
+    pub oversampling: u8,
+    pub gate_enabled: bool,
+    pub gate_division: u8,
+    pub gate_pattern: Vec<bool>,
+    pub autopan_enabled: bool,
+    pub autopan_rate: f32,
+    pub autopan_depth: f32,
+    pub noisegate_enabled: bool,
+    pub noisegate_threshold: f32, // dB
+    pub noisegate_attack: f32,
+    pub noisegate_hold: f32,
+    pub noisegate_release: f32,
+    pub delay_level: f32,
+    pub reverb_level: f32,
+    pub tremolo_level: f32,
+    pub flanger_level: f32,
+    pub filter_env_attack: f32,
+    pub filter_env_decay: f32,
+    pub filter_env_sustain: f32,
+    pub filter_env_release: f32,
+    pub arp_enabled: bool,
+    pub arp_mode: u8,
+    pub arp_division: u8,
+    pub sequencer_enabled: bool,
+    pub sequencer_division: u8,
+    pub sequence: Vec<i8>,
+    pub mod_routes: Vec<ModRouteState>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct DelayTapState {
+    pub time: f32,
+    pub level: f32,
+    pub pan: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ModRouteState {
+    pub source: u8,
+    pub dest: u8,
+    pub amount: f32,
+}
+
+// Plain values matching a new engine, so parsing a preset doesn't build one
+impl Default for SynthState {
+    fn default() -> Self {
+        SynthState {
+            master_volume: 1.0,
+            live_volume: DEFAULT_ENGINE_VOLUME / 0.3,
+            timeline_volume: DEFAULT_ENGINE_VOLUME / 0.3,
+            master_tune: 0.0,
+            transpose: 0,
+            tempo: DEFAULT_TEMPO_BPM,
+            duck_enabled: false,
+            duck_amount: 0.5,
+            duck_release: 200.0,
+            dc_blocker: true,
+            width: 1.0,
+            live: EngineState::default(),
+            timeline: EngineState::default(),
+        }
+    }
+}
+
+impl Default for EngineState {
+    fn default() -> Self {
+        EngineState {
+            waveform: 0,
+            velocity_layer: u8::MAX,
+            attack: 0.01,
+            decay: 0.3,
+            sustain: 0.7,
+            release: 0.5,
+            detune: 0.0,
+            glide_time: 0.0,
+            glide_mode: 0,
+            envelope_delay: 0.0,
+            envelope_hold: 0.0,
+            velocity_to_attack: 0.0,
+            envelope_mode: 0,
+            fm_ratio: 2.0,
+            fm_index: 0.3,
+            retrigger_mode: 0,
+            vibrato_rate: 5.0,
+            vibrato_depth: 0.0,
+            vibrato_fade: 0.0,
+            filter_enabled: false,
+            filter_cutoff: 20000.0,
+            filter_resonance: 0.0,
+            filter_q: None,
+            filter_mode: 0,
+            filter_morph: 0.0,
+            filter_vowel: 0.0,
+            filter_band_low: 300.0,
+            filter_band_high: 3000.0,
+            filter_fm_from_note: false,
+            filter_fm_amount: 0.0,
+            filter_peak_gain: 0.0,
+            filter_shelf_gain: 0.0,
+            filter_drive: 0.0,
+            filter_slope: 2,
+            filter_model: 0,
+            filter_per_voice: false,
+            filter_keytrack: 0.0,
+            lfo_to_filter: false,
+            lfo_polarity: 1,
+            lfo_filter_depth: 1.0,
+            lfo_rate: 1.0,
+            lfo_depth: 0.0,
+            lfo_waveform: 0,
+            lfo_one_shot: false,
+            lfo_phase: 0.0,
+            env_follow: 0.0,
+            env_follow_attack: 5.0,
+            env_follow_release: 150.0,
+            delay_enabled: false,
+            delay_max_time: 2000.0,
+            delay_time: 0.5,
+            delay_time_right: None,
+            delay_feedback: 0.3,
+            delay_mix: 0.3,
+            delay_feedback_tone: 20000.0,
+            delay_wow: 0.0,
+            delay_wow_rate: 0.7,
+            delay_saturation: 0.0,
+            delay_time_change: 0,
+            delay_ping_pong: false,
+            delay_sync: None,
+            delay_kill_dry: false,
+            delay_reverse: false,
+            delay_ducking: 0.0,
+            delay_ducking_release: 300.0,
+            delay_taps: Vec::new(),
+            delay_feedback_tap: u8::MAX,
+            ensemble_enabled: false,
+            ensemble_intensity: 0.5,
+            ensemble_depth: 0.5,
+            reverb_enabled: false,
+            reverb_room_size: 0.5,
+            reverb_damping: 0.5,
+            reverb_saturation: false,
+            reverb_shimmer: 0.0,
+            reverb_mix: 0.04,
+            reverb_predelay: 0.0,
+            reverb_width: 1.0,
+            reverb_early_late: 1.0,
+            convolution_enabled: false,
+            convolution_mix: 0.3,
+            tremolo_enabled: false,
+            tremolo_rate: 5.0,
+            tremolo_depth: 0.5,
+            flanger_enabled: false,
+            flanger_rate: 1.0,
+            flanger_depth: 5.0,
+            flanger_feedback: 0.3,
+            flanger_mix: 0.5,
+            ringmod_enabled: false,
+            ringmod_frequency: 440.0,
+            ringmod_mix: 0.5,
+            formant_enabled: false,
+            formant_vowel: 0,
+            formant_morph: 0.0,
+            pitchshift_enabled: false,
+            pitchshift_semitones: 0.0,
+            pitchshift_mix: 0.5,
+            freqshift_enabled: false,
+            freqshift_hz: 0.0,
+            freqshift_mix: 0.5,
+            oversampling: 1,
+            gate_enabled: false,
+            gate_division: 4,
+            gate_pattern: vec![true; MAX_GATE_STEPS],
+            autopan_enabled: false,
+            autopan_rate: 0.5,
+            autopan_depth: 1.0,
+            noisegate_enabled: false,
+            noisegate_threshold: -50.0,
+            noisegate_attack: 1.0,
+            noisegate_hold: 50.0,
+            noisegate_release: 100.0,
+            delay_level: 1.0,
+            reverb_level: 1.0,
+            tremolo_level: 1.0,
+            flanger_level: 1.0,
+            filter_env_attack: 0.01,
+            filter_env_decay: 0.3,
+            filter_env_sustain: 0.7,
+            filter_env_release: 0.5,
+            arp_enabled: false,
+            arp_mode: 0,
+            arp_division: 4,
+            sequencer_enabled: false,
+            sequencer_division: 4,
+            sequence: Vec::new(),
+            mod_routes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Preset {
+    #[serde(default)]
+    version: u32,
+    #[serde(flatten)]
+    state: SynthState,
+}
+
+pub fn preset_to_json(state: &SynthState) -> String {
+    let preset = Preset {
+        version: PRESET_VERSION,
+        state: state.clone(),
+    };
+    // Plain data with no maps keyed by non-strings, so this cannot fail
+    serde_json::to_string(&preset).unwrap_or_default()
+}
+
+pub fn parse_preset(json: &str) -> Result<SynthState, String> {
+    let preset: Preset = serde_json::from_str(json).map_err(|e| format!("invalid preset: {}", e))?;
+
+    // Version 0 (no version field) has the same layout as version 1, and newer
+    // versions keep the fields we know about since unknown ones are skipped.
+    // Migrations for older layouts go here once PRESET_VERSION moves past 1.
+    Ok(preset.state)
+}
+
+impl Engine {
+    pub(crate) fn get_state(&self) -> EngineState {
+        // Voice settings are always applied to every voice, so the first one is representative
+        let voice = &self.voices[0];
+        let (attack, decay, sustain, release) = voice.get_adsr();
+        let (vibrato_rate, vibrato_depth, vibrato_fade) = voice.get_vibrato();
+        let (fm_ratio, fm_index) = voice.get_fm();
+        let (filter_env_attack, filter_env_decay, filter_env_sustain, filter_env_release) =
+            self.filter_env.get_adsr();
+
+        EngineState {
+            waveform: voice.get_waveform(),
+            velocity_layer: voice.get_velocity_layer().unwrap_or(u8::MAX),
+            attack,
+            decay,
+            sustain,
+            release,
+            detune: self.detune_cents,
+            glide_time: voice.get_glide_time(),
+            glide_mode: self.glide_mode as u8,
+            envelope_delay: voice.get_delay_hold().0,
+            envelope_hold: voice.get_delay_hold().1,
+            velocity_to_attack: voice.get_velocity_to_attack(),
+            envelope_mode: voice.get_envelope_mode(),
+            fm_ratio,
+            fm_index,
+            retrigger_mode: self.retrigger_mode as u8,
+            vibrato_rate,
+            vibrato_depth,
+            vibrato_fade,
+            filter_enabled: self.filter_enabled,
+            filter_cutoff: self.base_filter_cutoff,
+            filter_resonance: self.filter.get_resonance(),
+            filter_q: self.filter.get_q_setting(),
+            filter_mode: self.filter.get_mode(),
+            filter_morph: self.filter.get_morph(),
+            filter_vowel: self.filter.get_vowel(),
+            filter_band_low: self.filter.get_band_range().0,
+            filter_band_high: self.filter.get_band_range().1,
+            filter_fm_from_note: self.filter.get_fm().0,
+            filter_fm_amount: self.filter.get_fm().1,
+            filter_peak_gain: self.filter.get_peak_gain_db(),
+            filter_shelf_gain: self.filter.get_shelf_gain_db(),
+            filter_drive: self.filter.get_drive(),
+            filter_slope: self.filter.get_slope(),
+            filter_model: self.filter.get_model(),
+            filter_per_voice: self.filter_per_voice,
+            filter_keytrack: self.filter_keytrack,
+            lfo_to_filter: self.lfo_to_filter,
+            lfo_polarity: self.lfo_polarity as u8,
+            lfo_filter_depth: self.lfo_filter_octaves,
+            env_follow: self.env_follow_octaves,
+            env_follow_attack: self.env_follower.get_attack(),
+            env_follow_release: self.env_follower.get_release(),
+            lfo_rate: self.lfo.get_rate(),
+            lfo_depth: self.lfo.get_depth(),
+            lfo_waveform: self.lfo.get_waveform(),
+            lfo_one_shot: self.lfo.is_one_shot(),
+            lfo_phase: self.lfo.get_phase_offset(),
+            delay_enabled: self.delay_enabled,
+            delay_max_time: self.delay.get_max_delay(),
+            delay_time: self.delay.get_delay_time(),
+            delay_time_right: Some(self.delay.get_delay_time_right())
+                .filter(|&right| right != self.delay.get_delay_time()),
+            delay_feedback: self.delay.get_feedback(),
+            delay_mix: self.delay.get_mix(),
+            delay_feedback_tone: self.delay.get_feedback_tone(),
+            delay_wow: self.delay.get_wow(),
+            delay_wow_rate: self.delay.get_wow_rate(),
+            delay_saturation: self.delay.get_saturation(),
+            delay_time_change: self.delay.get_time_change_mode(),
+            delay_ping_pong: self.delay.get_ping_pong(),
+            delay_sync: self.delay.get_sync(),
+            delay_kill_dry: self.delay.get_kill_dry(),
+            delay_reverse: self.delay.get_reverse(),
+            delay_ducking: self.delay.get_ducking().0,
+            delay_ducking_release: self.delay.get_ducking().1,
+            delay_taps: self
+                .delay
+                .get_taps()
+                .into_iter()
+                .map(|(time, level, pan)| DelayTapState { time, level, pan })
+                .collect(),
+            delay_feedback_tap: self.delay.get_feedback_tap(),
+            ensemble_enabled: self.ensemble_enabled,
+            ensemble_intensity: self.ensemble.get_intensity(),
+            ensemble_depth: self.ensemble.get_depth(),
+            reverb_enabled: self.reverb_enabled,
+            reverb_room_size: self.reverb.get_room_size(),
+            reverb_damping: self.reverb.get_damping(),
+            reverb_saturation: self.reverb.get_saturation(),
+            reverb_shimmer: self.reverb.get_shimmer(),
+            reverb_mix: self.reverb.get_mix(),
+            reverb_predelay: self.reverb.get_predelay(),
+            reverb_width: self.reverb.get_width(),
+            reverb_early_late: self.reverb.get_early_late_balance(),
+            convolution_enabled: self.convolution_enabled,
+            convolution_mix: self.convolution.get_mix(),
+            tremolo_enabled: self.tremolo_enabled,
+            tremolo_rate: self.tremolo.get_rate(),
+            tremolo_depth: self.tremolo.get_depth(),
+            flanger_enabled: self.flanger_enabled,
+            flanger_rate: self.flanger.get_lfo_rate(),
+            flanger_depth: self.flanger.get_delay_range(),
+            flanger_feedback: self.flanger.get_feedback(),
+            flanger_mix: self.flanger.get_mix(),
+            ringmod_enabled: self.ringmod_enabled,
+            ringmod_frequency: self.ringmod.get_frequency(),
+            ringmod_mix: self.ringmod.get_mix(),
+            formant_enabled: self.formant_enabled,
+            formant_vowel: self.formant.get_vowel(),
+            formant_morph: self.formant.get_morph(),
+            pitchshift_enabled: self.pitchshift_enabled,
+            pitchshift_semitones: self.pitchshift.get_semitones(),
+            pitchshift_mix: self.pitchshift.get_mix(),
+            freqshift_enabled: self.freqshift_enabled,
+            freqshift_hz: self.freqshift.get_shift_hz(),
+            freqshift_mix: self.freqshift.get_mix(),
+            oversampling: self.oversampler.get_factor(),
+            gate_enabled: self.gate_enabled,
+            gate_division: self.gate.get_division(),
+            gate_pattern: self.gate.get_pattern().to_vec(),
+            autopan_enabled: self.autopan_enabled,
+            autopan_rate: self.autopan.get_rate(),
+            autopan_depth: self.autopan.get_depth(),
+            noisegate_enabled: self.noisegate_enabled,
+            noisegate_threshold: self.noisegate.get_threshold_db(),
+            noisegate_attack: self.noisegate.get_attack_ms(),
+            noisegate_hold: self.noisegate.get_hold_ms(),
+            noisegate_release: self.noisegate.get_release_ms(),
+            delay_level: self.delay_level,
+            reverb_level: self.reverb_level,
+            tremolo_level: self.tremolo_level,
+            flanger_level: self.flanger_level,
+            filter_env_attack,
+            filter_env_decay,
+            filter_env_sustain,
+            filter_env_release,
+            arp_enabled: self.arp.is_enabled(),
+            arp_mode: self.arp.get_mode(),
+            arp_division: self.arp.get_division(),
+            sequencer_enabled: self.sequencer.is_enabled(),
+            sequencer_division: self.sequencer.get_division(),
+            sequence: self.sequencer.get_steps().to_vec(),
+            mod_routes: self
+                .mod_matrix
+                .routes()
+                .iter()
+                .map(|route| ModRouteState {
+                    source: route.source as u8,
+                    dest: route.dest as u8,
+                    amount: route.amount,
+                })
+                .collect(),
+        }
+    }
+
+    pub(crate) fn apply_state(&mut self, state: &EngineState) {
+        for voice in &mut self.voices {
+            voice.set_waveform(state.waveform);
+            if state.velocity_layer != u8::MAX {
+                voice.set_velocity_layer(state.waveform, state.velocity_layer);
+            }
+            voice.set_adsr(state.attack, state.decay, state.sustain, state.release);
+            voice.set_detune(state.detune);
+            voice.set_glide_time(state.glide_time);
+            voice.set_delay_hold(state.envelope_delay, state.envelope_hold);
+            voice.set_envelope_mode(state.envelope_mode);
+            voice.set_velocity_to_attack(state.velocity_to_attack);
+            voice.set_fm(state.fm_ratio, state.fm_index);
+            voice.set_vibrato(state.vibrato_rate, state.vibrato_depth, state.vibrato_fade);
+        }
+        self.detune_cents = state.detune;
+        self.retrigger_mode = RetriggerMode::from_u8(state.retrigger_mode);
+        self.glide_mode = GlideMode::from_u8(state.glide_mode);
+
+        self.filter_enabled = state.filter_enabled;
+        self.set_filter_cutoff(state.filter_cutoff);
+        self.configure_filters(|filter| {
+            match state.filter_q {
+                Some(q) => filter.set_q(q),
+                None => filter.set_resonance(state.filter_resonance),
+            }
+            filter.set_mode(state.filter_mode);
+            filter.set_morph(state.filter_morph);
+            filter.set_vowel(state.filter_vowel);
+            filter.set_band_range(state.filter_band_low, state.filter_band_high);
+            filter.set_fm(state.filter_fm_from_note, state.filter_fm_amount);
+            filter.set_peak_gain_db(state.filter_peak_gain);
+            filter.set_shelf_gain_db(state.filter_shelf_gain);
+            filter.set_drive(state.filter_drive);
+            filter.set_slope(state.filter_slope);
+            filter.set_model(state.filter_model);
+        });
+        self.set_filter_per_voice(state.filter_per_voice);
+        self.set_filter_keytrack(state.filter_keytrack);
+        self.lfo_to_filter = state.lfo_to_filter;
+        self.lfo_polarity = LfoPolarity::from_u8(state.lfo_polarity);
+        self.lfo_filter_octaves = state.lfo_filter_depth.clamp(0.0, 8.0);
+        self.set_env_follow(state.env_follow);
+        self.env_follower.set_attack(state.env_follow_attack);
+        self.env_follower.set_release(state.env_follow_release);
+
+        self.lfo.set_rate(state.lfo_rate);
+        self.lfo.set_depth(state.lfo_depth);
+        self.lfo.set_waveform(state.lfo_waveform);
+        self.lfo.set_one_shot(state.lfo_one_shot);
+        self.lfo.set_phase_offset(state.lfo_phase);
+
+        self.delay_enabled = state.delay_enabled;
+        self.delay.set_max_delay(state.delay_max_time);
+        self.delay.set_delay_time_lr(state.delay_time, state.delay_time_right.unwrap_or(state.delay_time));
+        self.delay.set_feedback(state.delay_feedback);
+        self.delay.set_mix(state.delay_mix);
+        self.delay.set_feedback_tone(state.delay_feedback_tone);
+        self.delay.set_wow(state.delay_wow);
+        self.delay.set_wow_rate(state.delay_wow_rate);
+        self.delay.set_saturation(state.delay_saturation);
+        self.delay.set_time_change_mode(state.delay_time_change);
+        self.delay.set_ping_pong(state.delay_ping_pong);
+        self.delay.set_sync(state.delay_sync);
+        self.delay.set_kill_dry(state.delay_kill_dry);
+        self.delay.set_reverse(state.delay_reverse);
+        self.delay.set_ducking(state.delay_ducking, state.delay_ducking_release);
+        let times: Vec<f32> = state.delay_taps.iter().map(|tap| tap.time).collect();
+        let levels: Vec<f32> = state.delay_taps.iter().map(|tap| tap.level).collect();
+        let pans: Vec<f32> = state.delay_taps.iter().map(|tap| tap.pan).collect();
+        self.delay.set_taps(&times, &levels);
+        self.delay.set_tap_pans(&pans);
+        self.delay.set_feedback_tap(state.delay_feedback_tap);
+
+        self.ensemble_enabled = state.ensemble_enabled;
+        self.ensemble.set_intensity(state.ensemble_intensity);
+        self.ensemble.set_depth(state.ensemble_depth);
+
+        self.reverb_enabled = state.reverb_enabled;
+        self.reverb.set_room_size(state.reverb_room_size);
+        self.reverb.set_damping(state.reverb_damping);
+        self.reverb.set_saturation(state.reverb_saturation);
+        self.reverb.set_shimmer(state.reverb_shimmer);
+        self.reverb.set_mix(state.reverb_mix);
+        self.reverb.set_predelay(state.reverb_predelay);
+        self.reverb.set_width(state.reverb_width);
+        self.reverb.set_early_late_balance(state.reverb_early_late);
+
+        self.convolution_enabled = state.convolution_enabled;
+        self.convolution.set_mix(state.convolution_mix);
+
+        self.tremolo_enabled = state.tremolo_enabled;
+        self.tremolo.set_rate(state.tremolo_rate);
+        self.tremolo.set_depth(state.tremolo_depth);
+
+        self.flanger_enabled = state.flanger_enabled;
+        self.flanger.set_lfo_rate(state.flanger_rate);
+        self.flanger.set_delay_range(state.flanger_depth);
+        self.flanger.set_feedback(state.flanger_feedback);
+        self.flanger.set_mix(state.flanger_mix);
+
+        self.ringmod_enabled = state.ringmod_enabled;
+        self.ringmod.set_frequency(state.ringmod_frequency);
+        self.ringmod.set_mix(state.ringmod_mix);
+
+        self.formant_enabled = state.formant_enabled;
+        self.formant.set_vowel(state.formant_vowel);
+        self.formant.set_morph(state.formant_morph);
+
+        self.pitchshift_enabled = state.pitchshift_enabled;
+        self.pitchshift.set_semitones(state.pitchshift_semitones);
+        self.pitchshift.set_mix(state.pitchshift_mix);
+
+        self.freqshift_enabled = state.freqshift_enabled;
+        self.freqshift.set_shift_hz(state.freqshift_hz);
+        self.freqshift.set_mix(state.freqshift_mix);
+
+        self.set_oversampling(state.oversampling);
+
+        self.gate.set_pattern(&state.gate_pattern);
+        self.set_gate(state.gate_enabled, state.gate_division);
+
+        self.autopan_enabled = state.autopan_enabled;
+        self.autopan.set_rate(state.autopan_rate);
+        self.autopan.set_depth(state.autopan_depth);
+
+        self.noisegate.set_threshold_db(state.noisegate_threshold);
+        self.noisegate.set_attack_ms(state.noisegate_attack);
+        self.noisegate.set_hold_ms(state.noisegate_hold);
+        self.noisegate.set_release_ms(state.noisegate_release);
+        self.set_noisegate(state.noisegate_enabled);
+
+        self.delay_level = state.delay_level.clamp(0.0, 1.0);
+        self.reverb_level = state.reverb_level.clamp(0.0, 1.0);
+        self.tremolo_level = state.tremolo_level.clamp(0.0, 1.0);
+        self.flanger_level = state.flanger_level.clamp(0.0, 1.0);
+
+        self.filter_env.set_adsr(
+            state.filter_env_attack,
+            state.filter_env_decay,
+            state.filter_env_sustain,
+            state.filter_env_release,
+        );
+        self.set_arp(state.arp_enabled, state.arp_mode, state.arp_division);
+        self.sequencer.load(&state.sequence);
+        self.sequencer.set_enabled(state.sequencer_enabled);
+        self.sequencer.set_division(state.sequencer_division);
+        self.clear_mod_routes();
+        for route in &state.mod_routes {
+            self.set_mod_route(route.source, route.dest, route.amount);
+        }
+    }
+}
+
+impl AudioEngine {
+    pub(crate) fn snapshot(&self) -> SynthState {
+        SynthState {
+            master_volume: self.master_volume.get_target(),
+            // Stored scaled to 0-0.3, reported in the 0-1 range the setters take
+            live_volume: self.live_volume / 0.3,
+            timeline_volume: self.timeline_volume / 0.3,
+            master_tune: self.live_engine.tuning.get_master_tune(),
+            transpose: self.live_engine.tuning.get_transpose(),
+            tempo: self.live_engine.tempo_bpm,
+            duck_enabled: self.duck_enabled,
+            duck_amount: self.duck.get_amount(),
+            duck_release: self.duck.get_release(),
+            dc_blocker: self.dc_blocker_enabled,
+            width: self.widener.get_width(),
+            live: self.live_engine.get_state(),
+            timeline: self.timeline_engine.get_state(),
+        }
+    }
+
+    pub(crate) fn restore(&mut self, state: &SynthState) {
+        self.set_master_volume(state.master_volume);
+        self.set_live_volume(state.live_volume);
+        self.set_timeline_volume(state.timeline_volume);
+        self.set_master_tune(state.master_tune);
+        self.set_transpose(state.transpose);
+        self.set_tempo(state.tempo);
+        self.set_sidechain_duck(state.duck_enabled, state.duck_amount, state.duck_release);
+        self.set_dc_blocker(state.dc_blocker);
+        self.set_width(state.width);
+        self.live_engine.apply_state(&state.live);
+        self.timeline_engine.apply_state(&state.timeline);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{MAX_VOICES_PER_ENGINE, SAMPLE_RATE};
+
+    #[test]
+    fn defaults_match_a_new_engine() {
+        assert_eq!(SynthState::default(), AudioEngine::new().snapshot());
+        assert_eq!(EngineState::default(), Engine::new(SAMPLE_RATE, MAX_VOICES_PER_ENGINE).get_state());
+    }
+
+    // Plays a chord through the restored engine and the original, seeded alike
+    fn render(engine: &mut AudioEngine) -> Vec<f32> {
+        engine.set_random_seed(3);
+        for note in [57, 60, 64] {
+            engine.note_on(note, 0.7);
+        }
+        let mut output = vec![0.0; 24000];
+        for block in output.chunks_mut(128) {
+            engine.process(block);
+        }
+        output
+    }
+
+    fn configured_engine() -> AudioEngine {
+        let mut engine = AudioEngine::new();
+        engine.set_master_volume(0.8);
+        engine.set_waveform(1);
+        engine.set_adsr(0.02, 0.2, 0.6, 0.3);
+        engine.set_detune(7.0);
+        engine.set_filter_enabled(true);
+        engine.set_filter_cutoff(1500.0);
+        engine.set_filter_resonance(0.6);
+        engine.set_filter_mode(2);
+        engine.set_lfo_rate(3.0);
+        engine.set_lfo_depth(0.5);
+        engine.set_lfo_to_filter(true);
+        engine.set_delay(true, 120.0, 0.4, 0.3);
+        engine.set_reverb(true, 0.7, 0.4);
+        engine.set_reverb_mix(0.3);
+        engine.set_tremolo(true, 4.0, 0.3);
+        engine.set_mod_route(3, 1, 0.5);
+        engine
+    }
+
+    #[test]
+    fn restored_state_reproduces_the_output() {
+        let mut original = configured_engine();
+        let state = original.snapshot();
+
+        let mut restored = AudioEngine::new();
+        restored.restore(&state);
+        assert_eq!(restored.snapshot(), state);
+        assert_eq!(render(&mut restored), render(&mut original));
+    }
+
+    #[test]
+    fn exported_preset_imports_to_the_same_parameters() {
+        let original = configured_engine();
+        let json = original.export_preset();
+        assert!(json.contains(&format!("\"version\":{}", PRESET_VERSION)));
+
+        let mut imported = AudioEngine::new();
+        imported.restore(&parse_preset(&json).unwrap());
+        assert_eq!(imported.snapshot(), original.snapshot());
+    }
+
+    #[test]
+    fn malformed_preset_is_an_error() {
+        assert!(parse_preset("{\"master_volume\": 0.5").is_err());
+        assert!(parse_preset("not json").is_err());
+        assert!(parse_preset("{\"master_volume\": \"loud\"}").is_err());
+    }
+
+    #[test]
+    fn unknown_and_missing_preset_fields_are_tolerated() {
+        let state = parse_preset("{\"version\": 7, \"master_volume\": 0.5, \"future_knob\": 1}").unwrap();
+        assert_eq!(state.master_volume, 0.5);
+        assert_eq!(state.live, EngineState::default());
+    }
+}
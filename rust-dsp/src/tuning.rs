@@ -0,0 +1,108 @@
+pub struct Tuning {
+    master_tune_cents: f32,
+    transpose: i8,
+    pitch_class_cents: [f32; 12],
+    note_frequencies: Option<Vec<f32>>,
+}
+
+impl Tuning {
+    pub fn new() -> Self {
+        Tuning {
+            master_tune_cents: 0.0,
+            transpose: 0,
+            pitch_class_cents: [0.0; 12],
+            note_frequencies: None,
+        }
+    }
+
+    pub fn set_master_tune(&mut self, cents: f32) {
+        // +/- 1 semitone covers A=415..466Hz (A=432Hz is about -31.8 cents)
+        self.master_tune_cents = cents.clamp(-100.0, 100.0);
+    }
+
+    pub fn set_transpose(&mut self, semitones: i8) {
+        self.transpose = semitones.clamp(-48, 48);
+    }
+
+    pub fn get_master_tune(&self) -> f32 {
+        self.master_tune_cents
+    }
+
+    pub fn get_transpose(&self) -> i8 {
+        self.transpose
+    }
+
+    // Cent offsets from equal temperament per pitch class (C, C#, ... B)
+    // Anything other than 12 entries is ignored
+    pub fn set_pitch_class_cents(&mut self, cents_per_note: &[f32]) {
+        if cents_per_note.len() != 12 {
+            return;
+        }
+        for (offset, cents) in self.pitch_class_cents.iter_mut().zip(cents_per_note) {
+            *offset = cents.clamp(-100.0, 100.0);
+        }
+    }
+
+    // Absolute frequency for every MIDI note, overrides the pitch class table
+    // Anything other than 128 entries is ignored
+    pub fn set_note_frequencies(&mut self, frequencies: &[f32]) {
+        if frequencies.len() != 128 {
+            return;
+        }
+        self.note_frequencies = Some(frequencies.iter().map(|f| f.clamp(1.0, 20000.0)).collect());
+    }
+
+    pub fn reset(&mut self) {
+        self.pitch_class_cents = [0.0; 12];
+        self.note_frequencies = None;
+    }
+
+    pub fn note_to_freq(&self, midi_note: u8) -> f32 {
+        let note = (midi_note as i32 + self.transpose as i32).clamp(0, 127) as usize;
+        let master_ratio = 2.0_f32.powf(self.master_tune_cents / 1200.0);
+
+        if let Some(frequencies) = &self.note_frequencies {
+            return frequencies[note] * master_ratio;
+        }
+
+        let cents = self.pitch_class_cents[note % 12];
+        440.0 * 2.0_f32.powf((note as f32 + cents / 100.0 - 69.0) / 12.0) * master_ratio
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Just intonation from C: 16/15, 9/8, 6/5, 5/4, 4/3, 45/32, 3/2, 8/5, 5/3, 9/5, 15/8
+    const JUST_CENTS: [f32; 12] = [
+        0.0, 11.73, 3.91, 15.64, -13.69, -1.96, -9.78, 1.96, 13.69, -15.64, 17.6, -11.73,
+    ];
+
+    #[test]
+    fn just_major_third_is_flat_of_equal_temperament() {
+        let equal = Tuning::new();
+        let mut just = Tuning::new();
+        just.set_pitch_class_cents(&JUST_CENTS);
+
+        // E above middle C lands on 5/4 of C
+        let third = just.note_to_freq(64);
+        assert!(third < equal.note_to_freq(64));
+        assert!((third / just.note_to_freq(60) - 1.25).abs() < 1e-4);
+
+        just.reset();
+        assert_eq!(just.note_to_freq(64), equal.note_to_freq(64));
+    }
+
+    #[test]
+    fn frequency_table_overrides_every_note() {
+        let mut tuning = Tuning::new();
+        let table: Vec<f32> = (0..128).map(|note| 100.0 + note as f32).collect();
+        tuning.set_note_frequencies(&table);
+        assert_eq!(tuning.note_to_freq(69), 169.0);
+
+        // Wrong lengths are ignored
+        tuning.set_note_frequencies(&[440.0; 12]);
+        assert_eq!(tuning.note_to_freq(0), 100.0);
+    }
+}
@@ -1,62 +1,227 @@
 use crate::oscillator::Oscillator;
 use crate::envelope::Envelope;
 use crate::effects::glide::Glide;
+use crate::lfo::Lfo;
+use crate::filter::{keytrack_ratio, FilterChain};
+use crate::modmatrix::{ModDest, ModMatrix, ModSource, ModSources};
+use crate::smoother::Smoother;
+
+const EXPRESSION_SMOOTHING_MS: f32 = 5.0; // MPE bend/pressure/slide arrive in steps
+const MAX_BEND_SEMITONES: f32 = 48.0;
 
 pub struct Voice {
     oscillator: Oscillator,
+    // High-velocity layer, phase-locked to the main oscillator; only run while its
+    // waveform differs, and crossfaded in by velocity
+    layer: Oscillator,
+    layered: bool,
     envelope: Envelope,
     glide: Glide,
+    filter: FilterChain, // Only used when the engine runs per-voice filters
+    keytrack_amount: f32,
+    keytrack: f32, // Cutoff multiplier from the note pitch
+    note_freq: f32,
     active: bool,
+    held: bool,
+    note: u8,
     age: f32,
     velocity: f32,
+    vibrato: Lfo,
+    vibrato_depth_cents: f32,
+    vibrato_fade_samples: f32,
+    vibrato_fade_pos: f32,
+    vibrato_scale: f32,
+    velocity_to_attack: f32,
+    // MPE expression, only applied while the voice has an id
+    id: u32,
+    bend: Smoother,
+    bend_ratio: f32,
+    pressure: Smoother,
+    slide: Smoother,
+    sample_rate: f32,
 }
 
 impl Voice {
     pub fn new(sample_rate: f32) -> Self {
+        let mut vibrato = Lfo::new(sample_rate);
+        vibrato.set_rate(5.0);
+        vibrato.set_depth(1.0);
+
         Voice {
             oscillator: Oscillator::new(sample_rate),
+            layer: Oscillator::new(sample_rate),
+            layered: false,
             envelope: Envelope::new(sample_rate),
             glide: Glide::new(sample_rate),
+            filter: FilterChain::new(sample_rate),
+            keytrack_amount: 0.0,
+            keytrack: 1.0,
+            note_freq: 440.0,
             active: false,
+            held: false,
+            note: 0,
             age: 0.0,
             velocity: 1.0,
+            vibrato,
+            vibrato_depth_cents: 0.0,
+            vibrato_fade_samples: 0.0,
+            vibrato_fade_pos: 0.0,
+            vibrato_scale: 1.0,
+            velocity_to_attack: 0.0,
+            id: 0,
+            bend: Smoother::new(sample_rate, 0.0, EXPRESSION_SMOOTHING_MS),
+            bend_ratio: 1.0,
+            pressure: Smoother::new(sample_rate, 0.0, EXPRESSION_SMOOTHING_MS),
+            slide: Smoother::new(sample_rate, 0.0, EXPRESSION_SMOOTHING_MS),
+            sample_rate,
         }
     }
 
-    pub fn note_on(&mut self, frequency: f32, velocity: f32) {
+    pub fn note_on(&mut self, note: u8, frequency: f32, velocity: f32) {
         // Use glide for smooth frequency transitions
         self.glide.set_target(frequency);
         
         // Only reset phase if this is a new note (not retriggering)
         if !self.active {
             self.oscillator.reset_phase();
+            self.layer.reset_phase();
+            self.filter.reset();
         }
         
         self.velocity = velocity;
+        self.note = note;
+        self.note_freq = frequency;
+        self.keytrack = keytrack_ratio(frequency, self.keytrack_amount);
+        self.filter.set_note_frequency(frequency);
+        if self.vibrato_depth_cents > 0.0 {
+            self.vibrato.retrigger_scattered(0.25);
+            self.vibrato_fade_pos = 0.0;
+        }
+        self.envelope.set_attack_scale(1.0 - self.velocity_to_attack * velocity);
         self.envelope.gate_on();
         self.active = true;
+        self.held = true;
         self.age = 0.0;
+        self.id = 0;
+        self.bend.reset(0.0);
+        self.bend_ratio = 1.0;
+        self.pressure.reset(0.0);
+        self.slide.reset(0.0);
+    }
+
+    // Restarts the glide of the note just started from another pitch, or none for no glide
+    pub fn set_glide_start(&mut self, frequency: Option<f32>) {
+        match frequency {
+            Some(frequency) => {
+                self.glide.jump_to(frequency);
+                self.glide.set_target(self.note_freq);
+            }
+            None => self.glide.jump_to(self.note_freq),
+        }
+    }
+
+    // Marks the note just started as an MPE note, addressable by id
+    pub fn set_id(&mut self, id: u32) {
+        self.id = id;
+    }
+
+    pub fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn set_bend(&mut self, semitones: f32) {
+        self.bend.set_target(semitones.clamp(-MAX_BEND_SEMITONES, MAX_BEND_SEMITONES));
+    }
+
+    pub fn set_pressure(&mut self, pressure: f32) {
+        self.pressure.set_target(pressure.clamp(0.0, 1.0));
+    }
+
+    pub fn set_slide(&mut self, slide: f32) {
+        self.slide.set_target(slide.clamp(0.0, 1.0));
     }
 
     pub fn note_off(&mut self) {
+        self.held = false;
         self.envelope.gate_off();
     }
 
-    pub fn process(&mut self, output: &mut [f32]) {
+    // mod_frames holds the engine-level mod sources for each sample of the block
+    // With cutoffs given, the voice runs through its own filter before it is summed
+    pub fn process(
+        &mut self,
+        output: &mut [f32],
+        mod_frames: &[ModSources],
+        matrix: &ModMatrix,
+        cutoffs: Option<&[f32]>,
+    ) {
         if !self.active && !self.envelope.is_active() {
             return;
         }
 
         self.age += 1.0;
 
-        for sample in output.iter_mut() {
+        let vibrato_on = self.vibrato_depth_cents > 0.0 && self.vibrato_scale > 0.0;
+        let cutoff_mod = cutoffs.is_some() && matrix.has_routes_to(ModDest::Cutoff);
+        let voice_mod = matrix.has_voice_routes() || cutoff_mod;
+        let run_lfo2 = vibrato_on || (voice_mod && matrix.uses_lfo2());
+
+        for (i, (sample, frame)) in output.iter_mut().zip(mod_frames).enumerate() {
+            let lfo2 = if run_lfo2 { self.vibrato.process() } else { 0.0 };
+
             // Process glide and update oscillator frequency
-            let current_freq = self.glide.process();
+            let mut current_freq = self.glide.process();
+            let (mut pressure, mut slide) = (0.0, 0.0);
+            if self.id != 0 {
+                self.bend_ratio = (self.bend.next() / 12.0).exp2();
+                current_freq *= self.bend_ratio;
+                pressure = self.pressure.next();
+                slide = self.slide.next();
+            }
+            if vibrato_on {
+                current_freq *= self.vibrato_ratio(lfo2);
+            }
+
+            let mut gain = 1.0;
+            let mut cutoff_octaves = 0.0;
+            if voice_mod {
+                let mut sources = *frame;
+                sources[ModSource::Lfo2 as usize] = lfo2;
+                sources[ModSource::Velocity as usize] = self.velocity;
+                sources[ModSource::Pressure as usize] = pressure;
+                sources[ModSource::Slide as usize] = slide;
+
+                let semitones = matrix.sum(ModDest::Pitch, &sources);
+                if semitones != 0.0 {
+                    current_freq *= (semitones / 12.0).exp2();
+                }
+                gain = (1.0 + matrix.sum(ModDest::Amplitude, &sources)).max(0.0);
+                let (pulse_width, fm_index) =
+                    (matrix.sum(ModDest::PulseWidth, &sources), matrix.sum(ModDest::FmAmount, &sources));
+                self.oscillator.set_pulse_width_mod(pulse_width);
+                self.oscillator.set_fm_index_mod(fm_index);
+                if self.layered {
+                    self.layer.set_pulse_width_mod(pulse_width);
+                    self.layer.set_fm_index_mod(fm_index);
+                }
+                if cutoff_mod {
+                    cutoff_octaves = matrix.sum(ModDest::Cutoff, &sources);
+                }
+            }
             self.oscillator.set_frequency(current_freq);
             
-            let osc_out = self.oscillator.process();
+            let mut osc_out = self.oscillator.process();
+            if self.layered {
+                self.layer.set_frequency(current_freq);
+                let high = self.layer.process();
+                osc_out += (high - osc_out) * self.velocity;
+            }
             let env_out = self.envelope.process();
-            *sample += osc_out * env_out * self.velocity;
+            let mut voice_out = osc_out * env_out * self.velocity * gain;
+            if let Some(cutoffs) = cutoffs {
+                voice_out = self.filter.process(voice_out, cutoffs[i] * self.keytrack * cutoff_octaves.exp2());
+            }
+            *sample += voice_out;
 
             if !self.envelope.is_active() {
                 self.active = false;
@@ -64,20 +229,79 @@ impl Voice {
         }
     }
 
+    fn vibrato_ratio(&mut self, lfo_value: f32) -> f32 {
+        let fade = if self.vibrato_fade_pos < self.vibrato_fade_samples {
+            self.vibrato_fade_pos += 1.0;
+            self.vibrato_fade_pos / self.vibrato_fade_samples
+        } else {
+            1.0
+        };
+        let cents = lfo_value * self.vibrato_depth_cents * self.vibrato_scale * fade;
+        2.0_f32.powf(cents / 1200.0)
+    }
+
+    pub fn filter_mut(&mut self) -> &mut FilterChain {
+        &mut self.filter
+    }
+
+    pub fn set_filter_keytrack(&mut self, amount: f32) {
+        self.keytrack_amount = amount;
+        self.keytrack = keytrack_ratio(self.note_freq, amount);
+    }
+
     pub fn is_active(&self) -> bool {
         self.active || self.envelope.is_active()
     }
 
+    // A single waveform, ending any velocity layering
     pub fn set_waveform(&mut self, waveform: u8) {
         self.oscillator.set_waveform(waveform);
+        self.layer.set_waveform(waveform);
+        self.layered = false;
+    }
+
+    // Velocity 0 plays low_wave, 1 plays high_wave, with a linear crossfade between
+    pub fn set_velocity_layer(&mut self, low_wave: u8, high_wave: u8) {
+        self.oscillator.set_waveform(low_wave);
+        self.layer.set_waveform(high_wave);
+        self.layered = self.oscillator.get_waveform() != self.layer.get_waveform();
+    }
+
+    // High-velocity waveform, None while a single waveform plays
+    pub fn get_velocity_layer(&self) -> Option<u8> {
+        self.layered.then(|| self.layer.get_waveform())
     }
 
     pub fn set_adsr(&mut self, attack: f32, decay: f32, sustain: f32, release: f32) {
         self.envelope.set_adsr(attack, decay, sustain, release);
     }
 
-    pub fn get_frequency(&self) -> f32 {
-        self.glide.get_frequency()
+    // 0 = ADSR, 1 = one-shot AD: the voice decays to silence and frees itself even while held
+    pub fn set_envelope_mode(&mut self, mode: u8) {
+        self.envelope.set_mode(mode);
+    }
+
+    pub fn get_envelope_mode(&self) -> u8 {
+        self.envelope.get_mode()
+    }
+
+    // 0 = fixed attack, 1 = a full-velocity hit starts instantly
+    pub fn set_velocity_to_attack(&mut self, amount: f32) {
+        self.velocity_to_attack = amount.clamp(0.0, 1.0);
+    }
+
+    pub fn get_velocity_to_attack(&self) -> f32 {
+        self.velocity_to_attack
+    }
+
+    // DAHDSR delay before the attack and hold after it, in ms
+    pub fn set_delay_hold(&mut self, delay_ms: f32, hold_ms: f32) {
+        self.envelope.set_delay(delay_ms);
+        self.envelope.set_hold(hold_ms);
+    }
+
+    pub fn get_delay_hold(&self) -> (f32, f32) {
+        self.envelope.get_delay_hold()
     }
 
     pub fn get_age(&self) -> f32 {
@@ -85,15 +309,91 @@ impl Voice {
     }
 
     pub fn is_releasing(&self) -> bool {
-        !self.active && self.envelope.is_active()
+        !self.held && self.envelope.is_active()
+    }
+
+    // True between note_on and note_off (key still down)
+    pub fn is_held(&self) -> bool {
+        self.held && self.is_active()
+    }
+
+    pub fn get_note(&self) -> u8 {
+        self.note
+    }
+
+    // Current (glided) pitch with MPE bend but without vibrato or pitch modulation, 0 when silent
+    pub fn get_frequency(&self) -> f32 {
+        if self.is_active() { self.glide.get_current() * self.bend_ratio } else { 0.0 }
+    }
+
+    // Envelope level, before velocity; 0 when silent
+    pub fn get_amplitude(&self) -> f32 {
+        if self.is_active() { self.envelope.get_value() } else { 0.0 }
     }
 
     pub fn set_glide_time(&mut self, time_ms: f32) {
         self.glide.set_glide_time(time_ms);
     }
 
+    pub fn set_vibrato(&mut self, rate_hz: f32, depth_cents: f32, fade_ms: f32) {
+        self.vibrato.set_rate(rate_hz);
+        self.vibrato_depth_cents = depth_cents.clamp(0.0, 100.0);
+        self.vibrato_fade_samples = fade_ms.max(0.0) * self.sample_rate / 1000.0;
+    }
+
+    pub fn get_waveform(&self) -> u8 {
+        self.oscillator.get_waveform()
+    }
+
+    pub fn get_adsr(&self) -> (f32, f32, f32, f32) {
+        self.envelope.get_adsr()
+    }
+
+    pub fn get_glide_time(&self) -> f32 {
+        self.glide.get_glide_time()
+    }
+
+    // Real-time scaling of the vibrato depth from a performance controller
+    pub fn set_vibrato_scale(&mut self, scale: f32) {
+        self.vibrato_scale = scale.clamp(0.0, 1.0);
+    }
+
+    // (rate Hz, depth cents, fade ms)
+    pub fn get_vibrato(&self) -> (f32, f32, f32) {
+        (
+            self.vibrato.get_rate(),
+            self.vibrato_depth_cents,
+            self.vibrato_fade_samples * 1000.0 / self.sample_rate,
+        )
+    }
+
+    // Back to the unmodulated pulse width and FM index once no route drives them
+    pub fn clear_modulation(&mut self) {
+        for oscillator in [&mut self.oscillator, &mut self.layer] {
+            oscillator.set_pulse_width_mod(0.0);
+            oscillator.set_fm_index_mod(0.0);
+        }
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.vibrato.set_seed(seed);
+    }
+
     pub fn set_detune(&mut self, cents: f32) {
         self.oscillator.set_detune(cents);
+        self.layer.set_detune(cents);
+    }
+
+    pub fn set_fm(&mut self, ratio: f32, index: f32) {
+        for oscillator in [&mut self.oscillator, &mut self.layer] {
+            oscillator.set_fm_ratio(ratio);
+            oscillator.set_fm_index(index);
+        }
+    }
+
+    // (ratio, index)
+    pub fn get_fm(&self) -> (f32, f32) {
+        (self.oscillator.get_fm_ratio(), self.oscillator.get_fm_index())
     }
 }
 